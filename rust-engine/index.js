@@ -246,10 +246,24 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { TurboScanner, TurboAggregator, Deduplicator, setLightweightMode, isLightweightMode } = nativeBinding
+const { TurboScanner, TurboAggregator, Deduplicator, setLightweightMode, isLightweightMode, setDeterministic, isDeterministic, computeRealizedSlippage, opportunitiesToColumnar, columnarToOpportunities, opportunityProfitRatio, diffOpportunities, opportunitiesToProto, opportunitiesFromProto, pricesToBytes, pricesFromBytes, engineMetrics, applySlippage } = nativeBinding
 
 module.exports.TurboScanner = TurboScanner
 module.exports.TurboAggregator = TurboAggregator
 module.exports.Deduplicator = Deduplicator
 module.exports.setLightweightMode = setLightweightMode
 module.exports.isLightweightMode = isLightweightMode
+module.exports.setDeterministic = setDeterministic
+module.exports.isDeterministic = isDeterministic
+module.exports.computeRealizedSlippage = computeRealizedSlippage
+module.exports.opportunitiesToColumnar = opportunitiesToColumnar
+module.exports.columnarToOpportunities = columnarToOpportunities
+module.exports.opportunityProfitRatio = opportunityProfitRatio
+module.exports.diffOpportunities = diffOpportunities
+module.exports.pricesToBytes = pricesToBytes
+module.exports.pricesFromBytes = pricesFromBytes
+module.exports.engineMetrics = engineMetrics
+module.exports.applySlippage = applySlippage
+// Only present when built with the "proto" feature
+module.exports.opportunitiesToProto = opportunitiesToProto
+module.exports.opportunitiesFromProto = opportunitiesFromProto
@@ -0,0 +1,253 @@
+// Columnar export for opportunities
+// One object with parallel typed columns instead of one JS object per row
+
+use napi::bindgen_prelude::{Float64Array, Int32Array, Uint32Array};
+use napi_derive::napi;
+use crate::{Error, Opportunity};
+
+/// Opportunities as parallel columns (Arrow-style).
+///
+/// `path` and `dexes` are flattened: row `i` owns
+/// `path_tokens[path_offsets[i]..path_offsets[i + 1]]`, and likewise for
+/// `dex_offsets`/`dex_names`. Offset arrays therefore hold `len + 1`
+/// entries and start at 0. All other columns hold exactly `len` entries.
+/// Offsets and numeric columns are typed arrays whose buffers are handed
+/// to JS without copying. `timestamp` and `price_timestamp` are stored as
+/// float64 ms, which is exact up to 2^53; a missing `price_timestamp` is
+/// NaN.
+#[napi(object)]
+pub struct ColumnarBatch {
+    pub len: u32,
+    pub path_offsets: Uint32Array,
+    pub path_tokens: Vec<String>,
+    pub dex_offsets: Uint32Array,
+    pub dex_names: Vec<String>,
+    pub input_amount: Vec<String>,
+    pub output_amount: Vec<String>,
+    pub profit: Vec<String>,
+    pub profit_bps: Int32Array,
+    pub timestamp: Float64Array,
+    pub liquidity: Vec<Option<String>>,
+    pub price_timestamp: Float64Array,
+}
+
+/// `ColumnarBatch` with plain vectors for the typed-array columns, so the
+/// encoding can be built and checked without a JS runtime
+#[derive(Debug, Clone, Default)]
+struct Columns {
+    len: u32,
+    path_offsets: Vec<u32>,
+    path_tokens: Vec<String>,
+    dex_offsets: Vec<u32>,
+    dex_names: Vec<String>,
+    input_amount: Vec<String>,
+    output_amount: Vec<String>,
+    profit: Vec<String>,
+    profit_bps: Vec<i32>,
+    timestamp: Vec<f64>,
+    liquidity: Vec<Option<String>>,
+    price_timestamp: Vec<f64>,
+}
+
+impl From<Columns> for ColumnarBatch {
+    fn from(columns: Columns) -> Self {
+        Self {
+            len: columns.len,
+            path_offsets: Uint32Array::new(columns.path_offsets),
+            path_tokens: columns.path_tokens,
+            dex_offsets: Uint32Array::new(columns.dex_offsets),
+            dex_names: columns.dex_names,
+            input_amount: columns.input_amount,
+            output_amount: columns.output_amount,
+            profit: columns.profit,
+            profit_bps: Int32Array::new(columns.profit_bps),
+            timestamp: Float64Array::new(columns.timestamp),
+            liquidity: columns.liquidity,
+            price_timestamp: Float64Array::new(columns.price_timestamp),
+        }
+    }
+}
+
+impl From<ColumnarBatch> for Columns {
+    fn from(batch: ColumnarBatch) -> Self {
+        Self {
+            len: batch.len,
+            path_offsets: batch.path_offsets.to_vec(),
+            path_tokens: batch.path_tokens,
+            dex_offsets: batch.dex_offsets.to_vec(),
+            dex_names: batch.dex_names,
+            input_amount: batch.input_amount,
+            output_amount: batch.output_amount,
+            profit: batch.profit,
+            profit_bps: batch.profit_bps.to_vec(),
+            timestamp: batch.timestamp.to_vec(),
+            liquidity: batch.liquidity,
+            price_timestamp: batch.price_timestamp.to_vec(),
+        }
+    }
+}
+
+#[napi]
+pub fn opportunities_to_columnar(opps: Vec<Opportunity>) -> ColumnarBatch {
+    encode_columns(opps).into()
+}
+
+/// Rebuild row objects from a `ColumnarBatch`, validating the offset encoding
+#[napi]
+pub fn columnar_to_opportunities(batch: ColumnarBatch) -> napi::Result<Vec<Opportunity>> {
+    Ok(decode_columns(batch.into())?)
+}
+
+fn encode_columns(opps: Vec<Opportunity>) -> Columns {
+    let len = opps.len();
+    let mut path_offsets = Vec::with_capacity(len + 1);
+    let mut dex_offsets = Vec::with_capacity(len + 1);
+    let mut path_tokens = Vec::new();
+    let mut dex_names = Vec::new();
+    let mut input_amount = Vec::with_capacity(len);
+    let mut output_amount = Vec::with_capacity(len);
+    let mut profit = Vec::with_capacity(len);
+    let mut profit_bps = Vec::with_capacity(len);
+    let mut timestamp = Vec::with_capacity(len);
+    let mut liquidity = Vec::with_capacity(len);
+    let mut price_timestamp = Vec::with_capacity(len);
+
+    path_offsets.push(0);
+    dex_offsets.push(0);
+
+    for opp in opps {
+        path_tokens.extend(opp.path);
+        path_offsets.push(path_tokens.len() as u32);
+        dex_names.extend(opp.dexes);
+        dex_offsets.push(dex_names.len() as u32);
+        input_amount.push(opp.input_amount);
+        output_amount.push(opp.output_amount);
+        profit.push(opp.profit);
+        profit_bps.push(opp.profit_bps);
+        timestamp.push(opp.timestamp as f64);
+        liquidity.push(opp.liquidity);
+        price_timestamp.push(opp.price_timestamp.map_or(f64::NAN, |ts| ts as f64));
+    }
+
+    Columns {
+        len: len as u32,
+        path_offsets,
+        path_tokens,
+        dex_offsets,
+        dex_names,
+        input_amount,
+        output_amount,
+        profit,
+        profit_bps,
+        timestamp,
+        liquidity,
+        price_timestamp,
+    }
+}
+
+fn decode_columns(columns: Columns) -> Result<Vec<Opportunity>, Error> {
+    let len = columns.len as usize;
+    let columns_ok = columns.path_offsets.len() == len + 1
+        && columns.dex_offsets.len() == len + 1
+        && columns.input_amount.len() == len
+        && columns.output_amount.len() == len
+        && columns.profit.len() == len
+        && columns.profit_bps.len() == len
+        && columns.timestamp.len() == len
+        && columns.liquidity.len() == len
+        && columns.price_timestamp.len() == len;
+    if !columns_ok {
+        return Err(Error::InvalidInput("columnar batch has mismatched column lengths".to_string()));
+    }
+
+    let slice = |offsets: &[u32], values: &[String], i: usize| -> Result<Vec<String>, Error> {
+        let (start, end) = (offsets[i] as usize, offsets[i + 1] as usize);
+        values
+            .get(start..end)
+            .map(|s| s.to_vec())
+            .ok_or_else(|| Error::InvalidInput(format!("invalid offsets for row {}", i)))
+    };
+
+    let mut input_amount = columns.input_amount.into_iter();
+    let mut output_amount = columns.output_amount.into_iter();
+    let mut profit = columns.profit.into_iter();
+    let mut liquidity = columns.liquidity.into_iter();
+    let mut opps = Vec::with_capacity(len);
+
+    for i in 0..len {
+        opps.push(Opportunity {
+            path: slice(&columns.path_offsets, &columns.path_tokens, i)?,
+            dexes: slice(&columns.dex_offsets, &columns.dex_names, i)?,
+            input_amount: input_amount.next().unwrap_or_default(),
+            output_amount: output_amount.next().unwrap_or_default(),
+            profit: profit.next().unwrap_or_default(),
+            profit_bps: columns.profit_bps[i],
+            timestamp: columns.timestamp[i] as i64,
+            liquidity: liquidity.next().flatten(),
+            price_timestamp: Some(columns.price_timestamp[i]).filter(|ts| !ts.is_nan()).map(|ts| ts as i64),
+        });
+    }
+
+    Ok(opps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columnar_round_trip() {
+        let opps = vec![
+            Opportunity {
+                path: vec!["WETH".to_string(), "USDC".to_string(), "WETH".to_string()],
+                dexes: vec!["uniswap".to_string(), "sushiswap".to_string()],
+                input_amount: "1000".to_string(),
+                output_amount: "1010".to_string(),
+                profit: "10".to_string(),
+                profit_bps: 100,
+                timestamp: 1_700_000_000_000,
+                liquidity: Some("50000".to_string()),
+                price_timestamp: None,
+            },
+            Opportunity {
+                path: vec!["DAI".to_string(), "USDC".to_string()],
+                dexes: vec!["curve".to_string()],
+                input_amount: "5".to_string(),
+                output_amount: "6".to_string(),
+                profit: "1".to_string(),
+                profit_bps: -25,
+                timestamp: 42,
+                liquidity: None,
+                price_timestamp: Some(40),
+            },
+        ];
+
+        let batch = encode_columns(opps.clone());
+        assert_eq!(batch.len, 2);
+        assert_eq!(&batch.path_offsets[..], &[0, 3, 5]);
+        assert_eq!(&batch.dex_offsets[..], &[0, 2, 3]);
+        assert_eq!(&batch.profit_bps[..], &[100, -25]);
+        assert!(batch.price_timestamp[0].is_nan());
+
+        let rebuilt = decode_columns(batch).unwrap();
+        assert_eq!(rebuilt.len(), opps.len());
+        for (a, b) in rebuilt.iter().zip(opps.iter()) {
+            assert_eq!(a.path, b.path);
+            assert_eq!(a.dexes, b.dexes);
+            assert_eq!(a.input_amount, b.input_amount);
+            assert_eq!(a.output_amount, b.output_amount);
+            assert_eq!(a.profit, b.profit);
+            assert_eq!(a.profit_bps, b.profit_bps);
+            assert_eq!(a.timestamp, b.timestamp);
+            assert_eq!(a.liquidity, b.liquidity);
+            assert_eq!(a.price_timestamp, b.price_timestamp);
+        }
+    }
+
+    #[test]
+    fn test_columnar_rejects_bad_offsets() {
+        let mut batch = encode_columns(Vec::new());
+        batch.len = 1;
+        assert!(decode_columns(batch).is_err());
+    }
+}
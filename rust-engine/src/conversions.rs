@@ -0,0 +1,126 @@
+// Basis-point conversions
+// One place for the bps <-> ratio math so every profit adjustment rounds the same way
+
+use rust_decimal::Decimal;
+
+/// Basis points in a ratio of 1.0
+pub(crate) const BPS_SCALE: f64 = 10_000.0;
+
+/// `bps / 10000`. Exact for every `i32`, since it fits in an f64 mantissa.
+pub fn bps_to_ratio(bps: i32) -> f64 {
+    bps as f64 / BPS_SCALE
+}
+
+/// `ratio * 10000`, rounded half away from zero (2.5 bps -> 3, -2.5 -> -3).
+/// Saturates at `i32::MIN`/`i32::MAX` (including for infinities); NaN maps to 0.
+pub fn ratio_to_bps(ratio: f64) -> i32 {
+    // `as` already saturates and maps NaN to 0; keep that explicit
+    if ratio.is_nan() {
+        return 0;
+    }
+    (ratio * BPS_SCALE).round().clamp(i32::MIN as f64, i32::MAX as f64) as i32
+}
+
+/// Scale an integer amount by `(10000 + bps) / 10000`, rounding down.
+///
+/// Exact over the whole `u128` range: the amount is split into whole units
+/// of 10000 and a remainder so the intermediate product cannot overflow
+/// unless the result itself does, in which case it saturates at
+/// `u128::MAX`. A `bps` of -10000 or below yields 0.
+pub fn apply_bps(amount: u128, bps: i32) -> u128 {
+    let factor = 10_000i64 + bps as i64;
+    if factor <= 0 {
+        return 0;
+    }
+    let factor = factor as u128;
+
+    if let Some(product) = amount.checked_mul(factor) {
+        return product / 10_000;
+    }
+
+    let (whole, rest) = (amount / 10_000, amount % 10_000);
+    whole
+        .checked_mul(factor)
+        .and_then(|scaled| scaled.checked_add(rest * factor / 10_000))
+        .unwrap_or(u128::MAX)
+}
+
+/// `bps / 10000` as an exact decimal (four decimal places, no rounding)
+pub(crate) fn bps_to_decimal_ratio(bps: i32) -> Decimal {
+    Decimal::new(bps as i64, 4)
+}
+
+/// `apply_bps` for decimal amounts: `amount * (10000 + bps) / 10000`,
+/// exact unless the product needs more than `Decimal`'s 28 significant
+/// digits. A `bps` of -10000 or below yields 0, as in `apply_bps`; `None`
+/// only if the product overflows.
+pub(crate) fn apply_bps_decimal(amount: Decimal, bps: i32) -> Option<Decimal> {
+    let factor = Decimal::ONE + bps_to_decimal_ratio(bps);
+    if factor <= Decimal::ZERO {
+        return Some(Decimal::ZERO);
+    }
+    amount.checked_mul(factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bps_round_trip_is_stable() {
+        // Strided sweep over the full i32 range plus the edges
+        let samples = (i32::MIN..=i32::MAX)
+            .step_by(65_537)
+            .chain([i32::MIN, -10_001, -10_000, -1, 0, 1, 10_000, i32::MAX]);
+        for bps in samples {
+            let back = ratio_to_bps(bps_to_ratio(bps));
+            assert!((back as i64 - bps as i64).abs() <= 1, "{} -> {}", bps, back);
+        }
+
+        // And ratio -> bps -> ratio stays within half a bps
+        let mut ratio = -3.0;
+        while ratio < 3.0 {
+            let err = (bps_to_ratio(ratio_to_bps(ratio)) - ratio).abs();
+            assert!(err <= 0.5 / BPS_SCALE + 1e-12, "{}", ratio);
+            ratio += 0.000_037;
+        }
+    }
+
+    #[test]
+    fn test_ratio_to_bps_rounding_and_saturation() {
+        assert_eq!(ratio_to_bps(0.00025), 3);
+        assert_eq!(ratio_to_bps(-0.00025), -3);
+        assert_eq!(ratio_to_bps(0.00024), 2);
+        assert_eq!(ratio_to_bps(1e12), i32::MAX);
+        assert_eq!(ratio_to_bps(f64::NEG_INFINITY), i32::MIN);
+        assert_eq!(ratio_to_bps(f64::NAN), 0);
+    }
+
+    #[test]
+    fn test_apply_bps() {
+        assert_eq!(apply_bps(1_000_000, 30), 1_003_000);
+        assert_eq!(apply_bps(1_000_000, -30), 997_000);
+        // Rounds down
+        assert_eq!(apply_bps(999, 1), 999);
+        assert_eq!(apply_bps(1_000, -10_000), 0);
+        assert_eq!(apply_bps(1_000, i32::MIN), 0);
+
+        // Large amounts take the split path without losing precision
+        let big = u128::MAX / 2;
+        assert_eq!(apply_bps(big, 0), big);
+        assert_eq!(apply_bps(big, -5_000), big / 2);
+        assert_eq!(apply_bps(u128::MAX, 1), u128::MAX);
+        assert_eq!(apply_bps(u128::MAX, i32::MAX), u128::MAX);
+    }
+
+    #[test]
+    fn test_apply_bps_decimal() {
+        let amount = Decimal::new(10125, 1);
+        assert_eq!(apply_bps_decimal(amount, -30), Some(Decimal::new(100946250, 5)));
+        assert_eq!(apply_bps_decimal(amount, 0), Some(amount));
+        assert_eq!(apply_bps_decimal(amount, -10_000), Some(Decimal::ZERO));
+        assert_eq!(apply_bps_decimal(amount, i32::MIN), Some(Decimal::ZERO));
+        assert_eq!(apply_bps_decimal(Decimal::MAX, 1), None);
+        assert_eq!(bps_to_decimal_ratio(-25), Decimal::new(-25, 4));
+    }
+}
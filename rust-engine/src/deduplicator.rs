@@ -1,25 +1,144 @@
 // Deduplicator: High-performance duplicate detection for ARM
 // Uses ahash for faster hashing on ARM architectures
 
+use napi::bindgen_prelude::Buffer;
 use napi_derive::napi;
-use ahash::AHashSet;
+use ahash::{AHashMap, RandomState};
+use bincode::Options;
+use bloomfilter::Bloom;
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::sync::Arc;
-use crate::is_lightweight_mode;
+use crate::{hash_state, is_deterministic, is_lightweight_mode, table_bytes, Error};
+
+const NORMAL_MAX_SIZE: usize = 20000;
+const LIGHTWEIGHT_MAX_SIZE: usize = 5000;
+
+/// Leading byte of `export_state` buffers; bump when `DedupState` changes
+const STATE_VERSION: u8 = 1;
+
+/// Merged snapshots remembered by `merge_from`, so a periodic merge
+/// doesn't grow the history without bound
+const MERGED_SNAPSHOT_HISTORY: usize = 64;
+
+/// How keys are chosen to survive when the cache overflows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EvictionStrategy {
+    /// Keep the most recently recorded keys (ties broken by insertion order)
+    Oldest,
+    /// Keep a uniformly random subset drawn from `EvictionRng`
+    Random,
+}
+
+/// SplitMix64: tiny, fast and good enough to pick eviction victims
+#[derive(Debug)]
+struct EvictionRng {
+    state: u64,
+}
+
+impl EvictionRng {
+    /// Seeded from process entropy, or a fixed seed in deterministic mode
+    fn initial() -> Self {
+        if is_deterministic() {
+            return Self { state: 0 };
+        }
+        Self { state: RandomState::new().hash_one(0u64) }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform index in `0..bound` (bound > 0)
+    fn below(&mut self, bound: usize) -> usize {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+}
+
+/// Fixed-size probabilistic key set behind `new_bloom`
+struct BloomSet {
+    filter: Bloom<str>,
+    /// Keys the filter reported as new (false positives are never counted)
+    inserted: usize,
+}
+
+impl BloomSet {
+    fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        // Hash keys come from process entropy, or fixed in deterministic mode
+        let mut seed = [0u8; 32];
+        if !is_deterministic() {
+            let state = RandomState::new();
+            for (i, chunk) in seed.chunks_exact_mut(8).enumerate() {
+                chunk.copy_from_slice(&state.hash_one(i).to_le_bytes());
+            }
+        }
+        Self {
+            filter: Bloom::new_for_fp_rate_with_seed(expected_items, false_positive_rate, &seed),
+            inserted: 0,
+        }
+    }
+
+    /// Record `key`; true if it was (probably) already present
+    fn check_and_set(&mut self, key: &str) -> bool {
+        let present = self.filter.check_and_set(key);
+        self.inserted += !present as usize;
+        present
+    }
+}
 
 /// High-performance deduplicator optimized for ARM
 #[napi]
 pub struct Deduplicator {
-    seen_items: Arc<RwLock<AHashSet<String>>>,
-    max_size: usize,
+    /// Key -> (when it was recorded (caller's clock, ms), insertion sequence)
+    seen_items: Arc<RwLock<AHashMap<String, Stamp>>>,
+    /// Next insertion sequence; orders keys recorded at the same `now_ms`
+    next_seq: Arc<RwLock<u64>>,
+    /// 0 keeps keys until evicted or cleared
+    ttl_ms: i64,
+    max_size: Arc<RwLock<usize>>,
     stats: Arc<RwLock<DedupStats>>,
+    // Set by `new_recent`: remember only the last `max_size` distinct keys (FIFO)
+    count_window: bool,
+    insertion_order: Arc<RwLock<VecDeque<String>>>,
+    eviction_strategy: Arc<RwLock<EvictionStrategy>>,
+    eviction_rng: Arc<RwLock<EvictionRng>>,
+    /// Set by `new_bloom`: keys live in this filter instead of `seen_items`
+    bloom: Option<Arc<RwLock<BloomSet>>>,
+    /// Digests snapshots for `DedupStats::merged_snapshots`
+    snapshot_hasher: RandomState,
 }
 
+/// (recorded_at_ms, insertion sequence) of a seen key
+type Stamp = (i64, u64);
+
 #[derive(Debug, Default)]
 struct DedupStats {
     total_checked: u64,
     duplicates_found: u64,
     cache_clears: u64,
+    // Counts since the last `take_stats_delta`
+    delta_checked: u64,
+    delta_duplicates: u64,
+    /// Keys dropped for capacity while merging snapshots
+    merge_evictions: u64,
+    /// Digests of the last `MERGED_SNAPSHOT_HISTORY` snapshots merged,
+    /// oldest first; a repeat adds no stats
+    merged_snapshots: VecDeque<u64>,
+}
+
+/// Body of an `export_state` buffer (bincode, after the version byte)
+#[derive(Debug, Serialize, Deserialize)]
+struct DedupState {
+    /// (key, recorded_at), oldest first
+    keys: Vec<(String, i64)>,
+    total_checked: u64,
+    duplicates_found: u64,
+    cache_clears: u64,
 }
 
 #[napi(object)]
@@ -31,70 +150,367 @@ pub struct DedupResult {
 
 #[napi]
 impl Deduplicator {
+    /// `ttl_ms` (optional): a key recorded at least this long before the
+    /// `now_ms` of a later check counts as new again. Omitted, 0 or negative
+    /// keeps keys until evicted or cleared.
     #[napi(constructor)]
-    pub fn new() -> Self {
-        let lightweight = is_lightweight_mode();
-        let max_size = if lightweight { 5000 } else { 20000 };
-        
+    pub fn new(ttl_ms: Option<i64>) -> Self {
         Self {
-            seen_items: Arc::new(RwLock::new(AHashSet::new())),
-            max_size,
+            seen_items: Arc::new(RwLock::new(AHashMap::with_hasher(hash_state()))),
+            next_seq: Arc::new(RwLock::new(0)),
+            ttl_ms: ttl_ms.unwrap_or(0).max(0),
+            max_size: Arc::new(RwLock::new(Self::mode_max_size())),
             stats: Arc::new(RwLock::new(DedupStats::default())),
+            count_window: false,
+            insertion_order: Arc::new(RwLock::new(VecDeque::new())),
+            eviction_strategy: Arc::new(RwLock::new(EvictionStrategy::Oldest)),
+            eviction_rng: Arc::new(RwLock::new(EvictionRng::initial())),
+            bloom: None,
+            snapshot_hasher: hash_state(),
         }
     }
 
+    /// `new(None)` with reproducible internals for tests: the key map hashes
+    /// with fixed keys derived from `seed` (so its iteration order repeats
+    /// across runs of the same build) and the `"random"` eviction strategy
+    /// starts from `seed`. Unlike deterministic mode this is per instance
+    /// and leaves the wall clock alone.
+    #[napi(factory)]
+    pub fn new_with_seed(seed: u32) -> Self {
+        let seed = seed as u64;
+        // Spread one seed over ahash's four keys
+        let mut keys = EvictionRng { state: seed };
+        let state = RandomState::with_seeds(keys.next_u64(), keys.next_u64(), keys.next_u64(), keys.next_u64());
+        Self {
+            seen_items: Arc::new(RwLock::new(AHashMap::with_hasher(state))),
+            eviction_rng: Arc::new(RwLock::new(EvictionRng { state: seed })),
+            ..Self::new(None)
+        }
+    }
+
+    /// Count-based sliding window: remember only the most recent `k`
+    /// distinct keys, expiring the oldest on each new insert. Seeing a
+    /// duplicate does not refresh its position (FIFO, not LRU), so expiry
+    /// is deterministic and independent of wall-clock and lightweight mode.
+    #[napi(factory)]
+    pub fn new_recent(k: u32) -> napi::Result<Self> {
+        if k == 0 {
+            return Err(Error::invalid_argument("k", "must be at least 1").into());
+        }
+        let window = k as usize;
+        Ok(Self {
+            seen_items: Arc::new(RwLock::new(AHashMap::with_capacity_and_hasher(window, hash_state()))),
+            next_seq: Arc::new(RwLock::new(0)),
+            ttl_ms: 0,
+            max_size: Arc::new(RwLock::new(window)),
+            stats: Arc::new(RwLock::new(DedupStats::default())),
+            count_window: true,
+            insertion_order: Arc::new(RwLock::new(VecDeque::with_capacity(window + 1))),
+            eviction_strategy: Arc::new(RwLock::new(EvictionStrategy::Oldest)),
+            eviction_rng: Arc::new(RwLock::new(EvictionRng::initial())),
+            bloom: None,
+            snapshot_hasher: hash_state(),
+        })
+    }
+
+    /// Bloom-filter-backed deduplicator for key sets too large to hold
+    /// exactly (e.g. backfills over millions of keys). Memory is fixed at
+    /// construction, sized so that after `expected_items` distinct keys a
+    /// new key is misreported as a duplicate with probability about
+    /// `false_positive_rate` (in (0, 1)); past that the rate climbs. Keys
+    /// are never misreported as new, never evicted and never expire.
+    ///
+    /// The API matches the exact deduplicator, with these differences:
+    /// `get_cache_size` counts keys reported as new, `get_max_size` is
+    /// `expected_items`, `get_memory_usage` is the fixed filter size and
+    /// `get_memory_savings` the share of `expected_items` not yet used.
+    /// `clear` resets the filter. `reconfigure` is a no-op, while `resize`
+    /// and state export/import are errors.
+    #[napi(factory)]
+    pub fn new_bloom(expected_items: u32, false_positive_rate: f64) -> napi::Result<Self> {
+        if expected_items == 0 {
+            return Err(Error::invalid_argument("expected_items", "must be at least 1").into());
+        }
+        if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+            return Err(Error::invalid_argument(
+                "false_positive_rate",
+                format!("must be within (0, 1), got {}", false_positive_rate),
+            )
+            .into());
+        }
+        let bloom = BloomSet::new(expected_items as usize, false_positive_rate);
+        Ok(Self {
+            bloom: Some(Arc::new(RwLock::new(bloom))),
+            max_size: Arc::new(RwLock::new(expected_items as usize)),
+            ..Self::new(None)
+        })
+    }
+
     /// Check if item is duplicate and add to cache
-    /// Returns true if duplicate
+    /// Returns true if duplicate. Under a TTL, a key recorded `ttl_ms` or
+    /// more before `now_ms` is not a duplicate and is re-stamped at `now_ms`.
     #[napi]
-    pub fn check_and_add(&self, key: String) -> bool {
+    pub fn check_and_add(&self, key: String, now_ms: i64) -> bool {
         let mut seen = self.seen_items.write();
         let mut stats = self.stats.write();
         
         stats.total_checked += 1;
+        stats.delta_checked += 1;
 
-        // Check if already seen
-        if seen.contains(&key) {
+        let (is_dup, evicted) = self.admit(&mut seen, &mut self.insertion_order.write(), key, now_ms);
+        if is_dup {
             stats.duplicates_found += 1;
-            return true;
-        }
-
-        // Auto-cleanup in lightweight mode when cache is full
-        if seen.len() >= self.max_size {
-            if is_lightweight_mode() {
-                // Keep only 25% of entries (75% memory reduction)
-                let keep_size = self.max_size / 4;
-                let keys_to_keep: Vec<String> = seen.iter().take(keep_size).cloned().collect();
-                seen.clear();
-                seen.extend(keys_to_keep);
-                stats.cache_clears += 1;
-            } else {
-                // Clear 50% in normal mode
-                let keep_size = self.max_size / 2;
-                let keys_to_keep: Vec<String> = seen.iter().take(keep_size).cloned().collect();
-                seen.clear();
-                seen.extend(keys_to_keep);
-                stats.cache_clears += 1;
+            stats.delta_duplicates += 1;
+        }
+        if evicted {
+            stats.cache_clears += 1;
+        }
+        is_dup
+    }
+
+    /// Record `key` unless it is a live duplicate, making room first when
+    /// the cache is full. Returns (is_duplicate, evicted).
+    fn admit(
+        &self,
+        seen: &mut AHashMap<String, Stamp>,
+        order: &mut VecDeque<String>,
+        key: String,
+        now_ms: i64,
+    ) -> (bool, bool) {
+        if let Some(bloom) = &self.bloom {
+            return (bloom.write().check_and_set(&key), false);
+        }
+        if let Some(stamp) = seen.get_mut(&key) {
+            if !self.is_expired(stamp.0, now_ms) {
+                return (true, false);
             }
+            // Re-recorded: now the newest key
+            *stamp = self.stamp(now_ms);
+            return (false, false);
+        }
+
+        let max_size = *self.max_size.read();
+        if self.count_window {
+            Self::push_recent(seen, order, key, self.stamp(now_ms), max_size);
+            return (false, false);
         }
 
-        seen.insert(key);
-        false
+        // Auto-cleanup when the cache is full: expired keys first, then the oldest live ones
+        let mut evicted = false;
+        if seen.len() >= max_size {
+            if self.ttl_ms > 0 {
+                seen.retain(|_, (at, _)| !self.is_expired(*at, now_ms));
+            }
+            if seen.len() >= max_size {
+                if is_lightweight_mode() {
+                    // Keep only 25% of entries (75% memory reduction)
+                    self.retain_keys(seen, max_size / 4);
+                } else {
+                    // Clear 50% in normal mode
+                    self.retain_keys(seen, max_size / 2);
+                }
+            }
+            evicted = true;
+        }
+
+        seen.insert(key, self.stamp(now_ms));
+        (false, evicted)
+    }
+
+    /// Stamp a key recorded at `now_ms` as the latest insertion
+    fn stamp(&self, now_ms: i64) -> Stamp {
+        let mut next_seq = self.next_seq.write();
+        *next_seq += 1;
+        (now_ms, *next_seq)
     }
 
-    /// Batch check for duplicates (more efficient for ARM)
+    fn is_expired(&self, recorded_at: i64, now_ms: i64) -> bool {
+        self.ttl_ms > 0 && now_ms.saturating_sub(recorded_at) >= self.ttl_ms
+    }
+
+    /// Re-read the lightweight flag and apply its size limit to this
+    /// instance, evicting down to the new limit if it shrank. Returns the
+    /// number of evicted keys.
     #[napi]
-    pub fn check_batch(&self, keys: Vec<String>) -> Vec<bool> {
+    pub fn reconfigure(&self) -> u32 {
+        if self.count_window || self.bloom.is_some() {
+            return 0;
+        }
+        let new_max = Self::mode_max_size();
+        *self.max_size.write() = new_max;
+
+        let mut seen = self.seen_items.write();
+        let before = seen.len();
+        if before > new_max {
+            self.retain_keys(&mut seen, new_max);
+            self.stats.write().cache_clears += 1;
+        }
+        (before - seen.len()) as u32
+    }
+
+    /// Set this instance's size limit, evicting down to it right away if the
+    /// cache is larger (using the eviction strategy, or oldest-first for
+    /// `new_recent` instances). Returns the number of evicted keys. A later
+    /// `reconfigure` re-applies the lightweight-mode limit, except on
+    /// `new_recent` instances, whose window only changes here.
+    #[napi]
+    pub fn resize(&self, new_max: u32) -> napi::Result<u32> {
+        if new_max == 0 {
+            return Err(Error::invalid_argument("max size", "must be at least 1").into());
+        }
+        if self.bloom.is_some() {
+            return Err(Error::InvalidInput("a Bloom-filter deduplicator has a fixed size".to_string()).into());
+        }
+        let new_max = new_max as usize;
+        *self.max_size.write() = new_max;
+
         let mut seen = self.seen_items.write();
-        let mut results = Vec::with_capacity(keys.len());
+        let before = seen.len();
+        if before > new_max {
+            if self.count_window {
+                let mut order = self.insertion_order.write();
+                while order.len() > new_max {
+                    if let Some(oldest) = order.pop_front() {
+                        seen.remove(&oldest);
+                    }
+                }
+            } else {
+                self.retain_keys(&mut seen, new_max);
+            }
+            self.stats.write().cache_clears += 1;
+        }
+        Ok((before - seen.len()) as u32)
+    }
+
+    #[napi]
+    pub fn get_max_size(&self) -> u32 {
+        *self.max_size.read() as u32
+    }
+
+    fn mode_max_size() -> usize {
+        if is_lightweight_mode() { LIGHTWEIGHT_MAX_SIZE } else { NORMAL_MAX_SIZE }
+    }
+
+    /// Choose which keys survive an overflow: `"oldest"` (default) evicts
+    /// the keys recorded longest ago, `"random"` keeps a uniformly random
+    /// subset. `"arbitrary"`, from before keys carried timestamps, is an
+    /// alias for `"oldest"`. Does not apply to `new_recent`, which always
+    /// expires the oldest key.
+    #[napi]
+    pub fn set_eviction_strategy(&self, strategy: String) -> napi::Result<()> {
+        let strategy = match strategy.as_str() {
+            "oldest" | "arbitrary" => EvictionStrategy::Oldest,
+            "random" => EvictionStrategy::Random,
+            other => {
+                return Err(Error::invalid_argument(
+                    "strategy",
+                    format!("expected 'oldest' or 'random', got '{}'", other),
+                )
+                .into())
+            }
+        };
+        *self.eviction_strategy.write() = strategy;
+        Ok(())
+    }
+
+    /// Reseed the generator behind the `"random"` eviction strategy so the
+    /// surviving keys are reproducible (instances start from entropy). Has
+    /// no effect on the other strategies. JS numbers carry no u64, so the
+    /// seed's bits are reinterpreted as unsigned.
+    #[napi]
+    pub fn set_eviction_seed(&self, seed: i64) {
+        *self.eviction_rng.write() = EvictionRng { state: seed as u64 };
+    }
+
+    fn retain_keys(&self, seen: &mut AHashMap<String, Stamp>, keep_size: usize) {
+        let keys_to_keep: Vec<(String, Stamp)> = match *self.eviction_strategy.read() {
+            EvictionStrategy::Oldest => {
+                // Stamps are unique, so keys recorded at the same `now_ms`
+                // (e.g. one `check_batch`) survive in insertion order
+                let mut by_age: Vec<(Stamp, String)> = seen.drain().map(|(key, stamp)| (stamp, key)).collect();
+                by_age.sort_unstable();
+                let evict = by_age.len().saturating_sub(keep_size);
+                by_age.into_iter().skip(evict).map(|(stamp, key)| (key, stamp)).collect()
+            }
+            EvictionStrategy::Random => {
+                // Sort first: hash map order differs between processes, and
+                // the seed must pick the same keys every time
+                let mut keys: Vec<(String, Stamp)> = seen.drain().collect();
+                keys.sort_unstable();
+                let keep = keep_size.min(keys.len());
+                let mut rng = self.eviction_rng.write();
+                for i in 0..keep {
+                    let j = i + rng.below(keys.len() - i);
+                    keys.swap(i, j);
+                }
+                keys.truncate(keep);
+                keys
+            }
+        };
+        seen.clear();
+        seen.extend(keys_to_keep);
+    }
 
-        for key in keys {
-            let is_dup = seen.contains(&key);
-            if !is_dup {
-                seen.insert(key);
+    /// Insert a new key in count-window mode, expiring the oldest past `window`
+    fn push_recent(
+        seen: &mut AHashMap<String, Stamp>,
+        order: &mut VecDeque<String>,
+        key: String,
+        stamp: Stamp,
+        window: usize,
+    ) {
+        seen.insert(key.clone(), stamp);
+        order.push_back(key);
+        while order.len() > window {
+            if let Some(oldest) = order.pop_front() {
+                seen.remove(&oldest);
             }
-            results.push(is_dup);
         }
+    }
+
+    /// Membership test without recording the key or touching stats
+    /// (ignores the TTL, which needs the caller's clock)
+    #[napi]
+    pub fn contains(&self, key: String) -> bool {
+        if let Some(bloom) = &self.bloom {
+            return bloom.read().filter.check(&key);
+        }
+        self.seen_items.read().contains_key(&key)
+    }
+
+    /// How long before `now_ms` the key was recorded (0 if recorded after
+    /// it), without recording it or touching stats. `None` when the key
+    /// isn't held or has expired under the TTL, and always for Bloom-filter
+    /// instances, which keep no timestamps.
+    #[napi]
+    pub fn age_of(&self, key: String, now_ms: i64) -> Option<i64> {
+        let (recorded_at, _) = *self.seen_items.read().get(&key)?;
+        if self.is_expired(recorded_at, now_ms) {
+            return None;
+        }
+        Some(now_ms.saturating_sub(recorded_at).max(0))
+    }
+
+    /// Batch check for duplicates (more efficient for ARM). Same expiry and
+    /// eviction as `check_and_add` at a single `now_ms`; only evictions are
+    /// counted in the stats.
+    #[napi]
+    pub fn check_batch(&self, keys: Vec<String>, now_ms: i64) -> Vec<bool> {
+        let mut seen = self.seen_items.write();
+        let mut order = self.insertion_order.write();
+        let mut evictions = 0;
+        let results = keys
+            .into_iter()
+            .map(|key| {
+                let (is_dup, evicted) = self.admit(&mut seen, &mut order, key, now_ms);
+                evictions += evicted as u64;
+                is_dup
+            })
+            .collect();
 
+        if evictions > 0 {
+            self.stats.write().cache_clears += evictions;
+        }
         results
     }
 
@@ -108,23 +524,290 @@ impl Deduplicator {
         }
     }
 
+    /// Keys `merge_from` has evicted to stay within the max size
+    #[napi]
+    pub fn get_merge_evictions(&self) -> f64 {
+        self.stats.read().merge_evictions as f64
+    }
+
+    /// Counts accumulated since the previous call, resetting only the delta
+    /// counters; cumulative totals and the cache are left intact
+    #[napi]
+    pub fn take_stats_delta(&self) -> DedupResult {
+        let mut stats = self.stats.write();
+        let delta = DedupResult {
+            is_duplicate: false,
+            total_checked: stats.delta_checked as f64,
+            duplicates_found: stats.delta_duplicates as f64,
+        };
+        stats.delta_checked = 0;
+        stats.delta_duplicates = 0;
+        delta
+    }
+
+    /// Snapshot the seen keys (with their timestamps) and cumulative stats
+    /// so a restarted process can `import_state` them. The buffer starts
+    /// with a format version byte followed by a bincode body.
+    #[napi]
+    pub fn export_state(&self) -> napi::Result<Buffer> {
+        Ok(self.encode_state()?.into())
+    }
+
+    /// Replace the seen keys and cumulative stats with a snapshot from
+    /// `export_state`. When the snapshot holds more keys than this
+    /// instance's max size, only the newest are kept. Delta counters start
+    /// from zero. Corrupt or version-mismatched buffers are rejected before
+    /// any state changes. Returns the number of keys loaded.
+    #[napi]
+    pub fn import_state(&self, data: Buffer) -> napi::Result<u32> {
+        Ok(self.load_state(&data)?)
+    }
+
+    /// Union the keys of another instance's `export_state` snapshot into
+    /// this one, e.g. to combine per-worker shards at a checkpoint. A key
+    /// held by both keeps the later of its two timestamps; keys are taken
+    /// oldest first, and past the max size the overflow is evicted with
+    /// the eviction strategy (oldest first for `new_recent` instances).
+    /// Merging the same snapshot again adds no keys. The snapshot's
+    /// cumulative stats are added to this instance's the first time it is
+    /// merged; only the last 64 snapshots are remembered, and `clear` or
+    /// `import_state` forgets them all. Delta counters are left alone. Keys evicted for capacity are counted by
+    /// `get_merge_evictions`. Returns the number of keys added.
+    #[napi]
+    pub fn merge_from(&self, other_state: Buffer) -> napi::Result<u32> {
+        Ok(self.merge_state(&other_state)?)
+    }
+
+    pub(crate) fn merge_state(&self, bytes: &[u8]) -> Result<u32, Error> {
+        self.require_exact("merge")?;
+        let state = Self::decode_state(bytes)?;
+
+        let max_size = *self.max_size.read();
+        let mut seen = self.seen_items.write();
+        let mut order = self.insertion_order.write();
+        let mut added = 0;
+        for (key, at) in state.keys {
+            if let Some(stamp) = seen.get_mut(&key) {
+                // Count windows keep their FIFO position; elsewhere this is a refresh
+                if at > stamp.0 {
+                    *stamp = if self.count_window { (at, stamp.1) } else { self.stamp(at) };
+                }
+                continue;
+            }
+            added += 1;
+            let stamp = self.stamp(at);
+            if self.count_window {
+                Self::push_recent(&mut seen, &mut order, key, stamp, max_size);
+            } else {
+                seen.insert(key, stamp);
+            }
+        }
+        let before = seen.len();
+        if before > max_size {
+            self.retain_keys(&mut seen, max_size);
+        }
+        let evicted = before - seen.len();
+
+        let digest = self.snapshot_hasher.hash_one(bytes);
+        let mut stats = self.stats.write();
+        stats.merge_evictions += evicted as u64;
+        if !stats.merged_snapshots.contains(&digest) {
+            if stats.merged_snapshots.len() == MERGED_SNAPSHOT_HISTORY {
+                stats.merged_snapshots.pop_front();
+            }
+            stats.merged_snapshots.push_back(digest);
+            stats.total_checked += state.total_checked;
+            stats.duplicates_found += state.duplicates_found;
+            stats.cache_clears += state.cache_clears;
+        }
+        Ok(added)
+    }
+
+    pub(crate) fn encode_state(&self) -> Result<Vec<u8>, Error> {
+        self.require_exact("export")?;
+        let seen = self.seen_items.read();
+        let keys: Vec<(String, i64)> = if self.count_window {
+            let order = self.insertion_order.read();
+            order.iter().filter_map(|key| seen.get(key).map(|(at, _)| (key.clone(), *at))).collect()
+        } else {
+            let mut keys: Vec<(&String, Stamp)> = seen.iter().map(|(key, stamp)| (key, *stamp)).collect();
+            keys.sort_unstable_by_key(|(_, stamp)| *stamp);
+            keys.into_iter().map(|(key, (at, _))| (key.clone(), at)).collect()
+        };
+        drop(seen);
+
+        let stats = self.stats.read();
+        let state = DedupState {
+            keys,
+            total_checked: stats.total_checked,
+            duplicates_found: stats.duplicates_found,
+            cache_clears: stats.cache_clears,
+        };
+        drop(stats);
+
+        let mut out = vec![STATE_VERSION];
+        bincode::DefaultOptions::new()
+            .serialize_into(&mut out, &state)
+            .map_err(|e| Error::InvalidInput(format!("failed to encode dedup state: {}", e)))?;
+        Ok(out)
+    }
+
+    pub(crate) fn load_state(&self, bytes: &[u8]) -> Result<u32, Error> {
+        self.require_exact("import")?;
+        let state = Self::decode_state(bytes)?;
+
+        let max_size = *self.max_size.read();
+        let mut seen = self.seen_items.write();
+        let mut order = self.insertion_order.write();
+        seen.clear();
+        order.clear();
+
+        let skip = state.keys.len().saturating_sub(max_size);
+        for (key, at) in state.keys.into_iter().skip(skip) {
+            let stamp = self.stamp(at);
+            if self.count_window {
+                Self::push_recent(&mut seen, &mut order, key, stamp, max_size);
+            } else {
+                seen.insert(key, stamp);
+            }
+        }
+
+        *self.stats.write() = DedupStats {
+            total_checked: state.total_checked,
+            duplicates_found: state.duplicates_found,
+            cache_clears: state.cache_clears,
+            ..DedupStats::default()
+        };
+        Ok(seen.len() as u32)
+    }
+
+    /// State snapshots hold keys, which a Bloom filter cannot list
+    fn require_exact(&self, action: &str) -> Result<(), Error> {
+        if self.bloom.is_some() {
+            return Err(Error::InvalidInput(format!("cannot {} the state of a Bloom-filter deduplicator", action)));
+        }
+        Ok(())
+    }
+
+    fn decode_state(bytes: &[u8]) -> Result<DedupState, Error> {
+        let (&version, body) = bytes
+            .split_first()
+            .ok_or_else(|| Error::InvalidInput("empty dedup state".to_string()))?;
+        if version != STATE_VERSION {
+            return Err(Error::InvalidInput(format!("unsupported dedup state version {}", version)));
+        }
+        let state: DedupState = bincode::DefaultOptions::new()
+            // A corrupt length prefix must not allocate past the buffer
+            .with_limit(body.len() as u64)
+            .deserialize(body)
+            .map_err(|e| Error::InvalidInput(format!("corrupt dedup state: {}", e)))?;
+        if state.duplicates_found > state.total_checked {
+            return Err(Error::InvalidInput(format!(
+                "dedup state has {} duplicates out of {} checks",
+                state.duplicates_found, state.total_checked
+            )));
+        }
+        Ok(state)
+    }
+
+    /// Debugging aid for a deduplicator suspected of corruption: checks
+    /// that the stats are coherent (duplicates never exceed checks, the
+    /// delta counters never exceed the totals), that the cache is within
+    /// `max_size`, and that in count-window mode the insertion order tracks
+    /// exactly the cached keys. Throws a "corrupt state" error naming the
+    /// first violation. Never reads the clock. Takes every lock, so keep it
+    /// off the hot path.
+    #[napi]
+    pub fn self_check(&self) -> napi::Result<()> {
+        Ok(self.check_invariants().map_err(Error::CorruptState)?)
+    }
+
+    fn check_invariants(&self) -> Result<(), String> {
+        let seen = self.seen_items.read();
+        let order = self.insertion_order.read();
+        let stats = self.stats.read();
+        let max_size = *self.max_size.read();
+
+        if stats.duplicates_found > stats.total_checked {
+            return Err(format!(
+                "duplicates_found ({}) exceeds total_checked ({})",
+                stats.duplicates_found, stats.total_checked
+            ));
+        }
+        if stats.delta_duplicates > stats.delta_checked {
+            return Err(format!(
+                "delta duplicates ({}) exceed delta checked ({})",
+                stats.delta_duplicates, stats.delta_checked
+            ));
+        }
+        if stats.delta_checked > stats.total_checked {
+            return Err(format!(
+                "delta checked ({}) exceeds total_checked ({})",
+                stats.delta_checked, stats.total_checked
+            ));
+        }
+        if seen.len() > max_size {
+            return Err(format!("cache size {} exceeds max size {}", seen.len(), max_size));
+        }
+        if self.count_window {
+            if order.len() != seen.len() {
+                return Err(format!(
+                    "insertion order tracks {} keys but the cache holds {}",
+                    order.len(),
+                    seen.len()
+                ));
+            }
+            if let Some(missing) = order.iter().find(|key| !seen.contains_key(*key)) {
+                return Err(format!("insertion order key '{}' is missing from the cache", missing));
+            }
+        } else if !order.is_empty() {
+            return Err(format!("insertion order holds {} keys outside count-window mode", order.len()));
+        }
+        Ok(())
+    }
+
     #[napi]
     pub fn get_cache_size(&self) -> u32 {
+        if let Some(bloom) = &self.bloom {
+            return bloom.read().inserted as u32;
+        }
         self.seen_items.read().len() as u32
     }
 
     #[napi]
     pub fn clear(&self) {
+        if let Some(bloom) = &self.bloom {
+            let mut bloom = bloom.write();
+            bloom.filter.clear();
+            bloom.inserted = 0;
+        }
         self.seen_items.write().clear();
+        self.insertion_order.write().clear();
         let mut stats = self.stats.write();
         *stats = DedupStats::default();
     }
 
+    /// Bytes held by the seen keys: the map, its table (sized by capacity),
+    /// the key bytes, and for `new_recent` instances the insertion order
+    /// (which holds a second copy of each key). Allocator overhead is not
+    /// counted. Bloom-filter instances report their fixed bitmap size.
+    #[napi]
+    pub fn get_memory_usage(&self) -> f64 {
+        if let Some(bloom) = &self.bloom {
+            return (std::mem::size_of::<BloomSet>() as u64 + bloom.read().filter.number_of_bits().div_ceil(8)) as f64;
+        }
+        let seen = self.seen_items.read();
+        let order = self.insertion_order.read();
+        let key_bytes: usize = seen.keys().map(String::len).sum();
+        let order_bytes = order.capacity() * std::mem::size_of::<String>() + order.iter().map(String::len).sum::<usize>();
+        (std::mem::size_of::<AHashMap<String, Stamp>>() + table_bytes(&seen) + key_bytes + order_bytes) as f64
+    }
+
     /// Get memory savings percentage
     #[napi]
     pub fn get_memory_savings(&self) -> f64 {
-        let current_size = self.seen_items.read().len();
-        let max_size = self.max_size;
+        let current_size = self.get_cache_size() as usize;
+        let max_size = *self.max_size.read();
         
         if max_size == 0 {
             return 0.0;
@@ -137,28 +820,253 @@ impl Deduplicator {
 
 impl Default for Deduplicator {
     fn default() -> Self {
-        Self::new()
+        Self::new(None)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::set_lightweight_mode;
 
     #[test]
     fn test_deduplicator() {
-        let dedup = Deduplicator::new();
+        let dedup = Deduplicator::new(None);
         
-        assert!(!dedup.check_and_add("key1".to_string()));
-        assert!(dedup.check_and_add("key1".to_string())); // Duplicate
-        assert!(!dedup.check_and_add("key2".to_string()));
+        assert!(!dedup.check_and_add("key1".to_string(), 0));
+        assert!(dedup.check_and_add("key1".to_string(), 0)); // Duplicate
+        assert!(!dedup.check_and_add("key2".to_string(), 0));
         
         assert_eq!(dedup.get_cache_size(), 2);
     }
 
+    #[test]
+    fn test_ttl_expiry() {
+        let dedup = Deduplicator::new(Some(1_000));
+
+        assert!(!dedup.check_and_add("key1".to_string(), 0));
+        assert!(dedup.check_and_add("key1".to_string(), 999));
+        // Expired: new again, and re-stamped so it dedups for another TTL
+        assert!(!dedup.check_and_add("key1".to_string(), 1_000));
+        assert!(dedup.check_and_add("key1".to_string(), 1_999));
+        assert_eq!(dedup.get_cache_size(), 1);
+
+        // check_batch applies the same expiry
+        assert_eq!(dedup.check_batch(vec!["key1".to_string(), "key2".to_string()], 2_500), vec![false, false]);
+        assert_eq!(dedup.check_batch(vec!["key1".to_string(), "key2".to_string()], 3_000), vec![true, true]);
+
+        // Without a TTL keys never expire
+        let forever = Deduplicator::new(None);
+        forever.check_and_add("key1".to_string(), 0);
+        assert!(forever.check_and_add("key1".to_string(), i64::MAX));
+    }
+
+    #[test]
+    fn test_overflow_evicts_oldest() {
+        let fill = |dedup: &Deduplicator| {
+            dedup.resize(10).unwrap();
+            // Stamps out of key order: key9 is the oldest, key0 the newest
+            for i in 0..10 {
+                dedup.check_and_add(format!("key{}", i), 50 - i as i64);
+            }
+        };
+        let kept = |dedup: &Deduplicator| {
+            let mut keys: Vec<String> = dedup.seen_items.read().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+
+        // Nothing expired: the oldest half goes
+        let dedup = Deduplicator::new(None);
+        fill(&dedup);
+        dedup.check_and_add("new".to_string(), 60);
+        assert_eq!(kept(&dedup), vec!["key0", "key1", "key2", "key3", "key4", "new"]);
+
+        // Expired keys make room first; live ones are left alone
+        let dedup = Deduplicator::new(Some(13));
+        fill(&dedup);
+        dedup.check_and_add("new".to_string(), 55);
+        assert_eq!(kept(&dedup).len(), 9);
+        assert!(!dedup.contains("key8".to_string()) && !dedup.contains("key9".to_string()));
+        assert_eq!(dedup.get_stats().total_checked, 11.0);
+    }
+
+    #[test]
+    fn test_contains_is_read_only() {
+        let dedup = Deduplicator::new(None);
+
+        assert!(!dedup.contains("key1".to_string()));
+        assert_eq!(dedup.get_cache_size(), 0);
+        assert_eq!(dedup.get_stats().total_checked, 0.0);
+
+        dedup.check_and_add("key1".to_string(), 0);
+        assert!(dedup.contains("key1".to_string()));
+        assert_eq!(dedup.get_cache_size(), 1);
+        assert_eq!(dedup.get_stats().total_checked, 1.0);
+
+        assert_eq!(dedup.age_of("key1".to_string(), 1_500), Some(1_500));
+        assert_eq!(dedup.age_of("key1".to_string(), -5), Some(0));
+        assert_eq!(dedup.age_of("key2".to_string(), 1_500), None);
+        assert_eq!(dedup.get_cache_size(), 1);
+        assert_eq!(dedup.get_stats().total_checked, 1.0);
+
+        // Past the TTL the key counts as gone
+        let ttl = Deduplicator::new(Some(1_000));
+        ttl.check_and_add("key1".to_string(), 0);
+        assert_eq!(ttl.age_of("key1".to_string(), 999), Some(999));
+        assert_eq!(ttl.age_of("key1".to_string(), 1_000), None);
+        assert!(ttl.contains("key1".to_string()));
+    }
+
+    #[test]
+    fn test_stats_delta() {
+        let dedup = Deduplicator::new(None);
+        let mut checked = 0.0;
+        let mut duplicates = 0.0;
+
+        for round in 0..3 {
+            for i in 0..(round + 2) {
+                dedup.check_and_add(format!("key{}", i), 0);
+            }
+            let delta = dedup.take_stats_delta();
+            checked += delta.total_checked;
+            duplicates += delta.duplicates_found;
+        }
+
+        // Deltas sum to the cumulative totals, which were not reset
+        let stats = dedup.get_stats();
+        assert_eq!((checked, duplicates), (stats.total_checked, stats.duplicates_found));
+        assert_eq!((stats.total_checked, stats.duplicates_found), (9.0, 5.0));
+        assert_eq!(dedup.get_cache_size(), 4);
+
+        let empty = dedup.take_stats_delta();
+        assert_eq!((empty.total_checked, empty.duplicates_found), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_recent_window_expires_by_count() {
+        let _modes = crate::ModeGuard::acquire();
+        assert!(Deduplicator::new_recent(0).is_err());
+
+        let dedup = Deduplicator::new_recent(3).unwrap();
+        assert!(!dedup.check_and_add("a".to_string(), 0));
+        assert!(!dedup.check_and_add("b".to_string(), 0));
+        assert!(!dedup.check_and_add("c".to_string(), 0));
+
+        // A duplicate hit does not refresh "a"
+        assert!(dedup.check_and_add("a".to_string(), 0));
+
+        // "a" is still inside the window until a 4th distinct key arrives
+        assert!(!dedup.check_and_add("d".to_string(), 0));
+        assert!(!dedup.contains("a".to_string()));
+        assert_eq!(dedup.get_cache_size(), 3);
+
+        // After K more distinct inserts every earlier key has expired
+        assert_eq!(dedup.check_batch(vec!["e".to_string(), "f".to_string(), "g".to_string()], 0), vec![false; 3]);
+        for key in ["b", "c", "d"] {
+            assert!(!dedup.contains(key.to_string()));
+        }
+        assert_eq!(dedup.get_cache_size(), 3);
+
+        // Lightweight reconfiguration does not touch the count window
+        set_lightweight_mode(true);
+        assert_eq!(dedup.reconfigure(), 0);
+        assert_eq!(dedup.get_max_size(), 3);
+        set_lightweight_mode(false);
+    }
+
+    #[test]
+    fn test_seeded_random_eviction() {
+        let survivors = |seed: i64| {
+            let dedup = Deduplicator::new(None);
+            *dedup.max_size.write() = 8;
+            dedup.set_eviction_strategy("random".to_string()).unwrap();
+            dedup.set_eviction_seed(seed);
+            for i in 0..9 {
+                dedup.check_and_add(format!("key{}", i), 0);
+            }
+            let mut keys: Vec<String> = dedup.seen_items.read().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+
+        // 8 keys overflow: 4 survive, then key8 is inserted
+        assert_eq!(survivors(42), vec!["key2", "key3", "key4", "key5", "key8"]);
+        assert_eq!(survivors(42), survivors(42));
+        assert_ne!(survivors(42), survivors(7));
+
+        let dedup = Deduplicator::new(None);
+        assert!(dedup.set_eviction_strategy("lru".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_self_check() {
+        let dedup = Deduplicator::new(None);
+        dedup.check_batch(vec!["a".to_string(), "b".to_string()], 0);
+        dedup.check_and_add("a".to_string(), 0);
+        assert_eq!(dedup.check_invariants(), Ok(()));
+
+        let recent = Deduplicator::new_recent(2).unwrap();
+        recent.check_batch(vec!["a".to_string(), "b".to_string(), "c".to_string()], 0);
+        assert_eq!(recent.check_invariants(), Ok(()));
+
+        recent.seen_items.write().remove("c");
+        assert!(recent.check_invariants().unwrap_err().contains("insertion order"));
+
+        dedup.stats.write().duplicates_found = 5;
+        assert!(dedup.check_invariants().unwrap_err().starts_with("duplicates_found (5)"));
+    }
+
+    #[test]
+    fn test_resize_evicts_to_new_limit() {
+        let dedup = Deduplicator::new(None);
+        for i in 0..100 {
+            dedup.check_and_add(format!("key{}", i), 0);
+        }
+
+        assert!(dedup.resize(0).is_err());
+        assert_eq!(dedup.resize(40).unwrap(), 60);
+        assert_eq!(dedup.get_cache_size(), 40);
+        assert_eq!(dedup.get_max_size(), 40);
+
+        // Growing evicts nothing
+        assert_eq!(dedup.resize(1000).unwrap(), 0);
+        assert_eq!(dedup.get_cache_size(), 40);
+
+        // Count windows drop their oldest keys
+        let recent = Deduplicator::new_recent(5).unwrap();
+        recent.check_batch((0..5).map(|i| format!("key{}", i)).collect(), 0);
+        assert_eq!(recent.resize(2).unwrap(), 3);
+        assert!(recent.contains("key3".to_string()) && recent.contains("key4".to_string()));
+        recent.check_and_add("key5".to_string(), 0);
+        assert!(!recent.contains("key3".to_string()));
+        assert_eq!(recent.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_reconfigure_applies_mode() {
+        let _modes = crate::ModeGuard::acquire();
+        let dedup = Deduplicator::new(None);
+        assert_eq!(dedup.get_max_size(), 20000);
+
+        for i in 0..6000 {
+            dedup.check_and_add(format!("key{}", i), 0);
+        }
+
+        set_lightweight_mode(true);
+        assert_eq!(dedup.reconfigure(), 1000);
+        assert_eq!(dedup.get_max_size(), 5000);
+        assert_eq!(dedup.get_cache_size(), 5000);
+
+        set_lightweight_mode(false);
+        assert_eq!(dedup.reconfigure(), 0);
+        assert_eq!(dedup.get_max_size(), 20000);
+        assert_eq!(dedup.get_cache_size(), 5000);
+    }
+
     #[test]
     fn test_batch_check() {
-        let dedup = Deduplicator::new();
+        let dedup = Deduplicator::new(None);
         
         let keys = vec![
             "key1".to_string(),
@@ -166,7 +1074,210 @@ mod tests {
             "key1".to_string(), // Duplicate
         ];
         
-        let results = dedup.check_batch(keys);
+        let results = dedup.check_batch(keys, 0);
         assert_eq!(results, vec![false, false, true]);
     }
+
+    #[test]
+    fn test_state_round_trip() {
+        let dedup = Deduplicator::new(None);
+        for i in 0..5 {
+            dedup.check_and_add(format!("key{}", i), i);
+        }
+        dedup.check_and_add("key1".to_string(), 10);
+        let state = dedup.encode_state().unwrap();
+
+        let restarted = Deduplicator::new(None);
+        restarted.check_and_add("stale".to_string(), 0);
+        assert_eq!(restarted.load_state(&state).unwrap(), 5);
+        assert!(!restarted.contains("stale".to_string()));
+        assert!(restarted.check_and_add("key3".to_string(), 20));
+        let stats = restarted.get_stats();
+        assert_eq!((stats.total_checked, stats.duplicates_found), (7.0, 2.0));
+        restarted.check_invariants().unwrap();
+
+        // Corrupt, truncated or foreign buffers fail without touching state
+        let mut wrong_version = state.to_vec();
+        wrong_version[0] = STATE_VERSION + 1;
+        let mut corrupt = state.to_vec();
+        corrupt[1] = 0xff;
+        for bad in [Vec::new(), wrong_version, corrupt, state[..state.len() - 1].to_vec(), [&state[..], &[0]].concat()] {
+            assert!(restarted.load_state(&bad).is_err());
+        }
+        assert_eq!(restarted.get_cache_size(), 5);
+    }
+
+    #[test]
+    fn test_import_state_respects_max_size() {
+        let source = Deduplicator::new(None);
+        for i in 0..10 {
+            source.check_and_add(format!("key{}", i), i);
+        }
+
+        let full = Deduplicator::new(None);
+        full.resize(4).unwrap();
+        for i in 0..4 {
+            full.check_and_add(format!("other{}", i), 100);
+        }
+        assert_eq!(full.load_state(&source.encode_state().unwrap()).unwrap(), 4);
+        let mut kept: Vec<String> = full.seen_items.read().keys().cloned().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["key6", "key7", "key8", "key9"]);
+
+        // Count windows keep their order across the round trip
+        let window = Deduplicator::new_recent(3).unwrap();
+        assert_eq!(window.load_state(&source.encode_state().unwrap()).unwrap(), 3);
+        window.check_and_add("key10".to_string(), 10);
+        assert!(!window.contains("key7".to_string()));
+        window.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let dedup = Deduplicator::new(None);
+        let empty = dedup.get_memory_usage();
+        dedup.check_and_add("a".repeat(10), 0);
+        let capacity = dedup.seen_items.read().capacity();
+        let table = capacity * (std::mem::size_of::<(String, Stamp)>() + 1);
+        assert_eq!(dedup.get_memory_usage() - empty, (table + 10) as f64);
+
+        // Same table, longer key: only the key bytes differ
+        dedup.check_and_add("b".repeat(1000), 0);
+        assert_eq!(dedup.seen_items.read().capacity(), capacity);
+        assert_eq!(dedup.get_memory_usage() - empty, (table + 1010) as f64);
+
+        // Count windows also keep each key in the insertion order
+        let window = Deduplicator::new_recent(4).unwrap();
+        let before = window.get_memory_usage();
+        window.check_and_add("c".repeat(100), 0);
+        assert!(window.get_memory_usage() - before >= 200.0);
+    }
+
+    #[test]
+    fn test_bloom_false_positive_rate() {
+        let dedup = Deduplicator::new_bloom(100_000, 0.01).unwrap();
+        let footprint = dedup.get_memory_usage();
+
+        // Inserted keys are always reported as duplicates
+        let keys: Vec<String> = (0..100_000).map(|i| format!("route-{}", i)).collect();
+        let fresh = dedup.check_batch(keys.clone(), 0).iter().filter(|dup| !**dup).count();
+        assert!(dedup.check_batch(keys, 0).iter().all(|dup| *dup));
+        assert_eq!(dedup.get_cache_size() as usize, fresh);
+
+        // New keys at full load: ~1% false positives
+        let false_positives = (0..100_000).filter(|i| dedup.contains(format!("other-{}", i))).count();
+        let rate = false_positives as f64 / 100_000.0;
+        assert!((0.005..0.02).contains(&rate), "false positive rate {}", rate);
+
+        // Fixed footprint, about 9.6 bits per expected item at 1%
+        assert_eq!(dedup.get_memory_usage(), footprint);
+        assert!(footprint < 130_000.0);
+        assert!(dedup.resize(10).is_err());
+        assert!(dedup.encode_state().is_err());
+
+        dedup.clear();
+        assert_eq!(dedup.get_cache_size(), 0);
+        assert!(!dedup.check_and_add("route-1".to_string(), 0));
+        assert!(Deduplicator::new_bloom(0, 0.01).is_err());
+        assert!(Deduplicator::new_bloom(10, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_seeded_instances_match() {
+        let run = |seed: u32| {
+            let dedup = Deduplicator::new_with_seed(seed);
+            dedup.set_eviction_strategy("random".to_string()).unwrap();
+            dedup.resize(64).unwrap();
+            for i in 0..500 {
+                dedup.check_and_add(format!("key{}", i % 300), i);
+            }
+            let keys: Vec<String> = dedup.seen_items.read().keys().cloned().collect();
+            keys
+        };
+
+        // Same retained set, in the same hash order
+        let first = run(7);
+        assert_eq!(first, run(7));
+        assert!(first.len() <= 64);
+        let mut other = run(8);
+        let mut sorted = first.clone();
+        other.sort();
+        sorted.sort();
+        assert_ne!(sorted, other);
+    }
+
+    #[test]
+    fn test_batch_overflow_keeps_newest_insertions() {
+        let dedup = Deduplicator::new(None);
+        dedup.resize(8).unwrap();
+        // One batch, one timestamp: only insertion order tells the keys apart.
+        // Names run against that order so a key-order tie-break would differ.
+        let keys: Vec<String> = (0..8).map(|i| format!("key{}", 9 - i)).collect();
+        assert_eq!(dedup.check_batch(keys, 100), vec![false; 8]);
+
+        // The 9th key overflows: the oldest half (key9..key6) goes
+        assert_eq!(dedup.check_batch(vec!["new".to_string()], 100), vec![false]);
+        let mut kept: Vec<String> = dedup.seen_items.read().keys().cloned().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["key2", "key3", "key4", "key5", "new"]);
+    }
+
+    #[test]
+    fn test_merge_state() {
+        let shard = |keys: &[&str], repeat: &str| {
+            let dedup = Deduplicator::new(None);
+            for (i, key) in keys.iter().enumerate() {
+                dedup.check_and_add(key.to_string(), i as i64);
+            }
+            dedup.check_and_add(repeat.to_string(), 100);
+            dedup.encode_state().unwrap()
+        };
+
+        // Disjoint shards: every key is new and the stats add up
+        let master = Deduplicator::new(None);
+        master.check_and_add("m".to_string(), 0);
+        assert_eq!(master.merge_state(&shard(&["a", "b"], "a")).unwrap(), 2);
+        assert_eq!(master.merge_state(&shard(&["c", "d", "e"], "c")).unwrap(), 3);
+        assert_eq!(master.get_cache_size(), 6);
+        let stats = master.get_stats();
+        assert_eq!((stats.total_checked, stats.duplicates_found), (8.0, 2.0));
+        assert!(master.contains("e".to_string()));
+        assert!(master.check_and_add("d".to_string(), 200));
+
+        // Overlapping: only the unseen keys are added; merging again adds none
+        let overlapping = shard(&["b", "c", "f"], "f");
+        assert_eq!(master.merge_state(&overlapping).unwrap(), 1);
+        assert_eq!(master.merge_state(&overlapping).unwrap(), 0);
+        assert_eq!(master.get_cache_size(), 7);
+        let stats = master.get_stats();
+        assert_eq!((stats.total_checked, stats.duplicates_found), (13.0, 4.0));
+        master.check_invariants().unwrap();
+
+        // Past the max size the oldest go
+        let small = Deduplicator::new(None);
+        small.resize(3).unwrap();
+        small.check_and_add("old".to_string(), -10);
+        assert_eq!(small.merge_state(&shard(&["a", "b", "c"], "a")).unwrap(), 3);
+        assert_eq!(small.get_cache_size(), 3);
+        assert!(!small.contains("old".to_string()));
+        assert_eq!(small.get_merge_evictions(), 1.0);
+
+        // Count windows merge in snapshot order
+        let window = Deduplicator::new_recent(2).unwrap();
+        assert_eq!(window.merge_state(&shard(&["a", "b", "c"], "a")).unwrap(), 3);
+        assert!(!window.contains("a".to_string()));
+        window.check_invariants().unwrap();
+
+        assert!(master.merge_state(&[9, 9]).is_err());
+        assert!(Deduplicator::new_bloom(100, 0.01).unwrap().merge_state(&overlapping).is_err());
+
+        // Periodic merges keep only a bounded history, which `clear` drops
+        let periodic = Deduplicator::new(None);
+        for i in 0..MERGED_SNAPSHOT_HISTORY + 5 {
+            periodic.merge_state(&shard(&[&format!("k{}", i)], "r")).unwrap();
+        }
+        assert_eq!(periodic.stats.read().merged_snapshots.len(), MERGED_SNAPSHOT_HISTORY);
+        periodic.clear();
+        assert!(periodic.stats.read().merged_snapshots.is_empty());
+    }
 }
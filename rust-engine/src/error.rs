@@ -0,0 +1,76 @@
+// Crate error type
+// Surfaces validation failures to JS as exceptions with a clear message
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A constructor or setter argument is outside its valid range
+    InvalidArgument { name: &'static str, message: String },
+    /// A price or amount string could not be parsed
+    InvalidPrice { raw: String },
+    /// Structurally malformed input (mismatched columns, bad offsets, ...)
+    InvalidInput(String),
+    /// A method that reads the wall clock was called in deterministic mode
+    ClockUnavailable { method: &'static str },
+    /// `self_check` found an internal invariant broken
+    CorruptState(String),
+}
+
+impl Error {
+    pub(crate) fn invalid_argument(name: &'static str, message: impl Into<String>) -> Self {
+        Error::InvalidArgument { name, message: message.into() }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidArgument { name, message } => write!(f, "invalid {}: {}", name, message),
+            Error::InvalidPrice { raw } => write!(f, "invalid price '{}'", raw),
+            Error::InvalidInput(message) => write!(f, "invalid input: {}", message),
+            Error::ClockUnavailable { method } => {
+                write!(f, "{} reads the wall clock, which deterministic mode forbids; pass now_ms explicitly", method)
+            }
+            Error::CorruptState(message) => write!(f, "corrupt state: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for napi::Error {
+    fn from(err: Error) -> Self {
+        let status = match err {
+            Error::CorruptState(_) => napi::Status::GenericFailure,
+            _ => napi::Status::InvalidArg,
+        };
+        napi::Error::new(status, err.to_string())
+    }
+}
+
+/// Why a strict price calculation refused its input
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceError {
+    /// A price string is not an exact decimal
+    ParseFailure { source: String, raw: String },
+    /// Nothing to aggregate
+    EmptyInput,
+}
+
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceError::ParseFailure { source, raw } => write!(f, "unparseable price '{}' from source '{}'", raw, source),
+            PriceError::EmptyInput => write!(f, "no prices given"),
+        }
+    }
+}
+
+impl std::error::Error for PriceError {}
+
+impl From<PriceError> for napi::Error {
+    fn from(err: PriceError) -> Self {
+        napi::Error::new(napi::Status::InvalidArg, err.to_string())
+    }
+}
@@ -3,30 +3,127 @@
 use napi_derive::napi;
 use parking_lot::RwLock;
 use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 mod turbo_scanner;
 mod turbo_aggregator;
 mod deduplicator;
 mod lightweight_mode;
+mod timing;
+mod columnar;
+mod error;
+mod conversions;
+mod price_codec;
+mod seen_cache;
+mod metrics;
+#[cfg(feature = "proto")]
+mod proto;
 
-pub use turbo_scanner::TurboScanner;
-pub use turbo_aggregator::TurboAggregator;
+pub use turbo_scanner::{TurboScanner, ImpactModel, KeyStrategy, BpsBasis, OpportunityDiff, apply_slippage, diff_opportunities};
+pub use turbo_aggregator::{TurboAggregator, compute_realized_slippage};
 pub use deduplicator::Deduplicator;
-pub use lightweight_mode::LightweightConfig;
+pub use lightweight_mode::{LightweightConfig, get_lightweight_description};
+pub use error::{Error, PriceError};
+pub use conversions::{apply_bps, bps_to_ratio, ratio_to_bps};
+pub use price_codec::{prices_to_bytes, prices_from_bytes};
+pub use metrics::{EngineMetrics, engine_metrics};
+pub use columnar::{ColumnarBatch, opportunities_to_columnar, columnar_to_opportunities};
+#[cfg(feature = "proto")]
+pub use proto::{OpportunityBatch, OpportunityMessage, PriceDataMessage, opportunities_to_proto, opportunities_from_proto};
 
-// Global configuration for lightweight mode
-static LIGHTWEIGHT_MODE: Lazy<RwLock<bool>> = Lazy::new(|| RwLock::new(false));
+// Global engine modes (lightweight, deterministic)
+#[derive(Debug, Clone, Copy)]
+struct Modes {
+    lightweight: bool,
+    deterministic: bool,
+}
+
+const DEFAULT_MODES: Modes = Modes { lightweight: false, deterministic: false };
+
+static MODES: Lazy<RwLock<Modes>> = Lazy::new(|| RwLock::new(DEFAULT_MODES));
+
+/// Held by tests that flip a global mode, so they run one at a time (the
+/// crate's `.cargo/config.toml` also runs the suite on one thread, since
+/// every engine reads the modes). Restores the defaults on drop, including
+/// when the test panics.
+#[cfg(test)]
+pub(crate) struct ModeGuard(#[allow(dead_code)] parking_lot::MutexGuard<'static, ()>);
+
+#[cfg(test)]
+static MODE_TEST_LOCK: parking_lot::Mutex<()> = parking_lot::const_mutex(());
+
+#[cfg(test)]
+impl ModeGuard {
+    pub(crate) fn acquire() -> Self {
+        let guard = MODE_TEST_LOCK.lock();
+        *MODES.write() = DEFAULT_MODES;
+        ModeGuard(guard)
+    }
+}
+
+#[cfg(test)]
+impl Drop for ModeGuard {
+    fn drop(&mut self) {
+        *MODES.write() = DEFAULT_MODES;
+    }
+}
 
 #[napi]
 pub fn set_lightweight_mode(enabled: bool) {
-    let mut mode = LIGHTWEIGHT_MODE.write();
-    *mode = enabled;
+    MODES.write().lightweight = enabled;
 }
 
 #[napi]
 pub fn is_lightweight_mode() -> bool {
-    *LIGHTWEIGHT_MODE.read()
+    MODES.read().lightweight
+}
+
+/// Deterministic replay mode for backtests (default off). Turn it on before
+/// constructing any engine; while it is on:
+///
+/// - Hash maps and sets created by engine constructors use fixed seeds
+///   instead of per-process random ones, so their iteration order repeats
+///   from run to run on the same build.
+/// - `Deduplicator` random eviction starts from a fixed seed instead of
+///   process entropy (`set_eviction_seed` still overrides it).
+/// - Methods that would read the wall clock return an error instead:
+///   `TurboScanner::filter_opportunities`, `filter_with_reasons`,
+///   `filter_opportunities_net`, `filter_opportunities_slippage`,
+///   `filter_opportunities_validated`, `rank_opportunities`, `scan_one`,
+///   `begin_batch`, `preload_seen`, `import_seen`, and `export_seen` while
+///   a dedup TTL is set;
+///   `TurboAggregator::start_gc`. Use
+///   `filter_opportunities_at`, `filter_opportunities_explained`,
+///   `filter_opportunities_net_at`, `filter_opportunities_slippage_at`,
+///   `filter_opportunities_validated_at`, `scan_one_at` and
+///   `begin_batch_at`.
+/// - `calculate_median_price` skips its memo (the memo TTL is wall-clock
+///   time); results are unchanged.
+/// - `self_check` skips its future-timestamp checks.
+/// - Opt-in call timing records nothing.
+#[napi]
+pub fn set_deterministic(enabled: bool) {
+    MODES.write().deterministic = enabled;
+}
+
+#[napi]
+pub fn is_deterministic() -> bool {
+    MODES.read().deterministic
+}
+
+/// Hasher for engine-owned maps: fixed seeds in deterministic mode
+pub(crate) fn hash_state() -> ahash::RandomState {
+    if is_deterministic() {
+        ahash::RandomState::with_seeds(
+            0x243F_6A88_85A3_08D3,
+            0x1319_8A2E_0370_7344,
+            0xA409_3822_299F_31D0,
+            0x082E_FA98_EC4E_6C89,
+        )
+    } else {
+        ahash::RandomState::new()
+    }
 }
 
 // Opportunity structure optimized for ARM with proper alignment
@@ -40,6 +137,31 @@ pub struct Opportunity {
     pub profit: String,
     pub profit_bps: i32,
     pub timestamp: i64,
+    /// Executable size at the quoted profit, in input units (optional)
+    #[serde(default)]
+    pub liquidity: Option<String>,
+    /// When the quoted prices were observed, in ms (optional). Drives the
+    /// scanner's staleness confidence; unset means age unknown.
+    #[serde(default)]
+    pub price_timestamp: Option<i64>,
+}
+
+impl Opportunity {
+    /// Fractional return `profit / input_amount`; `None` on zero input or
+    /// unparseable amounts. Unlike `profit_bps` this is not rounded.
+    pub fn profit_ratio(&self) -> Option<f64> {
+        let profit = parse_price(&self.profit)?;
+        let input = parse_price(&self.input_amount)?;
+        if input == 0.0 {
+            return None;
+        }
+        Some(profit / input)
+    }
+}
+
+#[napi]
+pub fn opportunity_profit_ratio(opp: Opportunity) -> Option<f64> {
+    opp.profit_ratio()
 }
 
 // Price data structure with ARM-friendly alignment
@@ -53,15 +175,168 @@ pub struct PriceData {
     pub timestamp: i64,
 }
 
+/// Wall-clock time for `method`, called without an explicit `now_ms`;
+/// fails in deterministic mode
+pub(crate) fn wall_clock_ms(method: &'static str) -> Result<i64, Error> {
+    if is_deterministic() {
+        return Err(Error::ClockUnavailable { method });
+    }
+    Ok(now_ms())
+}
+
+/// Wall-clock time in ms since the Unix epoch. Callers outside tests and
+/// deterministic-mode-aware paths should go through `wall_clock_ms`.
+pub(crate) fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Heap bytes of a hash map's table, excluding anything its keys and
+/// values own: one slot plus one control byte per bucket
+pub(crate) fn table_bytes<K, V, S>(map: &std::collections::HashMap<K, V, S>) -> usize {
+    map.capacity() * (std::mem::size_of::<(K, V)>() + 1)
+}
+
+/// How far past the wall clock a stored timestamp may be before `self_check` flags it
+pub(crate) const SELF_CHECK_FUTURE_TOLERANCE_MS: i64 = 60_000;
+
+/// Shared price/amount parser: accepts finite decimal strings only
+pub(crate) fn parse_price(raw: &str) -> Option<f64> {
+    raw.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// Exact counterpart of `parse_price` for comparisons that must not lose
+/// precision (up to 28 significant digits, e.g. 18-decimal token prices).
+/// Plain decimals that would need rounding to fit are rejected rather than
+/// silently approximated; scientific notation is also accepted.
+pub(crate) fn parse_decimal(raw: &str) -> Option<Decimal> {
+    let raw = raw.trim();
+    match Decimal::from_str_exact(raw) {
+        Ok(value) => Some(value),
+        Err(_) if raw.contains(['e', 'E']) => Decimal::from_scientific(raw).ok(),
+        Err(_) => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_lightweight_mode() {
+        let _modes = crate::ModeGuard::acquire();
         set_lightweight_mode(true);
         assert!(is_lightweight_mode());
         set_lightweight_mode(false);
         assert!(!is_lightweight_mode());
     }
+
+    #[test]
+    fn test_profit_ratio() {
+        let mut opp = Opportunity {
+            path: vec!["A".to_string(), "B".to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "3000".to_string(),
+            output_amount: "3001".to_string(),
+            profit: "1".to_string(),
+            profit_bps: 3,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        // 1/3000 is ~3.33 bps, more precise than the rounded profit_bps
+        let ratio = opportunity_profit_ratio(opp.clone()).unwrap();
+        assert!((ratio - 1.0 / 3000.0).abs() < 1e-15);
+
+        opp.input_amount = "0".to_string();
+        assert!(opp.profit_ratio().is_none());
+        opp.input_amount = "abc".to_string();
+        assert!(opp.profit_ratio().is_none());
+    }
+
+    /// One backtest step across every engine, rendered to comparable text
+    fn replay() -> Vec<String> {
+        let opp = |mid: usize, bps: i32| Opportunity {
+            path: vec!["WETH".to_string(), format!("T{}", mid), "WETH".to_string()],
+            dexes: vec!["uniswap".to_string(), "sushiswap".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1010".to_string(),
+            profit: "10".to_string(),
+            profit_bps: bps,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: Some(1_000),
+        };
+        let price = |pair: usize, source: usize| PriceData {
+            token_a: format!("T{}", pair),
+            token_b: "USDC".to_string(),
+            price: format!("{}.{}", 100 + pair, source),
+            source: format!("dex{}", source),
+            timestamp: 1_000,
+        };
+        let mut log = Vec::new();
+
+        let scanner = TurboScanner::new(50, None, None);
+        assert!(scanner.filter_opportunities(vec![opp(0, 100)]).is_err());
+        assert!(scanner.begin_batch().is_err());
+        assert!(scanner.filter_opportunities_validated(vec![opp(0, 100)], None).is_err());
+        scanner.set_sample_rate(0.5).unwrap();
+        let opps: Vec<Opportunity> = (0..300).map(|i| opp(i % 200, 40 + (i % 7) as i32 * 10)).collect();
+        let accepted = scanner.filter_opportunities_at(opps, 1_000);
+        log.push(serde_json::to_string(&accepted).unwrap());
+        log.push(format!("{:?}", scanner.export_seen().unwrap()));
+        set_lightweight_mode(true);
+        log.push(format!("{} {}", scanner.reconfigure(), scanner.get_cache_size()));
+        set_lightweight_mode(false);
+
+        let aggregator = TurboAggregator::new(5_000, None);
+        aggregator.set_memoization(60_000);
+        let prices: Vec<PriceData> = (0..40).map(|i| price(i % 8, i % 5)).collect();
+        log.push(serde_json::to_string(&aggregator.aggregate_prices(prices.clone(), 1_000)).unwrap());
+        for group in aggregator.aggregate_grouped(prices.clone(), 2_000) {
+            log.push(serde_json::to_string(&(group.token_a, group.prices, group.median)).unwrap());
+        }
+        log.push(serde_json::to_string(&aggregator.calculate_median_price(prices)).unwrap());
+
+        let dedup = Deduplicator::new(None);
+        for i in 0..500 {
+            dedup.check_and_add(format!("key{}", i), i);
+        }
+        dedup.resize(100).unwrap();
+        let random = Deduplicator::new(None);
+        random.set_eviction_strategy("random".to_string()).unwrap();
+        for i in 0..500 {
+            random.check_and_add(format!("key{}", i), i);
+        }
+        random.resize(100).unwrap();
+        for i in 0..500 {
+            let key = format!("key{}", i);
+            log.push(format!("{} {}", dedup.contains(key.clone()), random.contains(key)));
+        }
+        log
+    }
+
+    #[test]
+    fn test_deterministic_replay() {
+        let _modes = crate::ModeGuard::acquire();
+        set_deterministic(true);
+        let first = replay();
+        let second = replay();
+        set_deterministic(false);
+
+        assert_eq!(first, second);
+        assert!(TurboScanner::new(50, None, None).filter_opportunities(Vec::new()).is_ok());
+
+        // Oldest-first eviction keeps the newest keys
+        let survivors: Vec<usize> = first[first.len() - 500..]
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.starts_with("true"))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(survivors, (400..500).collect::<Vec<_>>());
+    }
 }
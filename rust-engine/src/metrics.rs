@@ -0,0 +1,93 @@
+// Combined operational metrics for exporters (e.g. a Prometheus scrape)
+// One FFI call instead of a getter per engine
+
+use napi_derive::napi;
+use crate::{is_lightweight_mode, Deduplicator, TurboAggregator, TurboScanner};
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineMetrics {
+    /// `TurboScanner::get_scan_count`
+    pub scan_count: f64,
+    /// `TurboAggregator::get_cache_size`
+    pub aggregator_cache_size: u32,
+    /// `Deduplicator::get_stats().total_checked`
+    pub dedup_total_checked: f64,
+    /// `Deduplicator::get_stats().duplicates_found`
+    pub dedup_duplicates: f64,
+    /// Aggregator cache plus deduplicator memory, as their `get_memory_usage`
+    pub memory_bytes: f64,
+    pub lightweight: bool,
+}
+
+/// Gather the usual scrape metrics of one engine set in a single call.
+/// Each value comes from the matching getter, so only read locks are taken,
+/// one getter at a time; the numbers are not a consistent snapshot across
+/// engines that are being updated concurrently.
+#[napi]
+pub fn engine_metrics(scanner: &TurboScanner, aggregator: &TurboAggregator, dedup: &Deduplicator) -> EngineMetrics {
+    let dedup_stats = dedup.get_stats();
+    EngineMetrics {
+        scan_count: scanner.get_scan_count(),
+        aggregator_cache_size: aggregator.get_cache_size(),
+        dedup_total_checked: dedup_stats.total_checked,
+        dedup_duplicates: dedup_stats.duplicates_found,
+        memory_bytes: aggregator.get_memory_usage() + dedup.get_memory_usage(),
+        lightweight: is_lightweight_mode(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{set_lightweight_mode, Opportunity, PriceData};
+
+    #[test]
+    fn test_engine_metrics_match_getters() {
+        let _modes = crate::ModeGuard::acquire();
+        let scanner = TurboScanner::new(50, None, None);
+        let aggregator = TurboAggregator::new(5_000, None);
+        let dedup = Deduplicator::new(None);
+
+        let opp = Opportunity {
+            path: vec!["A".to_string(), "B".to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+        scanner.filter_opportunities_at(vec![opp.clone()], 0);
+        scanner.filter_opportunities_at(vec![opp], 1);
+        let price = PriceData {
+            token_a: "WETH".to_string(),
+            token_b: "USDC".to_string(),
+            price: "3000".to_string(),
+            source: "dex1".to_string(),
+            timestamp: 0,
+        };
+        aggregator.aggregate_prices(vec![price], 0);
+        for key in ["a", "b", "a"] {
+            dedup.check_and_add(key.to_string(), 0);
+        }
+        set_lightweight_mode(true);
+
+        let metrics = engine_metrics(&scanner, &aggregator, &dedup);
+        assert_eq!(
+            metrics,
+            EngineMetrics {
+                scan_count: 2.0,
+                aggregator_cache_size: 1,
+                dedup_total_checked: 3.0,
+                dedup_duplicates: 1.0,
+                memory_bytes: aggregator.get_memory_usage() + dedup.get_memory_usage(),
+                lightweight: true,
+            }
+        );
+        assert_eq!(metrics.scan_count, scanner.get_scan_count());
+        set_lightweight_mode(false);
+    }
+}
@@ -0,0 +1,195 @@
+// Compact binary encoding for PriceData batches
+// Lets JS hand over one Buffer instead of thousands of objects
+//
+// Layout (all integers little-endian):
+//
+//   u8   version (currently 1)
+//   u32  record count
+//   per record:
+//     u32 len + UTF-8 bytes   token_a
+//     u32 len + UTF-8 bytes   token_b
+//     u32 len + UTF-8 bytes   price
+//     u32 len + UTF-8 bytes   source
+//     i64                     timestamp
+//
+// A batch must be consumed exactly: trailing bytes are an error.
+//
+// Building the Buffer with `prices_to_bytes` does the same marshaling as
+// passing the objects, so `aggregate_prices_bytes` only saves time when the
+// producer writes this layout directly (e.g. a feed decoder filling a Buffer).
+// Measured by scripts/benchmark-price-codec.js (release build, Node 20, one
+// x86_64 core, 100 to 10000 prices): a prebuilt Buffer aggregates 1.2x to
+// 1.6x faster than the objects; encoding it in the same call is at parity
+// (0.84x to 1.28x).
+
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use crate::{Error, PriceData};
+
+const FORMAT_VERSION: u8 = 1;
+
+/// Smallest possible record: four empty strings and a timestamp
+const MIN_RECORD_LEN: usize = 4 * 4 + 8;
+
+pub(crate) fn encode_prices(prices: &[PriceData]) -> Vec<u8> {
+    let strings: usize = prices
+        .iter()
+        .map(|p| p.token_a.len() + p.token_b.len() + p.price.len() + p.source.len())
+        .sum();
+    let mut out = Vec::with_capacity(5 + prices.len() * MIN_RECORD_LEN + strings);
+
+    out.push(FORMAT_VERSION);
+    out.extend_from_slice(&(prices.len() as u32).to_le_bytes());
+    for price in prices {
+        for field in [&price.token_a, &price.token_b, &price.price, &price.source] {
+            out.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            out.extend_from_slice(field.as_bytes());
+        }
+        out.extend_from_slice(&price.timestamp.to_le_bytes());
+    }
+    out
+}
+
+pub(crate) fn decode_prices(bytes: &[u8]) -> Result<Vec<PriceData>, Error> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    let version = reader.take(1)?[0];
+    if version != FORMAT_VERSION {
+        return Err(Error::InvalidInput(format!("unsupported price batch version {}", version)));
+    }
+    let count = reader.u32()? as usize;
+    // Don't trust the count for the allocation beyond what the bytes can hold
+    let mut prices = Vec::with_capacity(count.min(bytes.len() / MIN_RECORD_LEN));
+
+    for _ in 0..count {
+        prices.push(PriceData {
+            token_a: reader.string()?,
+            token_b: reader.string()?,
+            price: reader.string()?,
+            source: reader.string()?,
+            timestamp: i64::from_le_bytes(reader.take(8)?.try_into().unwrap_or_default()),
+        });
+    }
+
+    if reader.pos != bytes.len() {
+        return Err(Error::InvalidInput(format!(
+            "{} trailing bytes after price batch",
+            bytes.len() - reader.pos
+        )));
+    }
+    Ok(prices)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len()).ok_or_else(|| {
+            Error::InvalidInput(format!("price batch truncated at byte {}", self.pos))
+        })?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap_or_default()))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        let at = self.pos;
+        let raw = self.take(len)?;
+        String::from_utf8(raw.to_vec())
+            .map_err(|_| Error::InvalidInput(format!("invalid UTF-8 in price batch at byte {}", at)))
+    }
+}
+
+/// Encode prices in the compact layout described at the top of this file
+#[napi]
+pub fn prices_to_bytes(prices: Vec<PriceData>) -> Buffer {
+    encode_prices(&prices).into()
+}
+
+/// Decode a buffer produced by `prices_to_bytes` (or a compatible encoder)
+#[napi]
+pub fn prices_from_bytes(bytes: Buffer) -> napi::Result<Vec<PriceData>> {
+    Ok(decode_prices(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tiny deterministic generator so the fuzz cases are reproducible
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            self.0 >> 33
+        }
+
+        fn string(&mut self) -> String {
+            const ALPHABET: [&str; 8] = ["A", "b", "9", ".", "-", "é", "\u{1F4B1}", ""];
+            (0..self.next() % 12).map(|_| ALPHABET[(self.next() % 8) as usize]).collect()
+        }
+    }
+
+    #[test]
+    fn test_price_codec_round_trip_fuzz() {
+        let mut rng = Lcg(7);
+        for _ in 0..200 {
+            let prices: Vec<PriceData> = (0..rng.next() % 20)
+                .map(|_| PriceData {
+                    token_a: rng.string(),
+                    token_b: rng.string(),
+                    price: rng.string(),
+                    source: rng.string(),
+                    timestamp: (rng.next() as i64) << 31 ^ -(rng.next() as i64),
+                })
+                .collect();
+
+            let bytes = encode_prices(&prices);
+            let decoded = decode_prices(&bytes).unwrap();
+            assert_eq!(decoded.len(), prices.len());
+            for (a, b) in decoded.iter().zip(&prices) {
+                assert_eq!(
+                    (&a.token_a, &a.token_b, &a.price, &a.source, a.timestamp),
+                    (&b.token_a, &b.token_b, &b.price, &b.source, b.timestamp)
+                );
+            }
+
+            // Every strict prefix is rejected rather than misread
+            if !bytes.is_empty() {
+                let cut = (rng.next() as usize) % bytes.len();
+                assert!(decode_prices(&bytes[..cut]).is_err());
+            }
+
+            // Random corruption must never panic
+            let mut corrupted = bytes.clone();
+            for _ in 0..4 {
+                let i = (rng.next() as usize) % corrupted.len();
+                corrupted[i] = rng.next() as u8;
+            }
+            let _ = decode_prices(&corrupted);
+        }
+    }
+
+    #[test]
+    fn test_price_codec_rejects_malformed_input() {
+        assert!(decode_prices(&[]).is_err());
+        assert!(decode_prices(&[2, 0, 0, 0, 0]).is_err());
+        assert_eq!(decode_prices(&[1, 0, 0, 0, 0]).unwrap().len(), 0);
+        assert!(decode_prices(&[1, 0, 0, 0, 0, 0]).is_err());
+        // Huge claimed count with no records
+        assert!(decode_prices(&[1, 0xff, 0xff, 0xff, 0xff]).is_err());
+        // Invalid UTF-8 in token_a
+        let mut bytes = vec![1, 1, 0, 0, 0, 1, 0, 0, 0, 0xff];
+        bytes.extend_from_slice(&[0; 3 * 4 + 8]);
+        assert!(decode_prices(&bytes).is_err());
+    }
+}
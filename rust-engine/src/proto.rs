@@ -0,0 +1,215 @@
+// Protobuf wire format (feature "proto")
+// Hand-written prost messages for interop with non-JS services
+//
+// Schema, for generating the other side:
+//
+//   syntax = "proto3";
+//   package shango;
+//
+//   message Opportunity {
+//     repeated string path = 1;
+//     repeated string dexes = 2;
+//     string input_amount = 3;
+//     string output_amount = 4;
+//     string profit = 5;
+//     int32 profit_bps = 6;
+//     int64 timestamp = 7;
+//     optional string liquidity = 8;
+//     optional int64 price_timestamp = 9;
+//   }
+//
+//   message PriceData {
+//     string token_a = 1;
+//     string token_b = 2;
+//     string price = 3;
+//     string source = 4;
+//     int64 timestamp = 5;
+//   }
+//
+//   message OpportunityBatch {
+//     repeated Opportunity opportunities = 1;
+//   }
+//
+// Amounts stay decimal strings, exactly as in the JS objects.
+
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use prost::Message;
+use crate::{Error, Opportunity, PriceData};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OpportunityMessage {
+    #[prost(string, repeated, tag = "1")]
+    pub path: Vec<String>,
+    #[prost(string, repeated, tag = "2")]
+    pub dexes: Vec<String>,
+    #[prost(string, tag = "3")]
+    pub input_amount: String,
+    #[prost(string, tag = "4")]
+    pub output_amount: String,
+    #[prost(string, tag = "5")]
+    pub profit: String,
+    #[prost(int32, tag = "6")]
+    pub profit_bps: i32,
+    #[prost(int64, tag = "7")]
+    pub timestamp: i64,
+    #[prost(string, optional, tag = "8")]
+    pub liquidity: Option<String>,
+    #[prost(int64, optional, tag = "9")]
+    pub price_timestamp: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PriceDataMessage {
+    #[prost(string, tag = "1")]
+    pub token_a: String,
+    #[prost(string, tag = "2")]
+    pub token_b: String,
+    #[prost(string, tag = "3")]
+    pub price: String,
+    #[prost(string, tag = "4")]
+    pub source: String,
+    #[prost(int64, tag = "5")]
+    pub timestamp: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OpportunityBatch {
+    #[prost(message, repeated, tag = "1")]
+    pub opportunities: Vec<OpportunityMessage>,
+}
+
+impl From<Opportunity> for OpportunityMessage {
+    fn from(opp: Opportunity) -> Self {
+        Self {
+            path: opp.path,
+            dexes: opp.dexes,
+            input_amount: opp.input_amount,
+            output_amount: opp.output_amount,
+            profit: opp.profit,
+            profit_bps: opp.profit_bps,
+            timestamp: opp.timestamp,
+            liquidity: opp.liquidity,
+            price_timestamp: opp.price_timestamp,
+        }
+    }
+}
+
+impl From<OpportunityMessage> for Opportunity {
+    fn from(msg: OpportunityMessage) -> Self {
+        Self {
+            path: msg.path,
+            dexes: msg.dexes,
+            input_amount: msg.input_amount,
+            output_amount: msg.output_amount,
+            profit: msg.profit,
+            profit_bps: msg.profit_bps,
+            timestamp: msg.timestamp,
+            liquidity: msg.liquidity,
+            price_timestamp: msg.price_timestamp,
+        }
+    }
+}
+
+impl From<PriceData> for PriceDataMessage {
+    fn from(price: PriceData) -> Self {
+        Self {
+            token_a: price.token_a,
+            token_b: price.token_b,
+            price: price.price,
+            source: price.source,
+            timestamp: price.timestamp,
+        }
+    }
+}
+
+impl From<PriceDataMessage> for PriceData {
+    fn from(msg: PriceDataMessage) -> Self {
+        Self {
+            token_a: msg.token_a,
+            token_b: msg.token_b,
+            price: msg.price,
+            source: msg.source,
+            timestamp: msg.timestamp,
+        }
+    }
+}
+
+pub(crate) fn encode_opportunities(opps: Vec<Opportunity>) -> Vec<u8> {
+    OpportunityBatch { opportunities: opps.into_iter().map(Into::into).collect() }.encode_to_vec()
+}
+
+pub(crate) fn decode_opportunities(bytes: &[u8]) -> Result<Vec<Opportunity>, Error> {
+    let batch = OpportunityBatch::decode(bytes)
+        .map_err(|e| Error::InvalidInput(format!("malformed opportunity batch: {}", e)))?;
+    Ok(batch.opportunities.into_iter().map(Into::into).collect())
+}
+
+/// Encode opportunities as one `OpportunityBatch` message
+#[napi]
+pub fn opportunities_to_proto(opps: Vec<Opportunity>) -> Buffer {
+    encode_opportunities(opps).into()
+}
+
+/// Decode an `OpportunityBatch` message
+#[napi]
+pub fn opportunities_from_proto(bytes: Buffer) -> napi::Result<Vec<Opportunity>> {
+    Ok(decode_opportunities(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proto_round_trip() {
+        let opps = vec![
+            Opportunity {
+                path: vec!["WETH".to_string(), "USDC".to_string(), "WETH".to_string()],
+                dexes: vec!["uniswap".to_string(), "sushiswap".to_string()],
+                input_amount: "1000".to_string(),
+                output_amount: "1010".to_string(),
+                profit: "10".to_string(),
+                profit_bps: 100,
+                timestamp: 1_700_000_000_000,
+                liquidity: Some("50000".to_string()),
+                price_timestamp: None,
+            },
+            Opportunity {
+                path: Vec::new(),
+                dexes: Vec::new(),
+                input_amount: String::new(),
+                output_amount: "6".to_string(),
+                profit: "-1".to_string(),
+                profit_bps: -25,
+                timestamp: -1,
+                liquidity: None,
+                price_timestamp: Some(1_699_999_999_000),
+            },
+        ];
+
+        let bytes = encode_opportunities(opps.clone());
+        let decoded = decode_opportunities(&bytes).unwrap();
+        assert_eq!(decoded.len(), opps.len());
+        for (a, b) in decoded.into_iter().zip(opps) {
+            assert_eq!(OpportunityMessage::from(a), OpportunityMessage::from(b));
+        }
+
+        assert!(encode_opportunities(Vec::new()).is_empty());
+        assert!(decode_opportunities(&[0x0a, 0xff]).is_err());
+    }
+
+    #[test]
+    fn test_price_data_message_round_trip() {
+        let price = PriceData {
+            token_a: "WETH".to_string(),
+            token_b: "USDC".to_string(),
+            price: "3000.5".to_string(),
+            source: "uniswap".to_string(),
+            timestamp: 42,
+        };
+        let bytes = PriceDataMessage::from(price).encode_to_vec();
+        let decoded: PriceData = PriceDataMessage::decode(bytes.as_slice()).unwrap().into();
+        assert_eq!((decoded.price.as_str(), decoded.timestamp), ("3000.5", 42));
+    }
+}
@@ -0,0 +1,194 @@
+// Bounded LRU map behind the scanner's short-term dedup tier
+// Recency is a BTreeMap keyed by a use counter, so touch and evict are O(log n)
+
+use ahash::AHashMap;
+use std::collections::BTreeMap;
+use crate::hash_state;
+
+#[derive(Debug)]
+pub(crate) struct SeenCache {
+    /// Key -> (recorded_at_ms, last-use tick)
+    entries: AHashMap<String, (i64, u64)>,
+    /// Last-use tick -> key, least recently used first
+    recency: BTreeMap<u64, String>,
+    next_tick: u64,
+    /// `None` is unbounded
+    capacity: Option<usize>,
+}
+
+impl SeenCache {
+    pub(crate) fn new(capacity: Option<usize>) -> Self {
+        Self {
+            entries: AHashMap::with_hasher(hash_state()),
+            recency: BTreeMap::new(),
+            next_tick: 0,
+            capacity,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn contains_key(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// When `key` was recorded, without counting as a use
+    pub(crate) fn recorded_at(&self, key: &str) -> Option<i64> {
+        self.entries.get(key).map(|(at, _)| *at)
+    }
+
+    /// Mark `key` as just used so it is evicted last
+    pub(crate) fn touch(&mut self, key: &str) {
+        let tick = self.bump();
+        if let Some((_, last_used)) = self.entries.get_mut(key) {
+            if let Some(key) = self.recency.remove(last_used) {
+                self.recency.insert(tick, key);
+            }
+            *last_used = tick;
+        }
+    }
+
+    /// Record `key` at `recorded_at` as the most recently used entry,
+    /// replacing any previous stamp. Returns the number of evicted keys.
+    pub(crate) fn insert(&mut self, key: String, recorded_at: i64) -> usize {
+        let mut evicted = 0;
+        self.insert_reporting(key, recorded_at, |_| evicted += 1);
+        evicted
+    }
+
+    /// `insert`, handing each evicted key to `on_evict`
+    pub(crate) fn insert_reporting(&mut self, key: String, recorded_at: i64, on_evict: impl FnMut(String)) {
+        let tick = self.bump();
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (recorded_at, tick)) {
+            self.recency.remove(&last_used);
+        }
+        self.recency.insert(tick, key);
+        self.evict_reporting(on_evict);
+    }
+
+    /// `insert` only if `key` is absent; an existing entry keeps its stamp
+    /// and recency. Returns whether the key was inserted.
+    pub(crate) fn insert_if_absent(&mut self, key: String, recorded_at: i64) -> bool {
+        if self.entries.contains_key(&key) {
+            return false;
+        }
+        self.insert(key, recorded_at);
+        true
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) {
+        if let Some((_, last_used)) = self.entries.remove(key) {
+            self.recency.remove(&last_used);
+        }
+    }
+
+    /// Keep only entries for which `keep(key, recorded_at)` holds
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&str, i64) -> bool) {
+        let recency = &mut self.recency;
+        self.entries.retain(|key, (at, last_used)| {
+            let kept = keep(key, *at);
+            if !kept {
+                recency.remove(last_used);
+            }
+            kept
+        });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// `(key, recorded_at)` in no particular order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&String, i64)> {
+        self.entries.iter().map(|(key, (at, _))| (key, *at))
+    }
+
+    pub(crate) fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Change the bound, evicting least recently used keys if it shrank.
+    /// Returns the number of evicted keys.
+    pub(crate) fn set_capacity(&mut self, capacity: Option<usize>) -> usize {
+        self.capacity = capacity;
+        self.evict_to_capacity()
+    }
+
+    /// Internal consistency, for `self_check`
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if self.recency.len() != self.entries.len() {
+            return Err(format!(
+                "dedup recency tracks {} keys but the cache holds {}",
+                self.recency.len(),
+                self.entries.len()
+            ));
+        }
+        if let Some((tick, key)) = self.recency.iter().find(|(tick, key)| {
+            self.entries.get(key.as_str()).is_none_or(|(_, last_used)| last_used != *tick)
+        }) {
+            return Err(format!("dedup recency entry {} for '{}' does not match the cache", tick, key));
+        }
+        if let Some(capacity) = self.capacity.filter(|c| self.entries.len() > *c) {
+            return Err(format!("dedup cache holds {} keys (capacity {})", self.entries.len(), capacity));
+        }
+        Ok(())
+    }
+
+    fn bump(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    fn evict_to_capacity(&mut self) -> usize {
+        let mut evicted = 0;
+        self.evict_reporting(|_| evicted += 1);
+        evicted
+    }
+
+    fn evict_reporting(&mut self, mut on_evict: impl FnMut(String)) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        while self.entries.len() > capacity {
+            match self.recency.pop_first() {
+                Some((_, key)) => {
+                    self.entries.remove(&key);
+                    on_evict(key);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_order() {
+        let mut cache = SeenCache::new(Some(3));
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        cache.insert("c".to_string(), 3);
+        cache.touch("a");
+
+        // "b" is now least recently used
+        assert_eq!(cache.insert("d".to_string(), 4), 1);
+        assert!(!cache.contains_key("b"));
+        assert!(!cache.insert_if_absent("a".to_string(), 99));
+        assert_eq!(cache.recorded_at("a"), Some(1));
+
+        cache.retain(|key, _| key != "c");
+        assert_eq!(cache.set_capacity(Some(1)), 1);
+        assert!(cache.contains_key("d"));
+        cache.check().unwrap();
+
+        cache.remove("d");
+        assert_eq!(cache.len(), 0);
+        cache.check().unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+// Per-call timing instrumentation
+// Opt-in so the hot path doesn't pay for clock reads by default
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of recent calls included in the rolling average
+const TIMING_WINDOW: usize = 32;
+
+#[derive(Debug, Default)]
+pub(crate) struct CallTimer {
+    enabled: bool,
+    last_us: f64,
+    recent_us: VecDeque<f64>,
+}
+
+impl CallTimer {
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.last_us = 0.0;
+            self.recent_us.clear();
+        }
+    }
+
+    /// Start a measurement, or `None` when timing is disabled (or in
+    /// deterministic mode, which reads no clocks)
+    pub(crate) fn start(&self) -> Option<Instant> {
+        if self.enabled && !crate::is_deterministic() {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn record(&mut self, elapsed: Duration) {
+        let us = elapsed.as_secs_f64() * 1_000_000.0;
+        self.last_us = us;
+        if self.recent_us.len() == TIMING_WINDOW {
+            self.recent_us.pop_front();
+        }
+        self.recent_us.push_back(us);
+    }
+
+    pub(crate) fn last_us(&self) -> f64 {
+        self.last_us
+    }
+
+    #[cfg(test)]
+    pub(crate) fn recent_len(&self) -> usize {
+        self.recent_us.len()
+    }
+
+    pub(crate) fn average_us(&self) -> f64 {
+        if self.recent_us.is_empty() {
+            return 0.0;
+        }
+        self.recent_us.iter().sum::<f64>() / self.recent_us.len() as f64
+    }
+}
+
+/// Mean nanoseconds per call of `f` over `iters` calls, after one warm-up
+/// call. Used by the `#[ignore]`d benchmarks, which are meant to be run
+/// with `cargo test --release -- --ignored --nocapture`.
+#[cfg(test)]
+pub(crate) fn bench_ns_per_call(iters: u32, mut f: impl FnMut()) -> f64 {
+    f();
+    let started = Instant::now();
+    for _ in 0..iters {
+        f();
+    }
+    started.elapsed().as_nanos() as f64 / iters as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_timer_window() {
+        let mut timer = CallTimer::default();
+        assert!(timer.start().is_none());
+
+        timer.set_enabled(true);
+        assert!(timer.start().is_some());
+
+        for us in 1..=(TIMING_WINDOW as u64 + 8) {
+            timer.record(Duration::from_micros(us));
+        }
+        assert!((timer.last_us() - (TIMING_WINDOW as f64 + 8.0)).abs() < 1e-6);
+
+        // Only the last TIMING_WINDOW samples (9..=40) contribute
+        let expected = (9..=40).sum::<u64>() as f64 / TIMING_WINDOW as f64;
+        assert!((timer.average_us() - expected).abs() < 1e-6);
+
+        timer.set_enabled(false);
+        assert_eq!(timer.average_us(), 0.0);
+    }
+}
@@ -4,191 +4,3831 @@
 use napi_derive::napi;
 use ahash::AHashMap;
 use parking_lot::RwLock;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
 use std::sync::Arc;
-use crate::{PriceData, is_lightweight_mode};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use crate::{
+    Error, Opportunity, PriceData, PriceError, SELF_CHECK_FUTURE_TOLERANCE_MS, hash_state, is_deterministic, is_lightweight_mode, now_ms,
+    parse_decimal, parse_price, table_bytes, wall_clock_ms,
+};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
+use crate::timing::CallTimer;
+use crate::conversions::{bps_to_decimal_ratio, BPS_SCALE};
+use crate::price_codec::decode_prices;
+use napi::bindgen_prelude::Buffer;
+
+/// Signed slippage in bps between an expected and an executed price.
+/// Positive values are adverse: paying more than expected on a buy,
+/// receiving less than expected on a sell. Computed in exact decimals;
+/// only the result is converted to a float.
+#[napi]
+pub fn compute_realized_slippage(expected: PriceData, actual: PriceData, is_buy: bool) -> napi::Result<f64> {
+    let expected_val = parse_decimal(&expected.price)
+        .filter(|e| e.is_sign_positive() && !e.is_zero())
+        .ok_or(Error::InvalidPrice { raw: expected.price })?;
+    let actual_val = parse_decimal(&actual.price).ok_or_else(|| Error::InvalidPrice { raw: actual.price.clone() })?;
+
+    let diff = if is_buy { actual_val - expected_val } else { expected_val - actual_val };
+    let bps = diff
+        .checked_mul(Decimal::from(BPS_SCALE as i64))
+        .and_then(|scaled| scaled.checked_div(expected_val))
+        .ok_or(Error::InvalidPrice { raw: actual.price })?;
+    Ok(bps.to_f64().unwrap_or(f64::NAN))
+}
 
 /// High-performance price aggregator with ARM NEON optimizations
 #[napi]
 pub struct TurboAggregator {
     price_cache: Arc<RwLock<AHashMap<String, CachedPrice>>>,
-    cache_timeout_ms: i64,
-    dedup_window_ms: i64,
+    /// Most cache entries kept by `aggregate_prices` and `load_snapshot`;
+    /// 0 is unbounded
+    max_entries: usize,
+    /// (cached_at, key) of every entry cached under a `max_entries` bound,
+    /// oldest first. Entries removed or re-cached since are skipped (and
+    /// compacted away) lazily rather than tracked at every removal site.
+    cache_order: Arc<RwLock<BTreeSet<(i64, String)>>>,
+    /// Timeout as passed to the constructor or `set_cache_timeout_ms`,
+    /// before lightweight scaling
+    base_cache_timeout_ms: Arc<RwLock<i64>>,
+    cache_timeout_ms: Arc<RwLock<i64>>,
+    /// Lightweight flag as read by the constructor or the last
+    /// `reconfigure`; setters rescale with it rather than re-reading the mode
+    timeout_halved: Arc<RwLock<bool>>,
+    /// Optional [min, max] clamp applied after lightweight scaling
+    timeout_bounds: Arc<RwLock<Option<(i64, i64)>>>,
+    dedup_window_ms: Arc<RwLock<i64>>,
+    price_bounds: Arc<RwLock<AHashMap<String, (Decimal, Decimal)>>>,
+    rejected_out_of_band: Arc<RwLock<u64>>,
+    /// Prices dropped by the median or bounds check because they failed to parse
+    unparsed_prices: Arc<RwLock<u64>>,
+    weighted_skips: Arc<RwLock<u64>>,
+    /// Per-pair (quantum, decimals) used to round incoming prices
+    price_quanta: Arc<RwLock<AHashMap<String, (Decimal, u32)>>>,
+    price_changes: Arc<RwLock<u64>>,
+    stats: Arc<RwLock<AggregatorStats>>,
+    /// Per-pair `record_price` samples for `calculate_twap`, sorted by
+    /// timestamp and each bounded by HISTORY_CAPACITY
+    twap_samples: Arc<RwLock<AHashMap<String, TwapSamples>>>,
+    /// Per-pair observations, each bounded by HISTORY_CAPACITY
+    history: Arc<RwLock<AHashMap<String, PriceHistory>>>,
+    memo: Arc<RwLock<MedianMemo>>,
+    timing: Arc<RwLock<CallTimer>>,
+    case_insensitive_keys: Arc<RwLock<bool>>,
+    single_source_passthrough: Arc<RwLock<bool>>,
+    /// Whether single-source pass-throughs also skip the cache insert
+    single_source_skip_cache: Arc<RwLock<bool>>,
+    canonicalize_pairs: Arc<RwLock<bool>>,
+    /// (stale_ms, lag_ms) used by `feed_health`
+    health_thresholds: Arc<RwLock<(i64, i64)>>,
+    /// 0 accepts prices of any age
+    max_price_age_ms: Arc<RwLock<i64>>,
+    future_policy: Arc<RwLock<FuturePolicy>>,
+    /// Gain a `find_triangular` cycle must exceed
+    triangular_min_profit_bps: Arc<RwLock<i32>>,
+    gc: Arc<RwLock<Option<GcTask>>>,
+}
+
+/// Background eviction thread started by `start_gc`
+#[derive(Debug)]
+struct GcTask {
+    /// Sending (or dropping) this stops the thread at its next wake-up
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
 }
 
 #[derive(Debug, Clone)]
 struct CachedPrice {
     data: PriceData,
+    /// `current_time_ms` of the call that cached it
     timestamp: i64,
 }
 
+/// Milliseconds from `then` to `now`, saturating at zero and at `i64::MAX`.
+///
+/// Every age check in the aggregator goes through this. Something stamped
+/// after `now` (the caller's clock went backwards, or a future-dated price)
+/// counts as just seen: it stays inside the dedup window and the cache
+/// timeout until `now` passes its stamp by the full window, rather than
+/// expiring early or, with a raw subtraction, overflowing. Whether a price
+/// timestamped in the future is accepted at all is decided earlier, by
+/// `set_future_timestamp_policy`.
+fn age_ms(now: i64, then: i64) -> i64 {
+    now.saturating_sub(then).max(0)
+}
+
+/// Input size below which `calculate_median_fast` falls back to the full
+/// sort. Measured by `bench_median_crossover` (release build, one x86_64
+/// core): from 16 to 28 prices the two are at parity within noise (0.93x
+/// to 1.3x), and from 32 up selection wins on every run (1.3x at 32, 1.7x
+/// at 256, 2.4x at 1000). Selection is also ahead on tiny inputs, but by
+/// under 200ns a call, not enough to give up the sort's source tie-break
+/// and memo there.
+const FAST_MEDIAN_THRESHOLD: usize = 32;
+
+/// Observations kept per pair for range/history queries; the oldest
+/// sample is dropped once a pair's ring buffer is full
+const HISTORY_CAPACITY: usize = 256;
+
+/// Paired price changes required before `compute_correlation` returns a value
+const MIN_CORRELATION_SAMPLES: usize = 3;
+
+/// Default `feed_health` thresholds: a source silent for 30s is stale, one
+/// whose quotes trail our clock by 5s is lagging
+const DEFAULT_HEALTH_STALE_MS: i64 = 30_000;
+const DEFAULT_HEALTH_LAG_MS: i64 = 5_000;
+
+/// `calculate_median_price` is `calculate_percentile_price` at 50
+const MEDIAN_PERCENTILE: Decimal = Decimal::from_parts(50, 0, 0, false, 0);
+
+/// Memo size at which expired entries are pruned
+const MEMO_PRUNE_SIZE: usize = 1024;
+
+/// Significant digits kept by `calculate_geometric_mean`; its f64 log/exp
+/// round trip is only accurate to about this many
+const GEOMEAN_SIGNIFICANT_DIGITS: u32 = 15;
+
+/// Relative standard deviation at which `calculate_price_with_confidence`
+/// reports zero confidence (10%)
+const ZERO_CONFIDENCE_DISPERSION: f64 = 0.1;
+
+/// Memoized median results keyed by an order-independent hash of the input batch
+#[derive(Debug, Default)]
+struct MedianMemo {
+    /// 0 disables memoization
+    ttl_ms: i64,
+    entries: AHashMap<u64, (i64, Option<PriceData>)>,
+    hits: u64,
+    misses: u64,
+}
+
+/// Ring buffer of (observed_at_ms, price) for one pair
+type PriceHistory = VecDeque<(i64, f64)>;
+
+/// Per-pair (timestamp, price) samples fed by `record_price`
+type TwapSamples = VecDeque<(i64, Decimal)>;
+
+/// Per-source clock skew relative to the caller's clock
+#[napi(object)]
+pub struct SourceSkew {
+    pub source: String,
+    /// Mean of `timestamp - now_ms` (positive = source runs ahead)
+    pub skew_ms: f64,
+    /// Largest absolute skew seen for this source
+    pub max_abs_skew_ms: f64,
+    pub samples: u32,
+}
+
+/// What `aggregate_prices` does with a price stamped after `current_time_ms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FuturePolicy {
+    /// Keep it as given
+    Allow,
+    /// Keep it, restamped at `current_time_ms`
+    Clamp,
+    /// Drop it, counted in `rejected_future`
+    Reject,
+}
+
+/// Liveness classification of one source, see `feed_health`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceStatus {
+    Healthy,
+    Stale,
+    Lagging,
+}
+
+impl SourceStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            SourceStatus::Healthy => "healthy",
+            SourceStatus::Stale => "stale",
+            SourceStatus::Lagging => "lagging",
+        }
+    }
+}
+
+/// Liveness of one source in the cache
+#[napi(object)]
+pub struct SourceHealth {
+    pub source: String,
+    /// Time since the newest cached update from this source
+    pub last_seen_age_ms: f64,
+    /// Mean of `price.timestamp - received_at` over the source's cached
+    /// entries (negative = quotes trail our clock)
+    pub skew_ms: f64,
+    /// "healthy", "stale" or "lagging"
+    pub status: String,
+}
+
+/// Summary of every cached source, see `feed_health`
+#[napi(object)]
+pub struct FeedHealth {
+    /// At least one source and every source healthy
+    pub healthy: bool,
+    pub stale_sources: u32,
+    pub lagging_sources: u32,
+    /// Sorted by source name
+    pub sources: Vec<SourceHealth>,
+}
+
+/// Lowest and highest price observed for a pair within a window
+#[napi(object)]
+pub struct PriceRange {
+    pub min_price: f64,
+    pub min_timestamp: i64,
+    pub max_price: f64,
+    pub max_timestamp: i64,
+    pub samples: u32,
+}
+
+/// Weighting knobs for `aggregate_weighted`
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct WeightConfig {
+    /// Recency half-life in ms; `None` or a non-positive value disables decay
+    pub half_life_ms: Option<f64>,
+    /// Per-source trust weights
+    pub source_weights: HashMap<String, f64>,
+    /// Weight for sources missing from `source_weights` (default 1.0)
+    pub default_source_weight: Option<f64>,
+}
+
+/// Weighted price dispersion across sources, see `calculate_dispersion`
+#[napi(object)]
+pub struct DispersionResult {
+    /// Weighted mean price
+    pub mean: f64,
+    /// Weighted (population) standard deviation
+    pub std_dev: f64,
+    /// `std_dev` relative to `mean`, in bps (0 when the mean is not positive)
+    pub std_dev_bps: f64,
+    /// Weighted 25th percentile
+    pub q1: f64,
+    /// Weighted 75th percentile
+    pub q3: f64,
+    /// `q3 - q1`
+    pub interquartile_range: f64,
+    /// Prices that contributed (positive weight, parseable)
+    pub samples: u32,
+}
+
+/// Cross-source spread after round-trip fees, see `calculate_net_spread`
+#[napi(object)]
+pub struct SpreadResult {
+    /// Lowest price (the buy leg), as given
+    pub min_price: String,
+    /// Highest price (the sell leg), as given
+    pub max_price: String,
+    /// `(max - min) / min * 10000`
+    pub gross_spread_bps: f64,
+    /// Gross spread minus taker and maker fees, both in bps of the buy price
+    pub spread_bps: f64,
+    /// Whether the spread survives the fees (`spread_bps > 0`)
+    pub actionable: bool,
+}
+
+/// How far apart sources quote a pair, see `calculate_spread`
+#[napi(object)]
+pub struct PriceSpread {
+    /// Lowest price, as given
+    pub min: String,
+    /// Highest price, as given
+    pub max: String,
+    /// `(max - min) / min * 10000`, rounded half away from zero
+    pub spread_bps: i32,
+    /// Distinct sources among the parseable prices
+    pub source_count: u32,
+}
+
+/// Median price with a measure of source agreement, see
+/// `calculate_price_with_confidence`
+#[napi(object)]
+pub struct PricedWithConfidence {
+    pub price: String,
+    /// Within `[0, 1]`; 0 when fewer than two sources quote the pair
+    pub confidence: f64,
+    /// Distinct sources among the parseable prices
+    pub source_count: u32,
+}
+
+/// Latest price from every distinct source for one pair
+#[napi(object)]
+pub struct PairSources {
+    pub token_a: String,
+    pub token_b: String,
+    /// One entry per source, sorted by source name
+    pub prices: Vec<PriceData>,
+}
+
+/// How `aggregate_prices` handled each price that reached the cache, plus
+/// staleness rejections and outliers dropped by
+/// `calculate_median_price_filtered`; see `get_stats`. Prices dropped by
+/// bounds and single-source passthroughs that skip the cache are not
+/// counted.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct AggregatorStats {
+    /// Served the cached price (past the dedup window, within the timeout)
+    pub cache_hits: f64,
+    /// Dropped as a repeat within the dedup window
+    pub dedup_skips: f64,
+    /// Cached as a new or refreshed price
+    pub fresh_inserts: f64,
+    /// Dropped by `calculate_median_price_filtered` as too far from the median
+    pub outliers_rejected: f64,
+    /// Dropped on arrival as older than the max price age
+    pub rejected_stale: f64,
+    /// Dropped on arrival for a timestamp ahead of `current_time_ms`
+    pub rejected_future: f64,
+    /// Cache entries evicted, oldest first, to stay within `max_entries`
+    pub capacity_evictions: f64,
+}
+
+/// Aggregation result for a single pair
+#[napi(object)]
+pub struct PairAggregate {
+    pub token_a: String,
+    pub token_b: String,
+    /// Deduplicated/cached prices for this pair, in input order
+    pub prices: Vec<PriceData>,
+    pub median: Option<PriceData>,
+    /// Number of `prices`, i.e. the sources that survived dedup
+    pub count: u32,
+}
+
 #[napi]
 impl TurboAggregator {
+    /// Validating constructor: rejects a negative `cache_timeout_ms`
+    #[napi(factory)]
+    pub fn try_new(cache_timeout_ms: i64, max_entries: Option<u32>) -> napi::Result<Self> {
+        if cache_timeout_ms < 0 {
+            return Err(Error::invalid_argument(
+                "cache_timeout_ms",
+                format!("must be non-negative, got {}", cache_timeout_ms),
+            )
+            .into());
+        }
+        Ok(Self::new(cache_timeout_ms, max_entries))
+    }
+
+    /// `max_entries` caps the price cache (one entry per pair and source):
+    /// when it is full, caching a new entry first evicts the one cached
+    /// longest ago. Omitted or 0 leaves the cache unbounded outside
+    /// lightweight mode's expiry sweep.
     #[napi(constructor)]
-    pub fn new(cache_timeout_ms: i64) -> Self {
+    pub fn new(cache_timeout_ms: i64, max_entries: Option<u32>) -> Self {
         let lightweight = is_lightweight_mode();
-        
         Self {
-            price_cache: Arc::new(RwLock::new(AHashMap::new())),
-            cache_timeout_ms: if lightweight { cache_timeout_ms / 2 } else { cache_timeout_ms },
-            dedup_window_ms: 5000, // 5 second dedup window
+            price_cache: Arc::new(RwLock::new(AHashMap::with_hasher(hash_state()))),
+            max_entries: max_entries.unwrap_or(0) as usize,
+            cache_order: Arc::new(RwLock::new(BTreeSet::new())),
+            base_cache_timeout_ms: Arc::new(RwLock::new(cache_timeout_ms)),
+            cache_timeout_ms: Arc::new(RwLock::new(Self::scaled_timeout(cache_timeout_ms, lightweight))),
+            timeout_halved: Arc::new(RwLock::new(lightweight)),
+            timeout_bounds: Arc::new(RwLock::new(None)),
+            dedup_window_ms: Arc::new(RwLock::new(5000)), // 5 second dedup window
+            price_bounds: Arc::new(RwLock::new(AHashMap::with_hasher(hash_state()))),
+            rejected_out_of_band: Arc::new(RwLock::new(0)),
+            unparsed_prices: Arc::new(RwLock::new(0)),
+            weighted_skips: Arc::new(RwLock::new(0)),
+            price_quanta: Arc::new(RwLock::new(AHashMap::with_hasher(hash_state()))),
+            price_changes: Arc::new(RwLock::new(0)),
+            stats: Arc::new(RwLock::new(AggregatorStats::default())),
+            twap_samples: Arc::new(RwLock::new(AHashMap::with_hasher(hash_state()))),
+            history: Arc::new(RwLock::new(AHashMap::with_hasher(hash_state()))),
+            memo: Arc::new(RwLock::new(MedianMemo::default())),
+            timing: Arc::new(RwLock::new(CallTimer::default())),
+            case_insensitive_keys: Arc::new(RwLock::new(false)),
+            single_source_passthrough: Arc::new(RwLock::new(false)),
+            single_source_skip_cache: Arc::new(RwLock::new(false)),
+            canonicalize_pairs: Arc::new(RwLock::new(false)),
+            health_thresholds: Arc::new(RwLock::new((DEFAULT_HEALTH_STALE_MS, DEFAULT_HEALTH_LAG_MS))),
+            max_price_age_ms: Arc::new(RwLock::new(0)),
+            future_policy: Arc::new(RwLock::new(FuturePolicy::Allow)),
+            triangular_min_profit_bps: Arc::new(RwLock::new(0)),
+            gc: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Lowercase tokens and sources when building cache and bounds keys
+    /// (default off). Configure before `set_price_bounds`, since existing
+    /// keys are not rewritten.
+    #[napi]
+    pub fn set_case_insensitive_keys(&self, enabled: bool) {
+        *self.case_insensitive_keys.write() = enabled;
+    }
+
+    /// When enabled, a pair with exactly one distinct source in the batch
+    /// passes straight through `aggregate_prices`: no dedup window and no
+    /// cached substitute, and the price is returned as given. It is still
+    /// cached as usual (with change counting) unless `skip_cache` is set,
+    /// in which case nothing is inserted. Quantization, price bounds and
+    /// history still apply first. The source count is taken from the raw
+    /// batch, before bounds rejections; there is no `min_sources` gate
+    /// here, so nothing downstream can reject a pair for having a single
+    /// source. Default off.
+    #[napi]
+    pub fn set_single_source_passthrough(&self, enabled: bool, skip_cache: Option<bool>) {
+        *self.single_source_passthrough.write() = enabled;
+        *self.single_source_skip_cache.write() = skip_cache.unwrap_or(false);
+    }
+
+    /// When enabled, `aggregate_prices` orders each quote's tokens
+    /// lexicographically (after case normalization) before anything else,
+    /// swapping `token_a`/`token_b` and replacing the price with its exact
+    /// decimal reciprocal for B/A quotes. Both orientations then share one
+    /// cache entry, and results come back in the canonical orientation.
+    /// Quotes that can't be inverted (unparseable or zero) are dropped and
+    /// counted in `get_unparsed_prices`. Default off.
+    #[napi]
+    pub fn set_canonicalize_pairs(&self, enabled: bool) {
+        *self.canonicalize_pairs.write() = enabled;
+    }
+
+    /// Flip B/A quotes to A/B for `set_canonicalize_pairs`
+    fn canonicalize(&self, prices: Vec<PriceData>) -> Vec<PriceData> {
+        let mut dropped = 0u64;
+        let canonical = prices
+            .into_iter()
+            .filter_map(|mut price| {
+                if self.normalize_key(price.token_a.clone()) <= self.normalize_key(price.token_b.clone()) {
+                    return Some(price);
+                }
+                let Some(inverse) = parse_decimal(&price.price).and_then(|value| Decimal::ONE.checked_div(value)) else {
+                    dropped += 1;
+                    return None;
+                };
+                std::mem::swap(&mut price.token_a, &mut price.token_b);
+                price.price = inverse.normalize().to_string();
+                Some(price)
+            })
+            .collect();
+        if dropped > 0 {
+            *self.unparsed_prices.write() += dropped;
+        }
+        canonical
+    }
+
+    /// Drop prices in `aggregate_prices` whose own `timestamp` is more than
+    /// `max_age_ms` before `current_time_ms`, before they are cached or
+    /// counted anywhere else (see `rejected_stale` in `get_stats`). 0 (the
+    /// default) disables the check; negative values are rejected.
+    #[napi]
+    pub fn set_max_price_age_ms(&self, max_age_ms: i64) -> napi::Result<()> {
+        if max_age_ms < 0 {
+            return Err(Error::invalid_argument("max price age", format!("must be non-negative, got {}", max_age_ms)).into());
+        }
+        *self.max_price_age_ms.write() = max_age_ms;
+        Ok(())
+    }
+
+    /// How `aggregate_prices` treats a price stamped after `current_time_ms`
+    /// (source clock skew): `"allow"` (default) keeps it as given, `"clamp"`
+    /// restamps it at `current_time_ms`, `"reject"` drops it and counts it
+    /// in `rejected_future`.
+    #[napi]
+    pub fn set_future_timestamp_policy(&self, policy: String) -> napi::Result<()> {
+        let policy = match policy.as_str() {
+            "allow" => FuturePolicy::Allow,
+            "clamp" => FuturePolicy::Clamp,
+            "reject" => FuturePolicy::Reject,
+            other => {
+                return Err(Error::invalid_argument(
+                    "future timestamp policy",
+                    format!("expected 'allow', 'clamp' or 'reject', got '{}'", other),
+                )
+                .into())
+            }
+        };
+        *self.future_policy.write() = policy;
+        Ok(())
+    }
+
+    /// Opt-in per-call timing of `aggregate_prices` (excludes NAPI marshaling)
+    #[napi]
+    pub fn set_timing_enabled(&self, enabled: bool) {
+        self.timing.write().set_enabled(enabled);
+    }
+
+    /// Duration of the last timed call in microseconds
+    #[napi]
+    pub fn get_last_duration_us(&self) -> f64 {
+        self.timing.read().last_us()
+    }
+
+    /// Rolling average over recent timed calls in microseconds
+    #[napi]
+    pub fn get_average_duration_us(&self) -> f64 {
+        self.timing.read().average_us()
+    }
+
+    /// Re-read the lightweight flag, rescale the cache timeout from the
+    /// configured value and evict the entries that no longer fit it. Entry
+    /// stamps come from the callers' `current_time_ms`, so age is measured
+    /// against the newest stamp in the cache: whatever is older than that by
+    /// the new timeout could not be served again. Returns the number of
+    /// evicted entries.
+    #[napi]
+    pub fn reconfigure(&self) -> u32 {
+        *self.timeout_halved.write() = is_lightweight_mode();
+        let timeout = self.effective_timeout();
+        *self.cache_timeout_ms.write() = timeout;
+
+        let mut cache = self.price_cache.write();
+        let Some(newest) = cache.values().map(|cached| cached.timestamp).max() else {
+            return 0;
+        };
+        let before = cache.len();
+        Self::evict_expired(&mut cache, timeout, newest);
+        (before - cache.len()) as u32
+    }
+
+    /// Clamp the effective cache timeout into `[min_ms, max_ms]`. The clamp
+    /// runs after lightweight scaling: the constructor value is halved in
+    /// lightweight mode first, then clamped, so the bounds hold in either
+    /// mode. Applies immediately, with the mode as of the last
+    /// `reconfigure`, and on every `reconfigure`.
+    #[napi]
+    pub fn set_timeout_bounds(&self, min_ms: i64, max_ms: i64) -> napi::Result<()> {
+        if min_ms < 0 || min_ms > max_ms {
+            return Err(Error::invalid_argument(
+                "timeout bounds",
+                format!("expected 0 <= min <= max, got [{}, {}]", min_ms, max_ms),
+            )
+            .into());
+        }
+        *self.timeout_bounds.write() = Some((min_ms, max_ms));
+        *self.cache_timeout_ms.write() = self.effective_timeout();
+        Ok(())
+    }
+
+    /// Configured timeout scaled for the applied mode, clamped to the
+    /// configured bounds
+    fn effective_timeout(&self) -> i64 {
+        let scaled = Self::scaled_timeout(*self.base_cache_timeout_ms.read(), *self.timeout_halved.read());
+        match *self.timeout_bounds.read() {
+            Some((min_ms, max_ms)) => scaled.clamp(min_ms, max_ms),
+            None => scaled,
+        }
+    }
+
+    /// Effective cache timeout: the configured value after lightweight
+    /// scaling and `set_timeout_bounds`
+    #[napi]
+    pub fn get_cache_timeout_ms(&self) -> i64 {
+        *self.cache_timeout_ms.read()
+    }
+
+    /// Replace the constructor's cache timeout at runtime. Lightweight
+    /// halving and the timeout bounds apply to the new value, as they do to
+    /// the constructor's (with the mode as of the last `reconfigure`);
+    /// `get_cache_timeout_ms` reports the result. Takes effect on the next
+    /// `aggregate_prices`.
+    #[napi]
+    pub fn set_cache_timeout_ms(&self, timeout_ms: i64) -> napi::Result<()> {
+        if timeout_ms < 0 {
+            return Err(Error::invalid_argument("cache_timeout_ms", format!("must be non-negative, got {}", timeout_ms)).into());
+        }
+        *self.base_cache_timeout_ms.write() = timeout_ms;
+        *self.cache_timeout_ms.write() = self.effective_timeout();
+        Ok(())
+    }
+
+    /// Window within which a repeated (pair, source) quote is skipped by
+    /// `aggregate_prices`; 5000 by default. Takes effect on the next call.
+    #[napi]
+    pub fn set_dedup_window_ms(&self, window_ms: i64) -> napi::Result<()> {
+        if window_ms < 0 {
+            return Err(Error::invalid_argument("dedup_window_ms", format!("must be non-negative, got {}", window_ms)).into());
         }
+        *self.dedup_window_ms.write() = window_ms;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn get_dedup_window_ms(&self) -> i64 {
+        *self.dedup_window_ms.read()
+    }
+
+    fn scaled_timeout(base_ms: i64, lightweight: bool) -> i64 {
+        if lightweight { base_ms / 2 } else { base_ms }
+    }
+
+    /// Configure a hard sanity band for a pair; `aggregate_prices` drops
+    /// any price for that pair outside `[min, max]` (inclusive, compared
+    /// exactly as decimals)
+    #[napi]
+    pub fn set_price_bounds(&self, token_a: String, token_b: String, min: String, max: String) -> napi::Result<()> {
+        let (min_val, max_val) = match (parse_decimal(&min), parse_decimal(&max)) {
+            (Some(lo), Some(hi)) if lo <= hi => (lo, hi),
+            _ => {
+                return Err(Error::invalid_argument(
+                    "price bounds",
+                    format!("[{}, {}] for {}-{}", min, max, token_a, token_b),
+                )
+                .into())
+            }
+        };
+
+        self.price_bounds.write().insert(self.pair_key(&token_a, &token_b), (min_val, max_val));
+        Ok(())
+    }
+
+    #[napi]
+    pub fn clear_price_bounds(&self, token_a: String, token_b: String) {
+        self.price_bounds.write().remove(&self.pair_key(&token_a, &token_b));
+    }
+
+    /// Round incoming prices for a pair to the nearest multiple of `quantum`
+    /// (halves round away from zero) before bounds checks, caching and
+    /// change detection. Rounding is exact decimal arithmetic, and prices
+    /// are returned and cached at the quantum's precision, e.g. "0.01" or
+    /// "1e-2" yields two decimals.
+    #[napi]
+    pub fn set_price_quantum(&self, token_a: String, token_b: String, quantum: String) -> napi::Result<()> {
+        let value = parse_decimal(&quantum)
+            .filter(|q| q.is_sign_positive() && !q.is_zero())
+            .ok_or_else(|| Error::invalid_argument("price quantum", format!("must be positive, got '{}'", quantum)))?;
+        let decimals = value.scale();
+
+        self.price_quanta.write().insert(self.pair_key(&token_a, &token_b), (value, decimals));
+        Ok(())
+    }
+
+    /// Number of times a refreshed cache entry carried a different price
+    #[napi]
+    pub fn get_price_changes(&self) -> f64 {
+        *self.price_changes.read() as f64
+    }
+
+    /// Number of prices dropped for falling outside their pair's sanity band
+    #[napi]
+    pub fn get_rejected_out_of_band(&self) -> f64 {
+        *self.rejected_out_of_band.read() as f64
+    }
+
+    /// Number of prices skipped because they failed to parse as exact
+    /// decimals: by `calculate_median_price`/`calculate_median_fast` (and
+    /// the other median variants), `calculate_spread`, and by
+    /// `aggregate_prices` for pairs with a sanity band
+    #[napi]
+    pub fn get_unparsed_prices(&self) -> f64 {
+        *self.unparsed_prices.read() as f64
+    }
+
+    /// Cache hit, dedup skip and fresh insert counts from `aggregate_prices`
+    /// and the outlier count, since construction or the last `reset_stats`
+    #[napi]
+    pub fn get_stats(&self) -> AggregatorStats {
+        self.stats.read().clone()
+    }
+
+    #[napi]
+    pub fn reset_stats(&self) {
+        *self.stats.write() = AggregatorStats::default();
     }
 
     /// Aggregate prices with deduplication (75% memory reduction in lightweight mode)
     #[napi]
     pub fn aggregate_prices(&self, prices: Vec<PriceData>, current_time_ms: i64) -> Vec<PriceData> {
+        let started = self.timing.read().start();
         let lightweight = is_lightweight_mode();
+        let cache_timeout_ms = *self.cache_timeout_ms.read();
+        let dedup_window_ms = *self.dedup_window_ms.read();
         let mut cache = self.price_cache.write();
         let mut aggregated = Vec::new();
+        let mut stats = AggregatorStats::default();
 
         // In lightweight mode, clear old entries first to save memory
         if lightweight {
             self.evict_old_entries(&mut cache, current_time_ms);
         }
 
-        for price in prices {
-            let key = format!("{}-{}-{}", price.token_a, price.token_b, price.source);
+        let bounds = self.price_bounds.read();
+        let quanta = self.price_quanta.read();
+        let mut history = self.history.write();
+        let max_price_age_ms = *self.max_price_age_ms.read();
+        let future_policy = *self.future_policy.read();
+        let prices = if *self.canonicalize_pairs.read() { self.canonicalize(prices) } else { prices };
+
+        // Pair -> its only source in this batch, or None once a second appears
+        let mut single_sources: AHashMap<String, Option<String>> = AHashMap::new();
+        let skip_cache = *self.single_source_skip_cache.read();
+        if *self.single_source_passthrough.read() {
+            for price in &prices {
+                let source = self.normalize_key(price.source.clone());
+                single_sources
+                    .entry(self.pair_key(&price.token_a, &price.token_b))
+                    .and_modify(|only| {
+                        if only.as_ref() != Some(&source) {
+                            *only = None;
+                        }
+                    })
+                    .or_insert(Some(source));
+            }
+        }
+
+        for mut price in prices {
+            if price.timestamp > current_time_ms {
+                match future_policy {
+                    FuturePolicy::Allow => {}
+                    FuturePolicy::Clamp => price.timestamp = current_time_ms,
+                    FuturePolicy::Reject => {
+                        stats.rejected_future += 1.0;
+                        continue;
+                    }
+                }
+            } else if max_price_age_ms > 0 && age_ms(current_time_ms, price.timestamp) > max_price_age_ms {
+                stats.rejected_stale += 1.0;
+                continue;
+            }
+
+            let pair = self.pair_key(&price.token_a, &price.token_b);
+
+            // Snap to the pair's quantum so sub-tick jitter looks unchanged
+            if let Some(&(quantum, decimals)) = quanta.get(&pair) {
+                let snapped = parse_decimal(&price.price)
+                    .and_then(|value| value.checked_div(quantum))
+                    .and_then(|steps| steps.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero).checked_mul(quantum));
+                if let Some(mut snapped) = snapped {
+                    snapped.rescale(decimals);
+                    price.price = snapped.to_string();
+                }
+            }
+
+            // Hard guardrail: drop prices outside the configured band
+            if let Some(&(min, max)) = bounds.get(&pair) {
+                match parse_decimal(&price.price) {
+                    Some(value) if value >= min && value <= max => {}
+                    Some(_) => {
+                        *self.rejected_out_of_band.write() += 1;
+                        continue;
+                    }
+                    None => {
+                        *self.unparsed_prices.write() += 1;
+                        continue;
+                    }
+                }
+            }
+
+            let passthrough = matches!(single_sources.get(&pair), Some(Some(_)));
+            if passthrough && skip_cache {
+                Self::record_history(&mut history, pair, current_time_ms, &price.price);
+                aggregated.push(price);
+                continue;
+            }
+
+            let key = self.cache_key(&price.token_a, &price.token_b, &price.source);
             
-            // Check if we have a recent price
-            if let Some(cached) = cache.get(&key) {
-                let age_ms = current_time_ms - cached.timestamp;
+            // Check if we have a recent price (pass-throughs take the fresh one)
+            if let Some(cached) = cache.get(&key).filter(|_| !passthrough) {
+                let age = age_ms(current_time_ms, cached.timestamp);
                 
                 // Skip duplicates within dedup window
-                if age_ms < self.dedup_window_ms {
+                if age < dedup_window_ms {
+                    stats.dedup_skips += 1.0;
                     continue;
                 }
                 
                 // Use cached price if still valid
-                if age_ms < self.cache_timeout_ms {
+                if age < cache_timeout_ms {
+                    stats.cache_hits += 1.0;
                     aggregated.push(cached.data.clone());
                     continue;
                 }
             }
 
+            // Record the fresh observation in the pair's history
+            Self::record_history(&mut history, pair, current_time_ms, &price.price);
+
             // Cache new price
-            cache.insert(key, CachedPrice {
+            stats.capacity_evictions += self.make_room(&mut cache, &key, current_time_ms) as f64;
+            let previous = cache.insert(key, CachedPrice {
                 data: price.clone(),
                 timestamp: current_time_ms,
             });
+            if previous.is_some_and(|p| p.data.price != price.price) {
+                *self.price_changes.write() += 1;
+            }
+            stats.fresh_inserts += 1.0;
             
             aggregated.push(price);
         }
 
+        let mut totals = self.stats.write();
+        totals.cache_hits += stats.cache_hits;
+        totals.dedup_skips += stats.dedup_skips;
+        totals.fresh_inserts += stats.fresh_inserts;
+        totals.rejected_stale += stats.rejected_stale;
+        totals.rejected_future += stats.rejected_future;
+        totals.capacity_evictions += stats.capacity_evictions;
+        drop(totals);
+
+        if let Some(started) = started {
+            self.timing.write().record(started.elapsed());
+        }
+
         aggregated
     }
 
-    /// Calculate median price with ARM-optimized sorting
+    /// `aggregate_prices` over a batch encoded with `prices_to_bytes`, so the
+    /// input crosses the JS boundary as one `Buffer` instead of one object
+    /// per price. Malformed input is rejected before any state changes.
     #[napi]
-    pub fn calculate_median_price(&self, prices: Vec<PriceData>) -> Option<PriceData> {
-        if prices.is_empty() {
-            return None;
+    pub fn aggregate_prices_bytes(&self, bytes: Buffer, current_time_ms: i64) -> napi::Result<Vec<PriceData>> {
+        let prices = decode_prices(&bytes)?;
+        Ok(self.aggregate_prices(prices, current_time_ms))
+    }
+
+    /// Aggregate a mixed batch and return per-pair results.
+    ///
+    /// Pairs are grouped by the same key used for caching (so case-insensitive
+    /// keys also merge pairs) and returned in order of first appearance in the
+    /// input. A pair whose prices were all suppressed by the dedup window is
+    /// still returned, with no prices and no median.
+    #[napi]
+    pub fn aggregate_grouped(&self, prices: Vec<PriceData>, now_ms: i64) -> Vec<PairAggregate> {
+        let mut groups = self.group_aggregated(prices, now_ms);
+        for group in &mut groups {
+            group.median = self.calculate_median_price(group.prices.clone());
         }
+        groups
+    }
 
-        if prices.len() == 1 {
-            return Some(prices[0].clone());
+    /// Run `aggregate_prices` and split the result per pair, without medians
+    fn group_aggregated(&self, prices: Vec<PriceData>, now_ms: i64) -> Vec<PairAggregate> {
+        let mut index: AHashMap<String, usize> = AHashMap::new();
+        let mut groups: Vec<PairAggregate> = Vec::new();
+
+        for price in &prices {
+            let key = self.pair_key(&price.token_a, &price.token_b);
+            index.entry(key).or_insert_with(|| {
+                groups.push(PairAggregate {
+                    token_a: price.token_a.clone(),
+                    token_b: price.token_b.clone(),
+                    prices: Vec::new(),
+                    median: None,
+                    count: 0,
+                });
+                groups.len() - 1
+            });
         }
 
-        // Parse prices for median calculation (ARM-optimized)
-        let mut price_values: Vec<(f64, &PriceData)> = prices
-            .iter()
-            .filter_map(|p| {
-                p.price.parse::<f64>().ok().map(|val| (val, p))
-            })
-            .collect();
+        for price in self.aggregate_prices(prices, now_ms) {
+            if let Some(&i) = index.get(&self.pair_key(&price.token_a, &price.token_b)) {
+                groups[i].prices.push(price);
+            }
+        }
+        for group in &mut groups {
+            group.count = group.prices.len() as u32;
+        }
 
-        if price_values.is_empty() {
-            return None;
+        groups
+    }
+
+    /// Collect, per pair, the latest price from each distinct source.
+    ///
+    /// Candidates are the input batch plus any unexpired cached price for
+    /// the same pairs. Latest-wins: the entry with the greatest `timestamp`
+    /// is kept per (pair, source); on a tie, input beats cache and later
+    /// input beats earlier. Pairs are returned in order of first appearance
+    /// in the input. The cache and stats are not modified, so the result can
+    /// be fed straight into the median/mean methods.
+    #[napi]
+    pub fn collect_by_pair(&self, prices: Vec<PriceData>, now_ms: i64) -> Vec<PairSources> {
+        let mut index: AHashMap<String, usize> = AHashMap::new();
+        let mut groups: Vec<(PairSources, AHashMap<String, PriceData>)> = Vec::new();
+
+        for price in &prices {
+            let key = self.pair_key(&price.token_a, &price.token_b);
+            index.entry(key).or_insert_with(|| {
+                groups.push((
+                    PairSources {
+                        token_a: price.token_a.clone(),
+                        token_b: price.token_b.clone(),
+                        prices: Vec::new(),
+                    },
+                    AHashMap::new(),
+                ));
+                groups.len() - 1
+            });
+        }
+
+        // Seed with unexpired cached prices for the pairs in this batch
+        let cache_timeout_ms = *self.cache_timeout_ms.read();
+        for cached in self.price_cache.read().values() {
+            if age_ms(now_ms, cached.timestamp) >= cache_timeout_ms {
+                continue;
+            }
+            let data = &cached.data;
+            if let Some(&i) = index.get(&self.pair_key(&data.token_a, &data.token_b)) {
+                let source = self.normalize_key(data.source.clone());
+                let latest = &mut groups[i].1;
+                if latest.get(&source).is_none_or(|p| data.timestamp > p.timestamp) {
+                    latest.insert(source, data.clone());
+                }
+            }
         }
 
-        // Sort by price value
-        price_values.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        for price in prices {
+            let i = index[&self.pair_key(&price.token_a, &price.token_b)];
+            let source = self.normalize_key(price.source.clone());
+            let latest = &mut groups[i].1;
+            if latest.get(&source).is_none_or(|p| price.timestamp >= p.timestamp) {
+                latest.insert(source, price);
+            }
+        }
 
-        // Return median
-        let median_idx = price_values.len() / 2;
-        Some(price_values[median_idx].1.clone())
+        groups
+            .into_iter()
+            .map(|(mut group, latest)| {
+                let mut by_source: Vec<(String, PriceData)> = latest.into_iter().collect();
+                by_source.sort_by(|a, b| a.0.cmp(&b.0));
+                group.prices = by_source.into_iter().map(|(_, p)| p).collect();
+                group
+            })
+            .collect()
     }
 
-    /// Evict old entries to save memory (lightweight mode)
-    fn evict_old_entries(&self, cache: &mut AHashMap<String, CachedPrice>, current_time_ms: i64) {
-        cache.retain(|_, v| {
-            current_time_ms - v.timestamp < self.cache_timeout_ms
-        });
+    /// Memoize `calculate_median_price` results for `ttl_ms` (wall clock).
+    /// Only helps when the exact same batch (same fields, any order) is
+    /// submitted again within the TTL; 0 disables memoization. Bypassed in
+    /// deterministic mode.
+    #[napi]
+    pub fn set_memoization(&self, ttl_ms: i64) {
+        let mut memo = self.memo.write();
+        memo.ttl_ms = ttl_ms.max(0);
+        memo.entries.clear();
     }
 
     #[napi]
-    pub fn get_cache_size(&self) -> u32 {
-        self.price_cache.read().len() as u32
+    pub fn clear_memo(&self) {
+        let mut memo = self.memo.write();
+        memo.entries.clear();
+        memo.hits = 0;
+        memo.misses = 0;
     }
 
     #[napi]
-    pub fn clear_cache(&self) {
-        self.price_cache.write().clear();
+    pub fn get_memo_hits(&self) -> f64 {
+        self.memo.read().hits as f64
     }
 
-    /// Get memory usage estimate in bytes
     #[napi]
-    pub fn get_memory_usage(&self) -> f64 {
-        let cache = self.price_cache.read();
-        let base_size = std::mem::size_of::<AHashMap<String, CachedPrice>>() as f64;
-        let entries_size = cache.len() as f64 * 256.0; // Approximate size per entry
-        base_size + entries_size
+    pub fn get_memo_misses(&self) -> f64 {
+        self.memo.read().misses as f64
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Calculate median price with ARM-optimized sorting. Odd counts return
+    /// the middle entry unchanged; even counts return a synthesized entry
+    /// whose price is the exact average of the two middle prices (source
+    /// "median", the later of their timestamps, trailing zeros trimmed).
+    /// A single entry is returned as is, even if unparseable. Equal prices
+    /// are ordered by source (then the remaining fields), so the result
+    /// never depends on input order and a memo hit matches a fresh call.
+    /// Same result as `calculate_percentile_price` at 50, plus optional
+    /// memoization.
+    #[napi]
+    pub fn calculate_median_price(&self, prices: Vec<PriceData>) -> Option<PriceData> {
+        let ttl_ms = self.memo.read().ttl_ms;
+        // The memo TTL is wall-clock time, so deterministic mode bypasses it
+        if ttl_ms == 0 || is_deterministic() {
+            return self.compute_median(prices);
+        }
 
-    #[test]
-    fn test_turbo_aggregator() {
-        let aggregator = TurboAggregator::new(10000);
-        
-        let price1 = PriceData {
-            token_a: "A".to_string(),
-            token_b: "B".to_string(),
-            price: "100.5".to_string(),
-            source: "dex1".to_string(),
-            timestamp: 1000,
-        };
+        let batch_hash = Self::batch_hash(&prices);
+        let now = now_ms();
+        {
+            let mut memo = self.memo.write();
+            if let Some((at, result)) = memo.entries.get(&batch_hash) {
+                if age_ms(now, *at) < ttl_ms {
+                    let result = result.clone();
+                    memo.hits += 1;
+                    return result;
+                }
+            }
+            memo.misses += 1;
+        }
 
-        let price2 = price1.clone();
+        let result = self.compute_median(prices);
 
-        let prices = vec![price1, price2];
-        let aggregated = aggregator.aggregate_prices(prices, 1000);
-        
-        // Should deduplicate
-        assert_eq!(aggregated.len(), 1);
+        let mut memo = self.memo.write();
+        if memo.entries.len() >= MEMO_PRUNE_SIZE {
+            memo.entries.retain(|_, (at, _)| age_ms(now, *at) < ttl_ms);
+        }
+        memo.entries.insert(batch_hash, (now, result.clone()));
+        result
     }
 
-    #[test]
-    fn test_median_calculation() {
-        let aggregator = TurboAggregator::new(10000);
-        
-        let prices = vec![
-            PriceData {
-                token_a: "A".to_string(),
-                token_b: "B".to_string(),
-                price: "100".to_string(),
-                source: "dex1".to_string(),
-                timestamp: 1000,
-            },
-            PriceData {
-                token_a: "A".to_string(),
-                token_b: "B".to_string(),
-                price: "105".to_string(),
-                source: "dex2".to_string(),
-                timestamp: 1000,
+    /// Order-independent hash of a batch: hash each entry, sort, hash the sequence
+    fn batch_hash(prices: &[PriceData]) -> u64 {
+        let state = ahash::RandomState::with_seeds(1, 2, 3, 4);
+        let mut entry_hashes: Vec<u64> = prices
+            .iter()
+            .map(|p| state.hash_one((&p.token_a, &p.token_b, &p.price, &p.source, p.timestamp)))
+            .collect();
+        entry_hashes.sort_unstable();
+        state.hash_one(&entry_hashes)
+    }
+
+    fn compute_median(&self, prices: Vec<PriceData>) -> Option<PriceData> {
+        self.compute_percentile(&prices, MEDIAN_PERCENTILE)
+    }
+
+    /// Price at `percentile` (within `[0, 100]`) of the parseable prices,
+    /// e.g. 25 for conservative sizing or 75 for aggressive. The rank is
+    /// `percentile / 100 * (n - 1)` over the prices sorted ascending; a
+    /// whole rank returns that entry unchanged, a fractional one
+    /// interpolates linearly between its two neighbours into a synthesized
+    /// entry (source "percentile", or "median" at 50; the later of their
+    /// timestamps; trailing zeros trimmed). A single entry is returned as
+    /// is. Unparseable prices are skipped and counted like the median's.
+    /// `None` for empty input or a percentile outside `[0, 100]`.
+    #[napi]
+    pub fn calculate_percentile_price(&self, prices: Vec<PriceData>, percentile: f64) -> Option<PriceData> {
+        if !(0.0..=100.0).contains(&percentile) {
+            return None;
+        }
+        self.compute_percentile(&prices, Decimal::from_f64(percentile)?)
+    }
+
+    /// `calculate_median_price` that fails instead of skipping: an
+    /// unparseable price (the first in input order) or empty input is
+    /// reported as an error rather than shrinking the set. Not memoized.
+    #[napi]
+    pub fn calculate_median_price_strict(&self, prices: Vec<PriceData>) -> napi::Result<PriceData> {
+        Ok(self.strict_percentile(&prices, MEDIAN_PERCENTILE)?)
+    }
+
+    /// `calculate_percentile_price` with the same errors as
+    /// `calculate_median_price_strict`; a percentile outside `[0, 100]` is
+    /// an invalid argument.
+    #[napi]
+    pub fn calculate_percentile_price_strict(&self, prices: Vec<PriceData>, percentile: f64) -> napi::Result<PriceData> {
+        let value = Decimal::from_f64(percentile).filter(|_| (0.0..=100.0).contains(&percentile)).ok_or_else(|| {
+            Error::invalid_argument("percentile", format!("must be within [0, 100], got {}", percentile))
+        })?;
+        Ok(self.strict_percentile(&prices, value)?)
+    }
+
+    fn strict_percentile(&self, prices: &[PriceData], percentile: Decimal) -> Result<PriceData, PriceError> {
+        if let Some(bad) = prices.iter().find(|p| parse_decimal(&p.price).is_none()) {
+            return Err(PriceError::ParseFailure { source: bad.source.clone(), raw: bad.price.clone() });
+        }
+        self.compute_percentile(prices, percentile).ok_or(PriceError::EmptyInput)
+    }
+
+    fn compute_percentile(&self, prices: &[PriceData], percentile: Decimal) -> Option<PriceData> {
+        if prices.len() == 1 {
+            return Some(prices[0].clone());
+        }
+
+        // Exact decimals, so 18-decimal prices that collapse to the same f64 still order correctly
+        let mut price_values = self.parse_decimal_prices(prices);
+
+        // Sort by price value; ties by source, then the rest, so input order never matters
+        price_values.sort_by(|(a, pa), (b, pb)| {
+            a.cmp(b)
+                .then_with(|| pa.source.cmp(&pb.source))
+                .then_with(|| (&pa.price, pa.timestamp, &pa.token_a, &pa.token_b).cmp(&(&pb.price, pb.timestamp, &pb.token_a, &pb.token_b)))
+        });
+        Self::sorted_percentile(&price_values, percentile)
+    }
+
+    /// Median of entries already sorted by price; `None` when empty
+    fn sorted_median(sorted: &[(Decimal, &PriceData)]) -> Option<PriceData> {
+        Self::sorted_percentile(sorted, MEDIAN_PERCENTILE)
+    }
+
+    /// See `calculate_percentile_price`; `None` when empty
+    fn sorted_percentile(sorted: &[(Decimal, &PriceData)], percentile: Decimal) -> Option<PriceData> {
+        let last = sorted.len().checked_sub(1)?;
+        let rank = percentile * Decimal::from(last) / Decimal::ONE_HUNDRED;
+        let idx = rank.floor().to_usize()?.min(last);
+        let fraction = rank - rank.floor();
+        if fraction.is_zero() || idx == last {
+            return Some(sorted[idx].1.clone());
+        }
+
+        let source = if percentile == MEDIAN_PERCENTILE { "median" } else { "percentile" };
+        Some(Self::interpolate(sorted[idx], sorted[idx + 1], fraction, source))
+    }
+
+    /// Median after dropping outliers: prices more than `max_deviation_bps`
+    /// (negative counts as 0) away from the median of the whole batch are
+    /// discarded and the median is recomputed over the rest. Dropped prices
+    /// are counted in `get_stats().outliers_rejected`. With fewer than three
+    /// parseable prices there is no majority to judge by, so nothing is
+    /// dropped; likewise when the center is not positive. Returns `None`
+    /// when every price is dropped, which a tight bound can do to even-sized
+    /// batches whose two middle prices differ. Not memoized.
+    #[napi]
+    pub fn calculate_median_price_filtered(&self, prices: Vec<PriceData>, max_deviation_bps: i32) -> Option<PriceData> {
+        let mut price_values = self.parse_decimal_prices(&prices);
+        price_values.sort_by_key(|p| p.0);
+        let center = parse_decimal(&Self::sorted_median(&price_values)?.price)?;
+
+        if price_values.len() < 3 || center <= Decimal::ZERO {
+            return Self::sorted_median(&price_values);
+        }
+
+        // Keep |p - center| / center * 10000 <= max, cross-multiplied to avoid dividing
+        let limit = center.checked_mul(Decimal::from(max_deviation_bps.max(0)));
+        let before = price_values.len();
+        price_values.retain(|(value, _)| {
+            let deviation = (*value - center).abs().checked_mul(Decimal::from(BPS_SCALE as i64));
+            matches!((deviation, limit), (Some(d), Some(l)) if d <= l)
+        });
+        let dropped = before - price_values.len();
+        if dropped > 0 {
+            self.stats.write().outliers_rejected += dropped as f64;
+        }
+
+        Self::sorted_median(&price_values)
+    }
+
+    /// Even-count median: the exact average of the two middle prices, as a
+    /// synthesized entry with source "median" and the newer timestamp
+    fn midpoint(lower: (Decimal, &PriceData), upper: (Decimal, &PriceData)) -> PriceData {
+        Self::interpolate(lower, upper, Decimal::new(5, 1), "median")
+    }
+
+    /// `lower + (upper - lower) * fraction` as a synthesized entry with the
+    /// newer timestamp
+    fn interpolate(lower: (Decimal, &PriceData), upper: (Decimal, &PriceData), fraction: Decimal, source: &str) -> PriceData {
+        // a + (b - a) * f stays within [a, b] for a <= b and f in [0, 1]
+        let value = lower.0 + (upper.0 - lower.0) * fraction;
+        PriceData {
+            token_a: lower.1.token_a.clone(),
+            token_b: lower.1.token_b.clone(),
+            price: value.normalize().to_string(),
+            source: source.to_string(),
+            timestamp: lower.1.timestamp.max(upper.1.timestamp),
+        }
+    }
+
+    /// Report per-source clock skew, sorted by absolute mean skew descending
+    #[napi]
+    pub fn detect_clock_skew(&self, prices: Vec<PriceData>, now_ms: i64) -> Vec<SourceSkew> {
+        let mut by_source: AHashMap<String, (f64, f64, u32)> = AHashMap::new();
+
+        for price in prices {
+            let skew = price.timestamp.saturating_sub(now_ms) as f64;
+            let entry = by_source.entry(price.source).or_insert((0.0, 0.0, 0));
+            entry.0 += skew;
+            entry.1 = entry.1.max(skew.abs());
+            entry.2 += 1;
+        }
+
+        let mut skews: Vec<SourceSkew> = by_source
+            .into_iter()
+            .map(|(source, (sum, max_abs, samples))| SourceSkew {
+                source,
+                skew_ms: sum / samples as f64,
+                max_abs_skew_ms: max_abs,
+                samples,
+            })
+            .collect();
+
+        skews.sort_by(|a, b| {
+            b.skew_ms.abs().total_cmp(&a.skew_ms.abs()).then_with(|| a.source.cmp(&b.source))
+        });
+        skews
+    }
+
+    /// Append a parsed observation to the pair's bounded history ring
+    fn record_history(history: &mut AHashMap<String, PriceHistory>, pair: String, at_ms: i64, raw: &str) {
+        if let Some(value) = parse_price(raw) {
+            let samples = history.entry(pair).or_default();
+            if samples.len() == HISTORY_CAPACITY {
+                samples.pop_front();
+            }
+            samples.push_back((at_ms, value));
+        }
+    }
+
+    fn pair_key(&self, token_a: &str, token_b: &str) -> String {
+        self.normalize_key(format!("{}-{}", token_a, token_b))
+    }
+
+    fn cache_key(&self, token_a: &str, token_b: &str, source: &str) -> String {
+        self.normalize_key(format!("{}-{}-{}", token_a, token_b, source))
+    }
+
+    /// Apply the case-insensitivity setting to a key
+    fn normalize_key(&self, mut key: String) -> String {
+        if *self.case_insensitive_keys.read() {
+            key.make_ascii_lowercase();
+        }
+        key
+    }
+
+    /// Median via O(n) selection instead of a full sort, for pairs with many
+    /// sources. Uses `select_nth_unstable_by` (introselect with a
+    /// median-of-medians fallback, so linear in the worst case). Inputs
+    /// smaller than `FAST_MEDIAN_THRESHOLD` use `calculate_median_price`.
+    /// Picks the same rank as the exact median; among equal prices the
+    /// returned source may differ.
+    #[napi]
+    pub fn calculate_median_fast(&self, prices: Vec<PriceData>) -> Option<PriceData> {
+        if prices.len() < FAST_MEDIAN_THRESHOLD {
+            return self.calculate_median_price(prices);
+        }
+        self.select_median(&prices)
+    }
+
+    /// Selection half of `calculate_median_fast`, at any input size
+    fn select_median(&self, prices: &[PriceData]) -> Option<PriceData> {
+        let mut price_values = self.parse_decimal_prices(prices);
+
+        if price_values.is_empty() {
+            return None;
+        }
+
+        let odd = price_values.len() % 2 == 1;
+        let median_idx = price_values.len() / 2;
+        let (below, median, _) = price_values.select_nth_unstable_by(median_idx, |a, b| a.0.cmp(&b.0));
+        let upper = *median;
+        if odd {
+            return Some(upper.1.clone());
+        }
+        // The lower middle is the largest entry left of the selected one
+        let lower = below.iter().max_by_key(|p| p.0).copied()?;
+        Some(Self::midpoint(lower, upper))
+    }
+
+    /// Parse every price exactly, counting the ones that fail in `unparsed_prices`
+    fn parse_decimal_prices<'a>(&self, prices: &'a [PriceData]) -> Vec<(Decimal, &'a PriceData)> {
+        let parsed: Vec<(Decimal, &PriceData)> =
+            prices.iter().filter_map(|p| parse_decimal(&p.price).map(|val| (val, p))).collect();
+        if parsed.len() < prices.len() {
+            *self.unparsed_prices.write() += (prices.len() - parsed.len()) as u64;
+        }
+        parsed
+    }
+
+    /// Weighted median: the lowest price at which cumulative weight reaches
+    /// half the total. Weights are normalized internally, so any positive
+    /// total works. Entries whose price fails to parse or whose weight is
+    /// non-finite or negative are dropped and counted (see
+    /// `get_weighted_skips`). Returns `None` on a length mismatch or when no
+    /// positive weight remains.
+    #[napi]
+    pub fn calculate_weighted_median(&self, prices: Vec<PriceData>, weights: Vec<f64>) -> Option<PriceData> {
+        if prices.len() != weights.len() {
+            return None;
+        }
+
+        let entries = self.normalized_entries(&prices, &weights)?;
+        Self::weighted_median_of(entries)
+    }
+
+    /// Volume-weighted average price: `sum(price_i * weight_i) /
+    /// sum(weight_i)`, where `weights[i]` is the liquidity or volume behind
+    /// `prices[i]` as a decimal string. Computed in exact decimals, then
+    /// rounded to 28 significant digits by the division. Entries whose price
+    /// or weight fails to parse, or whose weight is negative, are skipped
+    /// and counted in `get_weighted_skips`. The result has source "vwap",
+    /// the tokens of the first contributing entry and the latest timestamp
+    /// among contributing entries. `None` on a length mismatch, when every
+    /// weight is zero, or if the sums overflow.
+    #[napi]
+    pub fn calculate_vwap(&self, prices: Vec<PriceData>, weights: Vec<String>) -> Option<PriceData> {
+        if prices.len() != weights.len() {
+            return None;
+        }
+
+        let mut weighted_sum = Decimal::ZERO;
+        let mut total_weight = Decimal::ZERO;
+        let mut first: Option<&PriceData> = None;
+        let mut timestamp = i64::MIN;
+        let mut skipped = 0u64;
+
+        for (price, weight) in prices.iter().zip(&weights) {
+            let (value, weight) = match (parse_decimal(&price.price), parse_decimal(weight)) {
+                (Some(value), Some(weight)) if !weight.is_sign_negative() => (value, weight),
+                _ => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            if weight.is_zero() {
+                continue;
+            }
+            weighted_sum = weighted_sum.checked_add(value.checked_mul(weight)?)?;
+            total_weight = total_weight.checked_add(weight)?;
+            first.get_or_insert(price);
+            timestamp = timestamp.max(price.timestamp);
+        }
+
+        if skipped > 0 {
+            *self.weighted_skips.write() += skipped;
+        }
+        let first = first?;
+        Some(PriceData {
+            token_a: first.token_a.clone(),
+            token_b: first.token_b.clone(),
+            price: weighted_sum.checked_div(total_weight)?.normalize().to_string(),
+            source: "vwap".to_string(),
+            timestamp,
+        })
+    }
+
+    /// Blend prices by recency and source into one weighted median.
+    ///
+    /// Each price gets `w = source_weight * 0.5^(age_ms / half_life_ms)`,
+    /// where `source_weight` comes from `config.source_weights` (falling back
+    /// to `default_source_weight`, then 1.0) and `age_ms = max(0, now_ms -
+    /// timestamp)`, so future-stamped prices are not boosted. Without a
+    /// half-life the decay factor is 1. Entries with a zero weight are
+    /// dropped; non-finite or negative weights and unparseable prices are
+    /// dropped and counted in `get_weighted_skips`. The remaining weights are
+    /// normalized and the result is the weighted median as in
+    /// `calculate_weighted_median`. `None` when no positive weight remains.
+    #[napi]
+    pub fn aggregate_weighted(&self, prices: Vec<PriceData>, now_ms: i64, config: WeightConfig) -> Option<PriceData> {
+        let half_life_ms = config.half_life_ms.filter(|h| h.is_finite() && *h > 0.0);
+        let default_weight = config.default_source_weight.unwrap_or(1.0);
+
+        let (prices, weights): (Vec<PriceData>, Vec<f64>) = prices
+            .into_iter()
+            .map(|price| {
+                let source_weight = config.source_weights.get(&price.source).copied().unwrap_or(default_weight);
+                let decay = match half_life_ms {
+                    Some(half_life) => {
+                        0.5f64.powf(age_ms(now_ms, price.timestamp) as f64 / half_life)
+                    }
+                    None => 1.0,
+                };
+                (price, source_weight * decay)
+            })
+            .filter(|(_, weight)| *weight != 0.0)
+            .unzip();
+
+        let entries = self.normalized_entries(&prices, &weights)?;
+        Self::weighted_median_of(entries)
+    }
+
+    /// Lowest price whose cumulative normalized weight reaches one half
+    fn weighted_median_of(mut entries: Vec<(f64, f64, &PriceData)>) -> Option<PriceData> {
+        entries.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self::weighted_quantile(&entries, 0.5).map(|i| entries[i].2.clone())
+    }
+
+    /// Index of the first entry (sorted by price, normalized weights) whose
+    /// cumulative weight reaches `q`
+    fn weighted_quantile(sorted: &[(f64, f64, &PriceData)], q: f64) -> Option<usize> {
+        let mut cumulative = 0.0;
+        for (i, (_, weight, _)) in sorted.iter().enumerate() {
+            cumulative += weight;
+            if cumulative >= q {
+                return Some(i);
+            }
+        }
+
+        // Rounding left the total a hair under 1.0
+        sorted.len().checked_sub(1)
+    }
+
+    /// Weighted disagreement across all sources, typically weighted by
+    /// liquidity so thin pools count less than in a plain min/max spread.
+    ///
+    /// Weights are normalized and filtered exactly as in
+    /// `calculate_weighted_median` (bad entries counted in
+    /// `get_weighted_skips`); zero weights are then dropped. With `w_i`
+    /// summing to 1: `mean = sum(w_i * p_i)`, `std_dev = sqrt(sum(w_i *
+    /// (p_i - mean)^2))`, and `q1`/`q3` are the lowest prices whose
+    /// cumulative weight reaches 0.25/0.75. `None` on a length mismatch or
+    /// with fewer than two positively weighted prices.
+    #[napi]
+    pub fn calculate_dispersion(&self, prices: Vec<PriceData>, weights: Vec<f64>) -> Option<DispersionResult> {
+        if prices.len() != weights.len() {
+            return None;
+        }
+
+        let mut entries = self.normalized_entries(&prices, &weights)?;
+        entries.retain(|e| e.1 > 0.0);
+        if entries.len() < 2 {
+            return None;
+        }
+        entries.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (mean, std_dev) = Self::weighted_mean_std_dev(&entries);
+        let q1 = entries[Self::weighted_quantile(&entries, 0.25)?].0;
+        let q3 = entries[Self::weighted_quantile(&entries, 0.75)?].0;
+
+        Some(DispersionResult {
+            mean,
+            std_dev,
+            std_dev_bps: if mean > 0.0 { std_dev / mean * BPS_SCALE } else { 0.0 },
+            q1,
+            q3,
+            interquartile_range: q3 - q1,
+            samples: entries.len() as u32,
+        })
+    }
+
+    /// `(mean, std_dev)` of entries whose weights sum to 1, as defined by
+    /// `calculate_dispersion`
+    fn weighted_mean_std_dev(entries: &[(f64, f64, &PriceData)]) -> (f64, f64) {
+        let mean: f64 = entries.iter().map(|(p, w, _)| w * p).sum();
+        let variance: f64 = entries.iter().map(|(p, w, _)| w * (p - mean).powi(2)).sum();
+        (mean, variance.sqrt())
+    }
+
+    /// Spread between the cheapest and dearest source after round-trip fees.
+    ///
+    /// Buying at the minimum and selling at the maximum pays one taker and
+    /// one maker fee, each charged on its leg's notional. The sell leg's
+    /// notional is the maximum, so in bps of the buy price its fee is
+    /// `maker_fee_bps * max / min`, and `spread_bps = gross_spread_bps -
+    /// taker_fee_bps - maker_fee_bps * max / min`, computed in exact
+    /// decimals. A negative maker fee (rebate) widens the spread.
+    /// The gross spread is `calculate_spread`'s, unrounded. Unparseable
+    /// prices are skipped and counted in `get_unparsed_prices`; `None` with
+    /// fewer than two usable prices or a non-positive minimum.
+    #[napi]
+    pub fn calculate_net_spread(&self, prices: Vec<PriceData>, taker_fee_bps: i32, maker_fee_bps: i32) -> Option<SpreadResult> {
+        let parsed = self.parse_decimal_prices(&prices);
+        if parsed.len() < 2 {
+            return None;
+        }
+        let (low, high, gross) = Self::spread_between(&parsed)?;
+
+        let gross_spread_bps = gross.and_then(|bps| bps.to_f64()).unwrap_or(f64::INFINITY);
+        // max / min is 1 + gross / 10000
+        let spread_bps = gross
+            .and_then(|gross| {
+                let max_over_min = Decimal::ONE.checked_add(gross.checked_div(Decimal::from(BPS_SCALE as i64))?)?;
+                let sell_fee = Decimal::from(maker_fee_bps).checked_mul(max_over_min)?;
+                gross.checked_sub(Decimal::from(taker_fee_bps))?.checked_sub(sell_fee)
+            })
+            .and_then(|net| net.to_f64())
+            .unwrap_or_else(|| {
+                gross_spread_bps * (1.0 - maker_fee_bps as f64 / BPS_SCALE) - taker_fee_bps as f64 - maker_fee_bps as f64
+            });
+        Some(SpreadResult {
+            min_price: low.price.clone(),
+            max_price: high.price.clone(),
+            gross_spread_bps,
+            spread_bps,
+            actionable: spread_bps > 0.0,
+        })
+    }
+
+    /// Geometric mean `exp(mean(ln(price_i)))` of the parseable prices, the
+    /// natural average for ratios: unlike the arithmetic mean it is not
+    /// pulled up by a few large quotes. The logs are taken in f64, so the
+    /// result is rounded to 15 significant digits (inputs beyond f64 range
+    /// or precision are approximated). Unparseable prices are skipped and
+    /// counted in `get_unparsed_prices`. The result has source "geomean",
+    /// the first entry's tokens and the latest timestamp. `None` when no
+    /// price parses or any parsed price is zero or negative.
+    #[napi]
+    pub fn calculate_geometric_mean(&self, prices: Vec<PriceData>) -> Option<PriceData> {
+        let parsed = self.parse_decimal_prices(&prices);
+        let first = parsed.first()?.1;
+
+        let mut log_sum = 0.0;
+        for (value, _) in &parsed {
+            let value = value.to_f64().filter(|v| *v > 0.0)?;
+            log_sum += value.ln();
+        }
+        let mean = (log_sum / parsed.len() as f64).exp();
+        let value = Decimal::from_f64(mean)?.round_sf(GEOMEAN_SIGNIFICANT_DIGITS)?;
+
+        Some(PriceData {
+            token_a: first.token_a.clone(),
+            token_b: first.token_b.clone(),
+            price: value.normalize().to_string(),
+            source: "geomean".to_string(),
+            timestamp: parsed.iter().map(|(_, p)| p.timestamp).max()?,
+        })
+    }
+
+    /// Mean of the parseable prices after dropping the lowest and highest
+    /// `trim_pct` percent (`floor(n * trim_pct / 100)` entries from each
+    /// end), in exact decimals: 0 is the plain mean, and just under 50
+    /// leaves the middle one or two, i.e. the median. Unparseable prices are
+    /// skipped and counted in `get_unparsed_prices`. The result has source
+    /// "trimmed_mean", the first entry's tokens and the latest timestamp
+    /// among the averaged prices. `None` when no price parses or `trim_pct`
+    /// is outside `[0, 50)`.
+    #[napi]
+    pub fn calculate_trimmed_mean(&self, prices: Vec<PriceData>, trim_pct: f64) -> Option<PriceData> {
+        if !(0.0..50.0).contains(&trim_pct) {
+            return None;
+        }
+        let mut parsed = self.parse_decimal_prices(&prices);
+        let first = parsed.first()?.1;
+
+        parsed.sort_by_key(|p| p.0);
+        let trim = (parsed.len() as f64 * trim_pct / 100.0) as usize;
+        let kept = &parsed[trim..parsed.len() - trim];
+        let sum = kept.iter().try_fold(Decimal::ZERO, |sum, (value, _)| sum.checked_add(*value))?;
+        let mean = sum.checked_div(Decimal::from(kept.len()))?;
+
+        Some(PriceData {
+            token_a: first.token_a.clone(),
+            token_b: first.token_b.clone(),
+            price: mean.normalize().to_string(),
+            source: "trimmed_mean".to_string(),
+            timestamp: kept.iter().map(|(_, p)| p.timestamp).max()?,
+        })
+    }
+
+    /// Cross-source agreement for one pair, e.g. to gate trades on a spread
+    /// threshold. Prices are parsed as exact decimals like the median;
+    /// unparseable ones are skipped and counted in `get_unparsed_prices`.
+    /// A single usable price gives a spread of 0. Returns `None` when no
+    /// price parses or the lowest is not positive; a spread too wide for
+    /// an `i32` saturates.
+    #[napi]
+    pub fn calculate_spread(&self, prices: Vec<PriceData>) -> Option<PriceSpread> {
+        let parsed = self.parse_decimal_prices(&prices);
+        let (low, high, gross) = Self::spread_between(&parsed)?;
+
+        let spread_bps = gross
+            .and_then(|bps| bps.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero).to_i32())
+            .unwrap_or(i32::MAX);
+        Some(PriceSpread {
+            min: low.price.clone(),
+            max: high.price.clone(),
+            spread_bps,
+            source_count: self.distinct_sources(&parsed),
+        })
+    }
+
+    /// Median price (as `calculate_median_price`, unmemoized) with a
+    /// confidence from how tightly the parseable prices cluster:
+    /// `confidence = clamp(1 - (stddev / mean) / 0.1, 0, 1)`, using the
+    /// population standard deviation, so identical prices give 1.0 and a
+    /// relative deviation of 10% or more gives 0. Agreement needs at least
+    /// two distinct sources; with one, confidence is 0 and `source_count`
+    /// flags why. A non-positive mean also gives 0. `None` when no price
+    /// parses.
+    #[napi]
+    pub fn calculate_price_with_confidence(&self, prices: Vec<PriceData>) -> Option<PricedWithConfidence> {
+        let mut parsed = self.parse_decimal_prices(&prices);
+        parsed.sort_by_key(|p| p.0);
+        let median = Self::sorted_median(&parsed)?;
+        let source_count = self.distinct_sources(&parsed);
+
+        // Equal weights make the dispersion moments the population mean and stddev
+        let weight = 1.0 / parsed.len() as f64;
+        let entries: Vec<(f64, f64, &PriceData)> =
+            parsed.iter().filter_map(|(value, p)| Some((value.to_f64()?, weight, *p))).collect();
+        let (mean, std_dev) = Self::weighted_mean_std_dev(&entries);
+        let confidence = if source_count < 2 || mean <= 0.0 {
+            0.0
+        } else {
+            (1.0 - std_dev / mean / ZERO_CONFIDENCE_DISPERSION).clamp(0.0, 1.0)
+        };
+
+        Some(PricedWithConfidence { price: median.price, confidence, source_count })
+    }
+
+    /// Number of distinct (normalized) sources among parsed prices
+    fn distinct_sources(&self, parsed: &[(Decimal, &PriceData)]) -> u32 {
+        let mut sources: Vec<String> = parsed.iter().map(|(_, p)| self.normalize_key(p.source.clone())).collect();
+        sources.sort_unstable();
+        sources.dedup();
+        sources.len() as u32
+    }
+
+    /// Lowest and highest of the parsed prices and the exact spread between
+    /// them, `(max - min) / min * 10000`, shared by `calculate_spread` and
+    /// `calculate_net_spread`. `None` when empty or the lowest is not
+    /// positive; the spread is `None` only if it overflows a decimal.
+    fn spread_between<'a>(parsed: &[(Decimal, &'a PriceData)]) -> Option<(&'a PriceData, &'a PriceData, Option<Decimal>)> {
+        let &(min, low) = parsed.iter().min_by_key(|p| p.0)?;
+        let &(max, high) = parsed.iter().max_by_key(|p| p.0)?;
+        if min <= Decimal::ZERO {
+            return None;
+        }
+        let spread_bps = (max - min)
+            .checked_mul(Decimal::from(BPS_SCALE as i64))
+            .and_then(|scaled| scaled.checked_div(min));
+        Some((low, high, spread_bps))
+    }
+
+    /// Number of price/weight entries dropped by the weighted methods
+    #[napi]
+    pub fn get_weighted_skips(&self) -> f64 {
+        *self.weighted_skips.read() as f64
+    }
+
+    /// Parse prices, drop non-finite/negative entries (counting them) and
+    /// normalize the remaining weights to sum to 1
+    fn normalized_entries<'a>(&self, prices: &'a [PriceData], weights: &[f64]) -> Option<Vec<(f64, f64, &'a PriceData)>> {
+        let mut skipped = 0u64;
+        let mut entries: Vec<(f64, f64, &PriceData)> = Vec::with_capacity(prices.len());
+
+        for (price, &weight) in prices.iter().zip(weights) {
+            match parse_price(&price.price) {
+                Some(value) if weight.is_finite() && weight >= 0.0 => entries.push((value, weight, price)),
+                _ => skipped += 1,
+            }
+        }
+
+        if skipped > 0 {
+            *self.weighted_skips.write() += skipped;
+        }
+
+        let total: f64 = entries.iter().map(|e| e.1).sum();
+        if !(total.is_finite() && total > 0.0) {
+            return None;
+        }
+
+        for entry in &mut entries {
+            entry.1 /= total;
+        }
+        Some(entries)
+    }
+
+    /// Min and max price observed for a pair in `[now_ms - window_ms, now_ms]`,
+    /// from the history recorded by `aggregate_prices`. `None` when no
+    /// observation falls in the window.
+    #[napi]
+    pub fn get_range(&self, token_a: String, token_b: String, window_ms: i64, now_ms: i64) -> Option<PriceRange> {
+        let history = self.history.read();
+        let samples = history.get(&self.pair_key(&token_a, &token_b))?;
+        let since = now_ms.saturating_sub(window_ms);
+
+        let mut range: Option<PriceRange> = None;
+        for &(ts, value) in samples.iter().filter(|(ts, _)| *ts >= since && *ts <= now_ms) {
+            let r = range.get_or_insert(PriceRange {
+                min_price: value,
+                min_timestamp: ts,
+                max_price: value,
+                max_timestamp: ts,
+                samples: 0,
+            });
+            if value < r.min_price {
+                r.min_price = value;
+                r.min_timestamp = ts;
+            }
+            if value > r.max_price {
+                r.max_price = value;
+                r.max_timestamp = ts;
+            }
+            r.samples += 1;
+        }
+        range
+    }
+
+    /// Pearson correlation of price changes between two pairs over
+    /// `[now_ms - window_ms, now_ms]`, from the history recorded by
+    /// `aggregate_prices`.
+    ///
+    /// The series are aligned on the aggregation timestamps both pairs have
+    /// in the window (the last observation wins when a pair was seen twice at
+    /// the same timestamp). Changes are relative moves between consecutive
+    /// aligned timestamps, so at least `MIN_CORRELATION_SAMPLES + 1` shared
+    /// timestamps are needed. Returns `None` below that, or when either
+    /// series is flat (zero variance).
+    #[napi]
+    pub fn compute_correlation(
+        &self,
+        token_a1: String,
+        token_b1: String,
+        token_a2: String,
+        token_b2: String,
+        window_ms: i64,
+        now_ms: i64,
+    ) -> Option<f64> {
+        let since = now_ms.saturating_sub(window_ms);
+        let history = self.history.read();
+        let in_window = |token_a: &str, token_b: &str| -> Option<AHashMap<i64, f64>> {
+            let samples = history.get(&self.pair_key(token_a, token_b))?;
+            Some(
+                samples
+                    .iter()
+                    .filter(|(ts, _)| *ts >= since && *ts <= now_ms)
+                    .copied()
+                    .collect(),
+            )
+        };
+        let first = in_window(&token_a1, &token_b1)?;
+        let second = in_window(&token_a2, &token_b2)?;
+
+        let mut shared: Vec<(i64, f64, f64)> = first
+            .iter()
+            .filter_map(|(ts, &x)| second.get(ts).map(|&y| (*ts, x, y)))
+            .collect();
+        shared.sort_unstable_by_key(|&(ts, _, _)| ts);
+
+        let changes: Vec<(f64, f64)> = shared
+            .windows(2)
+            .filter(|w| w[0].1 != 0.0 && w[0].2 != 0.0)
+            .map(|w| (w[1].1 / w[0].1 - 1.0, w[1].2 / w[0].2 - 1.0))
+            .collect();
+        if changes.len() < MIN_CORRELATION_SAMPLES {
+            return None;
+        }
+
+        let n = changes.len() as f64;
+        let mean_x = changes.iter().map(|c| c.0).sum::<f64>() / n;
+        let mean_y = changes.iter().map(|c| c.1).sum::<f64>() / n;
+        let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+        for &(x, y) in &changes {
+            cov += (x - mean_x) * (y - mean_y);
+            var_x += (x - mean_x).powi(2);
+            var_y += (y - mean_y).powi(2);
+        }
+        if var_x == 0.0 || var_y == 0.0 {
+            return None;
+        }
+        Some((cov / (var_x.sqrt() * var_y.sqrt())).clamp(-1.0, 1.0))
+    }
+
+    #[napi]
+    pub fn clear_history(&self) {
+        self.history.write().clear();
+    }
+
+    /// Add a sample for `calculate_twap`, stamped with `price.timestamp`.
+    /// Independent of `aggregate_prices` and its history. Samples may
+    /// arrive out of order; each pair keeps the newest 256, pruned here
+    /// (reads never modify them). Unparseable prices are rejected.
+    #[napi]
+    pub fn record_price(&self, price: PriceData) -> napi::Result<()> {
+        let value = parse_decimal(&price.price).ok_or(Error::InvalidPrice { raw: price.price.clone() })?;
+        let mut twap_samples = self.twap_samples.write();
+        let samples = twap_samples.entry(self.pair_key(&price.token_a, &price.token_b)).or_default();
+
+        let at = samples.partition_point(|(ts, _)| *ts <= price.timestamp);
+        samples.insert(at, (price.timestamp, value));
+        if samples.len() > HISTORY_CAPACITY {
+            samples.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Time-weighted average of the `record_price` samples for a pair over
+    /// `[now_ms - window_ms, now_ms]`, as a step function: each sample's
+    /// price holds until the next sample (or `now_ms`), and the last sample
+    /// before the window carries in at the window start. With those steps
+    /// `p_i` from `t_i`: `sum(p_i * (t_{i+1} - t_i)) / (now_ms - t_0)`,
+    /// where `t_0` is the window start, or the first sample when none
+    /// precedes the window (time before any sample is not counted). An
+    /// empty window gives the price in effect at `now_ms`. Samples after
+    /// `now_ms` are ignored. The result has source "twap" and timestamp
+    /// `now_ms`; `None` when no sample is at or before `now_ms`.
+    #[napi]
+    pub fn calculate_twap(&self, token_a: String, token_b: String, window_ms: i64, now_ms: i64) -> Option<PriceData> {
+        let since = now_ms.saturating_sub(window_ms.max(0));
+        let twap_samples = self.twap_samples.read();
+        let samples = twap_samples.get(&self.pair_key(&token_a, &token_b))?;
+
+        let start = samples.partition_point(|(ts, _)| *ts < since);
+        let end = samples.partition_point(|(ts, _)| *ts <= now_ms);
+        let carried = start.checked_sub(1).map(|i| (since, samples[i].1));
+        let steps: Vec<(i64, Decimal)> = carried.into_iter().chain(samples.range(start..end).copied()).collect();
+
+        let &(first_ts, _) = steps.first()?;
+        let elapsed = now_ms - first_ts;
+        let value = if elapsed == 0 {
+            steps.last()?.1
+        } else {
+            let mut weighted = Decimal::ZERO;
+            for (i, &(ts, value)) in steps.iter().enumerate() {
+                let until = steps.get(i + 1).map_or(now_ms, |next| next.0);
+                weighted = weighted.checked_add(value.checked_mul(Decimal::from(until - ts))?)?;
+            }
+            weighted.checked_div(Decimal::from(elapsed))?
+        };
+
+        Some(PriceData {
+            token_a,
+            token_b,
+            price: value.normalize().to_string(),
+            source: "twap".to_string(),
+            timestamp: now_ms,
+        })
+    }
+
+    /// Evict old entries to save memory (lightweight mode)
+    fn evict_old_entries(&self, cache: &mut AHashMap<String, CachedPrice>, current_time_ms: i64) {
+        Self::evict_expired(cache, *self.cache_timeout_ms.read(), current_time_ms);
+    }
+
+    fn evict_expired(cache: &mut AHashMap<String, CachedPrice>, cache_timeout_ms: i64, current_time_ms: i64) {
+        cache.retain(|_, v| {
+            age_ms(current_time_ms, v.timestamp) < cache_timeout_ms
+        });
+    }
+
+    /// Evict expired cache entries every `interval_ms` on a background
+    /// thread, using the wall clock and the cache timeout current at each
+    /// pass, so an idle aggregator doesn't hold stale prices until its next
+    /// call (e.g. with `get_lightweight_config().gc_interval_ms`). Replaces
+    /// a running GC. The thread holds only the cache, not the aggregator,
+    /// and exits on `stop_gc` or once the aggregator is dropped. Errors in
+    /// deterministic mode or for a non-positive interval.
+    #[napi]
+    pub fn start_gc(&self, interval_ms: i64) -> napi::Result<()> {
+        wall_clock_ms("start_gc")?;
+        if interval_ms <= 0 {
+            return Err(Error::invalid_argument("gc interval", format!("must be positive, got {}", interval_ms)).into());
+        }
+        self.stop_gc();
+
+        let (stop, stopped) = mpsc::channel();
+        let cache = self.price_cache.clone();
+        let cache_timeout_ms = self.cache_timeout_ms.clone();
+        let interval = Duration::from_millis(interval_ms as u64);
+        let thread = std::thread::Builder::new()
+            .name("aggregator-gc".to_string())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let timeout_ms = *cache_timeout_ms.read();
+                    Self::evict_expired(&mut cache.write(), timeout_ms, now_ms());
+                }
+            })
+            .map_err(|e| Error::InvalidInput(format!("failed to start the GC thread: {}", e)))?;
+        *self.gc.write() = Some(GcTask { stop, thread });
+        Ok(())
+    }
+
+    /// Stop the `start_gc` thread and wait for it to finish; returns whether
+    /// one was running
+    #[napi]
+    pub fn stop_gc(&self) -> bool {
+        let Some(task) = self.gc.write().take() else {
+            return false;
+        };
+        // A send error means the thread is already gone
+        let _ = task.stop.send(());
+        let _ = task.thread.join();
+        true
+    }
+
+    /// Whether a price for this pair and source is cached (read-only, ignores expiry)
+    #[napi]
+    pub fn contains(&self, token_a: String, token_b: String, source: String) -> bool {
+        let key = self.cache_key(&token_a, &token_b, &source);
+        self.price_cache.read().contains_key(&key)
+    }
+
+    /// Thresholds for `feed_health`: a source whose newest update is more
+    /// than `stale_ms` old is stale; one whose quotes trail the time we
+    /// received them by more than `lag_ms` on average is lagging. Both must
+    /// be positive.
+    #[napi]
+    pub fn set_health_thresholds(&self, stale_ms: i64, lag_ms: i64) -> napi::Result<()> {
+        if stale_ms <= 0 || lag_ms <= 0 {
+            return Err(Error::invalid_argument(
+                "health thresholds",
+                format!("expected positive values, got stale {} and lag {}", stale_ms, lag_ms),
+            )
+            .into());
+        }
+        *self.health_thresholds.write() = (stale_ms, lag_ms);
+        Ok(())
+    }
+
+    /// One-call feed liveness summary for readiness probes, built from the
+    /// cached entries alone (expired ones included, so a silent source
+    /// shows up as stale rather than disappearing). Staleness wins over lag
+    /// when both apply.
+    #[napi]
+    pub fn feed_health(&self, now_ms: i64) -> FeedHealth {
+        let (stale_ms, lag_ms) = *self.health_thresholds.read();
+
+        // source -> (newest received_at, skew sum, samples)
+        let mut by_source: AHashMap<String, (i64, f64, u32)> = AHashMap::new();
+        for cached in self.price_cache.read().values() {
+            let source = self.normalize_key(cached.data.source.clone());
+            let entry = by_source.entry(source).or_insert((i64::MIN, 0.0, 0));
+            entry.0 = entry.0.max(cached.timestamp);
+            entry.1 += cached.data.timestamp.saturating_sub(cached.timestamp) as f64;
+            entry.2 += 1;
+        }
+
+        let mut sources: Vec<SourceHealth> = by_source
+            .into_iter()
+            .map(|(source, (last_seen, skew_sum, samples))| {
+                let age = age_ms(now_ms, last_seen);
+                let skew_ms = skew_sum / samples as f64;
+                let status = if age > stale_ms {
+                    SourceStatus::Stale
+                } else if -skew_ms > lag_ms as f64 {
+                    SourceStatus::Lagging
+                } else {
+                    SourceStatus::Healthy
+                };
+                SourceHealth {
+                    source,
+                    last_seen_age_ms: age as f64,
+                    skew_ms,
+                    status: status.as_str().to_string(),
+                }
+            })
+            .collect();
+        sources.sort_by(|a, b| a.source.cmp(&b.source));
+
+        let count = |status: SourceStatus| sources.iter().filter(|s| s.status == status.as_str()).count() as u32;
+        let stale_sources = count(SourceStatus::Stale);
+        let lagging_sources = count(SourceStatus::Lagging);
+        FeedHealth {
+            healthy: !sources.is_empty() && stale_sources == 0 && lagging_sources == 0,
+            stale_sources,
+            lagging_sources,
+            sources,
+        }
+    }
+
+    #[napi]
+    pub fn get_cache_size(&self) -> u32 {
+        self.price_cache.read().len() as u32
+    }
+
+    /// Debugging aid for an aggregator suspected of corruption: checks
+    /// that every cache entry sits under the key of the pair and source it
+    /// holds, that price history stays within `HISTORY_CAPACITY` per pair,
+    /// that a disabled memo is empty, and that no entry or sample is stamped
+    /// in the future. Throws a "corrupt state" error naming the first
+    /// violation. Reads the wall clock (except in deterministic mode, which
+    /// skips the future-timestamp checks) and takes the cache, history and
+    /// memo locks, so keep it off the hot path.
+    #[napi]
+    pub fn self_check(&self) -> napi::Result<()> {
+        Ok(self.check_invariants().map_err(Error::CorruptState)?)
+    }
+
+    fn check_invariants(&self) -> Result<(), String> {
+        let limit = if is_deterministic() {
+            i64::MAX
+        } else {
+            now_ms().saturating_add(SELF_CHECK_FUTURE_TOLERANCE_MS)
+        };
+
+        let cache = self.price_cache.read();
+        for (key, cached) in cache.iter() {
+            if cached.timestamp > limit {
+                return Err(format!("cache entry '{}' is stamped {} ms in the future", key, cached.timestamp - now_ms()));
+            }
+            let expected = self.cache_key(&cached.data.token_a, &cached.data.token_b, &cached.data.source);
+            if !key.eq_ignore_ascii_case(&expected) {
+                return Err(format!("cache entry '{}' holds a price for '{}'", key, expected));
+            }
+        }
+
+        let history = self.history.read();
+        for (pair, samples) in history.iter() {
+            if samples.len() > HISTORY_CAPACITY {
+                return Err(format!("history for '{}' holds {} samples (capacity {})", pair, samples.len(), HISTORY_CAPACITY));
+            }
+            if let Some(&(ts, _)) = samples.iter().find(|(ts, _)| *ts > limit) {
+                return Err(format!("history for '{}' has a sample stamped {} ms in the future", pair, ts - now_ms()));
+            }
+        }
+
+        let memo = self.memo.read();
+        if memo.ttl_ms == 0 && !memo.entries.is_empty() {
+            return Err(format!("memo holds {} entries while disabled", memo.entries.len()));
+        }
+        Ok(())
+    }
+
+    /// Drop every cached entry from `source`; returns the number removed
+    #[napi]
+    pub fn purge_by_source(&self, source: String) -> u32 {
+        let source = self.normalize_key(source);
+        self.purge_where(|data| self.normalize_key(data.source.clone()) == source)
+    }
+
+    /// Drop every cached entry whose pair involves `token` on either side;
+    /// returns the number removed
+    #[napi]
+    pub fn purge_by_token(&self, token: String) -> u32 {
+        let token = self.normalize_key(token);
+        self.purge_where(|data| {
+            self.normalize_key(data.token_a.clone()) == token || self.normalize_key(data.token_b.clone()) == token
+        })
+    }
+
+    /// Single `retain` pass removing entries matching `predicate`
+    fn purge_where(&self, predicate: impl Fn(&PriceData) -> bool) -> u32 {
+        let mut cache = self.price_cache.write();
+        let before = cache.len();
+        cache.retain(|_, cached| !predicate(&cached.data));
+        (before - cache.len()) as u32
+    }
+
+    #[napi]
+    pub fn clear_cache(&self) {
+        self.price_cache.write().clear();
+        self.cache_order.write().clear();
+    }
+
+    /// Every cached price, sorted by cache key, e.g. to warm another
+    /// instance with `load_snapshot`. With `now_ms`, entries past the cache
+    /// timeout are left out; without it everything held is returned.
+    #[napi]
+    pub fn snapshot(&self, now_ms: Option<i64>) -> Vec<PriceData> {
+        let cache_timeout_ms = *self.cache_timeout_ms.read();
+        let cache = self.price_cache.read();
+        let mut entries: Vec<(&String, &CachedPrice)> = cache
+            .iter()
+            .filter(|(_, cached)| now_ms.is_none_or(|now_ms| age_ms(now_ms, cached.timestamp) < cache_timeout_ms))
+            .collect();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        entries.into_iter().map(|(_, cached)| cached.data.clone()).collect()
+    }
+
+    /// Seed the cache with `prices` (e.g. from `snapshot`) as if received
+    /// at `now_ms`, replacing entries for the same pair and source; a later
+    /// duplicate in `prices` wins. Unlike `aggregate_prices` nothing is
+    /// validated, deduped or recorded in history; only entries evicted to
+    /// stay within `max_entries` are counted in the stats.
+    #[napi]
+    pub fn load_snapshot(&self, prices: Vec<PriceData>, now_ms: i64) {
+        let mut cache = self.price_cache.write();
+        let mut evicted = 0;
+        for price in prices {
+            let key = self.cache_key(&price.token_a, &price.token_b, &price.source);
+            evicted += self.make_room(&mut cache, &key, now_ms);
+            cache.insert(key, CachedPrice { data: price, timestamp: now_ms });
+        }
+        self.stats.write().capacity_evictions += evicted as f64;
+    }
+
+    /// Gain over 1 that a `find_triangular` cycle's rate product must
+    /// exceed, in bps (default 0: any product above 1)
+    #[napi]
+    pub fn set_triangular_min_profit_bps(&self, bps: i32) {
+        *self.triangular_min_profit_bps.write() = bps;
+    }
+
+    /// Profitable three-leg cycles among `tokens` in the unexpired cache.
+    ///
+    /// Each cached price quotes `token_a` in `token_b`, so it is the rate
+    /// for the leg a -> b, and its reciprocal the rate for b -> a.
+    /// Every leg uses its best rate across sources and orientations (ties
+    /// by source name). For
+    /// each cycle A -> B -> C -> A over distinct tokens (each rotation once,
+    /// both directions) the three rates are multiplied in exact decimals; a
+    /// cycle whose product exceeds 1 by more than
+    /// `set_triangular_min_profit_bps` is returned as an opportunity
+    /// starting from 1 unit of A: `path` [A, B, C, A], `dexes` the legs'
+    /// sources, `output_amount` the product, `profit_bps` rounded half away
+    /// from zero (saturating), `timestamp` `now_ms` and `price_timestamp`
+    /// the oldest leg's. Cycles with a missing leg are skipped. Results
+    /// follow the order of `tokens`. Reciprocals are rounded to `Decimal`'s
+    /// 28 significant digits, so a product can carry a trailing residue.
+    #[napi]
+    pub fn find_triangular(&self, tokens: Vec<String>, now_ms: i64) -> Vec<Opportunity> {
+        let cache_timeout_ms = *self.cache_timeout_ms.read();
+        let min_gain = bps_to_decimal_ratio(*self.triangular_min_profit_bps.read());
+        let names: Vec<String> = tokens.iter().map(|token| self.normalize_key(token.clone())).collect();
+
+        // (from, to) token indices -> best (rate, quote)
+        let index = |token: &str| names.iter().position(|name| name == token);
+        let mut legs: AHashMap<(usize, usize), (Decimal, &PriceData)> = AHashMap::new();
+        let cache = self.price_cache.read();
+        for cached in cache.values().filter(|cached| age_ms(now_ms, cached.timestamp) < cache_timeout_ms) {
+            let data = &cached.data;
+            let (Some(a), Some(b)) = (index(&self.normalize_key(data.token_a.clone())), index(&self.normalize_key(data.token_b.clone())))
+            else {
+                continue;
+            };
+            let Some(price) = parse_decimal(&data.price).filter(|p| *p > Decimal::ZERO) else {
+                continue;
+            };
+            let inverse = Decimal::ONE.checked_div(price);
+            for (leg, rate) in [((a, b), Some(price)), ((b, a), inverse)] {
+                let Some(rate) = rate else { continue };
+                let best = legs.entry(leg).or_insert((rate, data));
+                if rate > best.0 || (rate == best.0 && data.source < best.1.source) {
+                    *best = (rate, data);
+                }
+            }
+        }
+
+        let mut found = Vec::new();
+        let n = names.len();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                for k in (i + 1)..n {
+                    // Repeated tokens only count at their first position
+                    if j == k || [i, j, k].iter().any(|&t| index(&names[t]) != Some(t)) {
+                        continue;
+                    }
+                    let route = [(i, j), (j, k), (k, i)].map(|leg| legs.get(&leg));
+                    let [Some(ab), Some(bc), Some(ca)] = route else {
+                        continue;
+                    };
+                    let Some(product) = ab.0.checked_mul(bc.0).and_then(|p| p.checked_mul(ca.0)) else {
+                        continue;
+                    };
+                    let gain = product - Decimal::ONE;
+                    if gain <= min_gain {
+                        continue;
+                    }
+                    let profit_bps = gain
+                        .checked_mul(Decimal::from(BPS_SCALE as i64))
+                        .and_then(|bps| bps.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero).to_i32())
+                        .unwrap_or(i32::MAX);
+                    found.push(Opportunity {
+                        path: [i, j, k, i].map(|t| tokens[t].clone()).to_vec(),
+                        dexes: [ab, bc, ca].map(|leg| leg.1.source.clone()).to_vec(),
+                        input_amount: "1".to_string(),
+                        output_amount: product.normalize().to_string(),
+                        profit: gain.normalize().to_string(),
+                        profit_bps,
+                        timestamp: now_ms,
+                        liquidity: None,
+                        price_timestamp: Some(ab.1.timestamp.min(bc.1.timestamp).min(ca.1.timestamp)),
+                    });
+                }
+            }
+        }
+        found
+    }
+
+    /// Before caching under `key` at `cached_at`, evict the entries cached
+    /// longest ago (ties by key) until a new entry fits within
+    /// `max_entries`, and index the new entry. Returns the number evicted;
+    /// replacing an existing key needs no room.
+    fn make_room(&self, cache: &mut AHashMap<String, CachedPrice>, key: &str, cached_at: i64) -> usize {
+        if self.max_entries == 0 {
+            return 0;
+        }
+        let mut order = self.cache_order.write();
+        let mut evicted = 0;
+        if !cache.contains_key(key) {
+            while cache.len() >= self.max_entries {
+                let Some((at, oldest)) = order.pop_first() else { break };
+                // Stale index entry: the key was removed or re-cached since
+                if cache.get(&oldest).is_some_and(|cached| cached.timestamp == at) {
+                    cache.remove(&oldest);
+                    evicted += 1;
+                }
+            }
+        }
+        order.insert((cached_at, key.to_string()));
+        if order.len() > 2 * self.max_entries {
+            order.retain(|(at, key)| cache.get(key).is_some_and(|cached| cached.timestamp == *at));
+        }
+        evicted
+    }
+
+    /// Bytes held by the price cache: the map itself, its table (sized by
+    /// capacity, so it doesn't shrink on eviction), and the bytes of every
+    /// key and `PriceData` string. Allocator overhead is not counted.
+    #[napi]
+    pub fn get_memory_usage(&self) -> f64 {
+        let cache = self.price_cache.read();
+        let strings: usize = cache
+            .iter()
+            .map(|(key, cached)| {
+                let data = &cached.data;
+                key.len() + data.token_a.len() + data.token_b.len() + data.price.len() + data.source.len()
+            })
+            .sum();
+        (std::mem::size_of::<AHashMap<String, CachedPrice>>() + table_bytes(&cache) + strings) as f64
+    }
+}
+
+// Separate impl block so the export disappears when the feature is off
+#[cfg(feature = "rayon")]
+#[napi]
+impl TurboAggregator {
+    /// `aggregate_grouped` with the per-pair medians computed in parallel on
+    /// the rayon pool. Dedup and cache updates still run serially through
+    /// `aggregate_prices` first; only the independent median step fans out.
+    /// Results are identical to `aggregate_grouped`, in the same order.
+    ///
+    /// The serial dedup/cache pass caps the gain from parallel medians.
+    /// Measured by `bench_grouped_parallel` (release build, one x86_64
+    /// core, so one rayon thread), parallel never pulls ahead of
+    /// `aggregate_grouped` beyond noise at any pair count from 10 to 2500
+    /// (0.7x to 1.17x). There is no crossover on a single core; multi-core
+    /// hosts are unmeasured, so rerun the benchmark there before switching.
+    #[napi]
+    pub fn aggregate_grouped_parallel(&self, prices: Vec<PriceData>, now_ms: i64) -> Vec<PairAggregate> {
+        use rayon::prelude::*;
+
+        let mut groups = self.group_aggregated(prices, now_ms);
+        groups.par_iter_mut().for_each(|group| {
+            group.median = self.calculate_median_price(group.prices.clone());
+        });
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `token_a/token_b` quote from `source`, shared by the tests below
+    fn price(token_a: &str, token_b: &str, value: &str, source: &str, timestamp: i64) -> PriceData {
+        PriceData {
+            token_a: token_a.to_string(),
+            token_b: token_b.to_string(),
+            price: value.to_string(),
+            source: source.to_string(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_turbo_aggregator() {
+        let aggregator = TurboAggregator::new(10000, None);
+        
+        let price1 = PriceData {
+            token_a: "A".to_string(),
+            token_b: "B".to_string(),
+            price: "100.5".to_string(),
+            source: "dex1".to_string(),
+            timestamp: 1000,
+        };
+
+        let price2 = price1.clone();
+
+        let prices = vec![price1, price2];
+        let aggregated = aggregator.aggregate_prices(prices, 1000);
+        
+        // Should deduplicate
+        assert_eq!(aggregated.len(), 1);
+    }
+
+    #[test]
+    fn test_median_calculation() {
+        let aggregator = TurboAggregator::new(10000, None);
+        
+        let prices = vec![
+            PriceData {
+                token_a: "A".to_string(),
+                token_b: "B".to_string(),
+                price: "100".to_string(),
+                source: "dex1".to_string(),
+                timestamp: 1000,
+            },
+            PriceData {
+                token_a: "A".to_string(),
+                token_b: "B".to_string(),
+                price: "105".to_string(),
+                source: "dex2".to_string(),
+                timestamp: 1000,
             },
             PriceData {
                 token_a: "A".to_string(),
                 token_b: "B".to_string(),
-                price: "110".to_string(),
-                source: "dex3".to_string(),
+                price: "110".to_string(),
+                source: "dex3".to_string(),
+                timestamp: 1000,
+            },
+        ];
+
+        let median = aggregator.calculate_median_price(prices).unwrap();
+        assert_eq!(median.price, "105");
+    }
+
+    #[test]
+    fn test_reconfigure_applies_mode() {
+        let _modes = crate::ModeGuard::acquire();
+        let aggregator = TurboAggregator::new(10000, None);
+        assert_eq!(aggregator.get_cache_timeout_ms(), 10000);
+        for (i, token) in ["T0", "T1", "T2", "T3"].iter().enumerate() {
+            aggregator.aggregate_prices(vec![price(token, "USDC", "100", "dex1", 0)], i as i64 * 3_000);
+        }
+        assert_eq!(aggregator.reconfigure(), 0);
+
+        // Halved to 5s: T0 (9s before T3) and T1 (6s) no longer fit
+        crate::set_lightweight_mode(true);
+        assert_eq!(aggregator.reconfigure(), 2);
+        assert_eq!(aggregator.get_cache_timeout_ms(), 5000);
+        let cached: Vec<String> = aggregator.snapshot(None).into_iter().map(|p| p.token_a).collect();
+        assert_eq!(cached, vec!["T2", "T3"]);
+
+        crate::set_lightweight_mode(false);
+        assert_eq!(aggregator.reconfigure(), 0);
+        assert_eq!(aggregator.get_cache_timeout_ms(), 10000);
+    }
+
+    #[test]
+    fn test_try_new_validates() {
+        assert!(TurboAggregator::try_new(0, None).is_ok());
+        let err = TurboAggregator::try_new(-1, None).err().unwrap();
+        assert_eq!(err.reason, "invalid cache_timeout_ms: must be non-negative, got -1");
+    }
+
+    #[test]
+    fn test_price_quantum_suppresses_jitter() {
+        let aggregator = TurboAggregator::new(1000, None);
+
+        // Without a quantum every sub-tick wobble is a change
+        for (i, value) in ["100.001", "100.002", "100.003"].iter().enumerate() {
+            aggregator.aggregate_prices(vec![price("A", "B", value, "dex1", 0)], (i as i64 + 1) * 10_000);
+        }
+        assert_eq!(aggregator.get_price_changes(), 2.0);
+
+        aggregator.clear_cache();
+        aggregator
+            .set_price_quantum("A".to_string(), "B".to_string(), "0.01".to_string())
+            .unwrap();
+
+        let mut returned = Vec::new();
+        for (i, value) in ["100.001", "100.002", "99.996", "100.004"].iter().enumerate() {
+            let batch = vec![price("A", "B", value, "dex1", 0)];
+            returned.extend(aggregator.aggregate_prices(batch, (i as i64 + 1) * 10_000));
+        }
+        assert_eq!(aggregator.get_price_changes(), 2.0);
+        assert!(returned.iter().all(|p| p.price == "100.00"));
+
+        // A move past half a quantum is a real change
+        aggregator.aggregate_prices(vec![price("A", "B", "100.006", "dex1", 0)], 100_000);
+        assert_eq!(aggregator.get_price_changes(), 3.0);
+
+        // Scientific quanta keep their precision, and steps are exact
+        aggregator
+            .set_price_quantum("A".to_string(), "B".to_string(), "1e-2".to_string())
+            .unwrap();
+        let snapped = aggregator.aggregate_prices(vec![price("A", "B", "0.3049", "dex1", 0)], 200_000);
+        assert_eq!(snapped[0].price, "0.30");
+        aggregator
+            .set_price_quantum("A".to_string(), "B".to_string(), "0.1".to_string())
+            .unwrap();
+        let snapped = aggregator.aggregate_prices(vec![price("A", "B", "0.35", "dex1", 0)], 300_000);
+        assert_eq!(snapped[0].price, "0.4");
+
+        assert!(aggregator
+            .set_price_quantum("A".to_string(), "B".to_string(), "0".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn test_purge_by_source_and_token() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        aggregator.aggregate_prices(
+            vec![
+                price("WETH", "USDC", "1", "dex1", 1000),
+                price("WETH", "USDC", "1", "bad", 1000),
+                price("WBTC", "USDC", "1", "bad", 1000),
+                price("WBTC", "DAI", "1", "dex1", 1000),
+                price("DAI", "WETH", "1", "dex2", 1000),
+            ],
+            1000,
+        );
+        assert_eq!(aggregator.get_cache_size(), 5);
+
+        assert_eq!(aggregator.purge_by_source("bad".to_string()), 2);
+        assert!(aggregator.contains("WETH".to_string(), "USDC".to_string(), "dex1".to_string()));
+        assert!(!aggregator.contains("WETH".to_string(), "USDC".to_string(), "bad".to_string()));
+
+        // Matches either side of the pair
+        assert_eq!(aggregator.purge_by_token("WETH".to_string()), 2);
+        assert!(aggregator.contains("WBTC".to_string(), "DAI".to_string(), "dex1".to_string()));
+        assert_eq!(aggregator.get_cache_size(), 1);
+
+        assert_eq!(aggregator.purge_by_token("UNKNOWN".to_string()), 0);
+    }
+
+    #[test]
+    fn test_vwap() {
+        let aggregator = TurboAggregator::new(10000, None);
+        let prices = vec![
+            price("WETH", "USDC", "3000", "deep", 10),
+            price("WETH", "USDC", "3100", "thin", 30),
+            price("WETH", "USDC", "3010", "mid", 20),
+        ];
+        let weights = |w: &[&str]| w.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+
+        // (3000*900 + 3100*10 + 3010*90) / 1000
+        let vwap = aggregator.calculate_vwap(prices.clone(), weights(&["900", "10", "90"])).unwrap();
+        assert_eq!((vwap.price.as_str(), vwap.source.as_str(), vwap.timestamp), ("3001.9", "vwap", 30));
+        let median = aggregator.calculate_median_price(prices.clone()).unwrap();
+        assert_ne!(vwap.price, median.price);
+
+        // Zero-weight entries don't contribute (not even their timestamp)
+        let vwap = aggregator.calculate_vwap(prices.clone(), weights(&["1", "0", "1.0"])).unwrap();
+        assert_eq!((vwap.price.as_str(), vwap.timestamp), ("3005", 20));
+
+        // Unparseable price or weight, or a negative weight, is skipped and counted
+        let mut with_bad = prices.clone();
+        with_bad.push(price("WETH", "USDC", "oops", "broken", 99));
+        let vwap = aggregator.calculate_vwap(with_bad, weights(&["1", "-5", "x", "1"])).unwrap();
+        assert_eq!(vwap.price, "3000");
+        assert_eq!(aggregator.get_weighted_skips(), 3.0);
+
+        assert!(aggregator.calculate_vwap(prices.clone(), weights(&["0", "0", "0"])).is_none());
+        assert!(aggregator.calculate_vwap(prices, weights(&["1"])).is_none());
+    }
+
+    #[test]
+    fn test_even_count_median_averages_middle_pair() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        let two = aggregator.calculate_median_price(vec![
+            price("WETH", "USDC", "3001", "a", 5),
+            price("WETH", "USDC", "3000", "b", 7),
+        ]).unwrap();
+        assert_eq!((two.price.as_str(), two.source.as_str(), two.timestamp), ("3000.5", "median", 7));
+        assert_eq!((two.token_a.as_str(), two.token_b.as_str()), ("WETH", "USDC"));
+
+        // Odd counts keep the middle entry unchanged
+        let three = aggregator
+            .calculate_median_price(vec![
+                price("WETH", "USDC", "3002", "a", 1),
+                price("WETH", "USDC", "3000.00", "b", 2),
+                price("WETH", "USDC", "3001.0", "c", 3),
+            ])
+            .unwrap();
+        assert_eq!((three.price.as_str(), three.source.as_str()), ("3001.0", "c"));
+
+        // 4 quotes: no longer biased towards the upper middle
+        let four = aggregator
+            .calculate_median_price(vec![
+                price("WETH", "USDC", "3010", "a", 1),
+                price("WETH", "USDC", "3000", "b", 1),
+                price("WETH", "USDC", "3001.5", "c", 1),
+                price("WETH", "USDC", "3003", "d", 1),
+            ])
+            .unwrap();
+        assert_eq!((four.price.as_str(), four.source.as_str()), ("3002.25", "median"));
+
+        // The selection path agrees on even counts
+        let many: Vec<PriceData> = (0..40).map(|i| price("WETH", "USDC", &((i * 7) % 40).to_string(), "s", i)).collect();
+        let fast = aggregator.calculate_median_fast(many.clone()).unwrap();
+        let exact = aggregator.calculate_median_price(many).unwrap();
+        assert_eq!((fast.price.as_str(), fast.timestamp), (exact.price.as_str(), exact.timestamp));
+        assert_eq!(fast.price, "19.5");
+
+        assert!(aggregator.calculate_median_price(Vec::new()).is_none());
+        let single = aggregator.calculate_median_price(vec![price("WETH", "USDC", "7", "only", 1)]).unwrap();
+        assert_eq!(single.source, "only");
+    }
+
+    #[test]
+    fn test_median_is_exact_for_18_decimal_prices() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        // All three collapse to the same f64
+        let prices = vec![
+            price("WETH", "DAI", "3000.000000000000000003", "high", 1000),
+            price("WETH", "DAI", "3000.000000000000000001", "low", 1000),
+            price("WETH", "DAI", "3000.000000000000000002", "mid", 1000),
+            price("WETH", "DAI", "not a price", "broken", 1000),
+        ];
+        let median = aggregator.calculate_median_price(prices).unwrap();
+        assert_eq!((median.source.as_str(), median.price.as_str()), ("mid", "3000.000000000000000002"));
+        assert_eq!(aggregator.get_unparsed_prices(), 1.0);
+
+        // Same rank through the selection path
+        let many: Vec<PriceData> = (0..41)
+            .map(|i| price("WETH", "DAI", &format!("0.0000000000000000{:02}", (i * 17) % 41 + 10), &format!("s{}", i), 1000))
+            .collect();
+        let median = aggregator.calculate_median_fast(many).unwrap();
+        assert_eq!(median.price, "0.000000000000000030");
+
+        // More digits than fit exactly are counted, not approximated
+        let median = aggregator
+            .calculate_median_price(vec![
+                price("WETH", "DAI", "1.0000000000000000000000000000001", "long", 1000),
+                price("WETH", "DAI", "2", "ok", 1000),
+            ])
+            .unwrap();
+        assert_eq!(median.source, "ok");
+        assert_eq!(aggregator.get_unparsed_prices(), 2.0);
+
+        // Bounds compare exactly too
+        aggregator
+            .set_price_bounds("WETH".to_string(), "DAI".to_string(), "0".to_string(), "3000.000000000000000002".to_string())
+            .unwrap();
+        let kept = aggregator.aggregate_prices(
+            vec![
+                price("WETH", "DAI", "3000.000000000000000002", "edge", 1000),
+                price("WETH", "DAI", "3000.000000000000000003", "over", 1000),
+                price("WETH", "DAI", "x", "bad", 1000),
+            ],
+            1000,
+        );
+        assert_eq!(kept.len(), 1);
+        assert_eq!(aggregator.get_rejected_out_of_band(), 1.0);
+        assert_eq!(aggregator.get_unparsed_prices(), 3.0);
+    }
+
+    #[test]
+    fn test_price_bounds() {
+        let aggregator = TurboAggregator::new(10000, None);
+        aggregator
+            .set_price_bounds("A".to_string(), "B".to_string(), "90".to_string(), "110".to_string())
+            .unwrap();
+
+        let aggregated = aggregator.aggregate_prices(
+            vec![
+                price("A", "B", "100", "dex1", 1000),   // in band
+                price("A", "B", "110", "dex2", 1000),   // on the upper edge
+                price("A", "B", "1000", "dex3", 1000),  // fat finger
+                price("A", "B", "0.5", "dex4", 1000),   // oracle glitch
+                price("A", "C", "1000", "dex1", 1000),  // no band configured
+            ],
+            1000,
+        );
+
+        let sources: Vec<(&str, &str)> = aggregated
+            .iter()
+            .map(|p| (p.token_b.as_str(), p.source.as_str()))
+            .collect();
+        assert_eq!(sources, vec![("B", "dex1"), ("B", "dex2"), ("C", "dex1")]);
+        assert_eq!(aggregator.get_rejected_out_of_band(), 2.0);
+
+        assert!(aggregator
+            .set_price_bounds("A".to_string(), "B".to_string(), "10".to_string(), "1".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn test_contains_is_read_only() {
+        let aggregator = TurboAggregator::new(10000, None);
+        let price = PriceData {
+            token_a: "A".to_string(),
+            token_b: "B".to_string(),
+            price: "100".to_string(),
+            source: "dex1".to_string(),
+            timestamp: 1000,
+        };
+
+        assert!(!aggregator.contains("A".to_string(), "B".to_string(), "dex1".to_string()));
+        assert_eq!(aggregator.get_cache_size(), 0);
+
+        aggregator.aggregate_prices(vec![price], 1000);
+        assert!(aggregator.contains("A".to_string(), "B".to_string(), "dex1".to_string()));
+        assert!(!aggregator.contains("A".to_string(), "B".to_string(), "dex2".to_string()));
+        assert_eq!(aggregator.get_cache_size(), 1);
+    }
+
+    #[test]
+    fn test_aggregate_grouped() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        let groups = aggregator.aggregate_grouped(
+            vec![
+                price("WETH", "USDC", "3000", "dex1", 1000),
+                price("WBTC", "USDC", "60000", "dex1", 1000),
+                price("WETH", "USDC", "3010", "dex2", 1000),
+                price("WETH", "USDC", "3020", "dex3", 1000),
+            ],
+            1000,
+        );
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].token_a, "WETH");
+        assert_eq!(groups[0].prices.len(), 3);
+        assert_eq!(groups[0].median.as_ref().unwrap().price, "3010");
+        assert_eq!(groups[1].token_a, "WBTC");
+        assert_eq!(groups[1].median.as_ref().unwrap().price, "60000");
+
+        // Repeating within the dedup window leaves the pair present but empty
+        let groups = aggregator.aggregate_grouped(vec![price("WBTC", "USDC", "60000", "dex1", 1000)], 2000);
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].prices.is_empty());
+        assert!(groups[0].median.is_none());
+    }
+
+    /// Three quotes for each of `pairs` distinct pairs
+    #[cfg(feature = "rayon")]
+    fn multi_pair_batch(pairs: usize) -> Vec<PriceData> {
+        (0..pairs)
+            .flat_map(|pair| {
+                (0..3).map(move |source| PriceData {
+                    token_a: format!("T{}", pair),
+                    token_b: "USDC".to_string(),
+                    price: format!("{}", 100 + pair * 3 + source),
+                    source: format!("dex{}", source),
+                    timestamp: 1000,
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_aggregate_grouped_parallel_matches_sequential() {
+        let batch = multi_pair_batch(300);
+
+        let sequential = TurboAggregator::new(10000, None).aggregate_grouped(batch.clone(), 1000);
+        let parallel = TurboAggregator::new(10000, None).aggregate_grouped_parallel(batch, 1000);
+
+        assert_eq!(parallel.len(), 300);
+        for (a, b) in sequential.iter().zip(&parallel) {
+            assert_eq!(a.token_a, b.token_a);
+            assert_eq!(a.prices.len(), b.prices.len());
+            assert_eq!(a.median.as_ref().map(|m| &m.price), b.median.as_ref().map(|m| &m.price));
+        }
+    }
+
+    /// Sequential vs parallel grouping per pair count, for the
+    /// `aggregate_grouped_parallel` doc: `cargo test --release --features
+    /// rayon bench_grouped_parallel -- --ignored --nocapture`
+    #[cfg(feature = "rayon")]
+    #[test]
+    #[ignore]
+    fn bench_grouped_parallel() {
+        println!("rayon threads: {}", rayon::current_num_threads());
+        for pairs in [10, 50, 100, 250, 500, 1000, 2500] {
+            let batch = multi_pair_batch(pairs);
+            let iters = (20_000 / pairs as u32).max(20);
+            // Fresh aggregator per call, or the dedup window would drop the repeated batch
+            let sequential_ns = crate::timing::bench_ns_per_call(iters, || {
+                let aggregator = TurboAggregator::new(10000, None);
+                std::hint::black_box(aggregator.aggregate_grouped(batch.clone(), 1000));
+            });
+            let parallel_ns = crate::timing::bench_ns_per_call(iters, || {
+                let aggregator = TurboAggregator::new(10000, None);
+                std::hint::black_box(aggregator.aggregate_grouped_parallel(batch.clone(), 1000));
+            });
+            println!(
+                "pairs={:>5}  sequential {:>12.0} ns  parallel {:>12.0} ns  ratio {:.2}",
+                pairs,
+                sequential_ns,
+                parallel_ns,
+                sequential_ns / parallel_ns
+            );
+        }
+    }
+
+    #[test]
+    fn test_collect_by_pair_latest_wins() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        // dex3 is only known from the cache
+        aggregator.aggregate_prices(vec![price("WETH", "USDC", "2990", "dex3", 500)], 500);
+
+        let groups = aggregator.collect_by_pair(
+            vec![
+                price("WETH", "USDC", "3000", "dex1", 1000),
+                price("WETH", "USDC", "3005", "dex1", 1200), // newer dex1 quote wins
+                price("WBTC", "USDC", "60000", "dex1", 1000),
+                price("WETH", "USDC", "3010", "dex2", 1100),
+                price("WETH", "USDC", "2999", "dex1", 900),  // older, ignored
+            ],
+            1500,
+        );
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].token_a, "WETH");
+        let weth: Vec<(&str, &str)> = groups[0]
+            .prices
+            .iter()
+            .map(|p| (p.source.as_str(), p.price.as_str()))
+            .collect();
+        assert_eq!(weth, vec![("dex1", "3005"), ("dex2", "3010"), ("dex3", "2990")]);
+        assert_eq!(groups[1].prices.len(), 1);
+
+        // Read-only with respect to the cache
+        assert_eq!(aggregator.get_cache_size(), 1);
+    }
+
+    #[test]
+    fn test_case_insensitive_keys() {
+        let aggregator = TurboAggregator::new(10000, None);
+        aggregator.set_case_insensitive_keys(true);
+
+        let aggregated = aggregator.aggregate_prices(
+            vec![
+                price("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "USDC", "100", "UniswapV3", 1000),
+                price("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", "USDC", "100", "uniswapv3", 1000),
+            ],
+            1000,
+        );
+        assert_eq!(aggregated.len(), 1);
+        assert_eq!(aggregator.get_cache_size(), 1);
+    }
+
+    #[test]
+    fn test_realized_slippage() {
+        let quoted = price("A", "B", "100", "dex1", 1000);
+        let filled = price("A", "B", "101", "dex1", 1000);
+
+        // Bought at 101 expecting 100: 100 bps adverse
+        let slippage = compute_realized_slippage(quoted.clone(), filled.clone(), true).unwrap();
+        assert!((slippage - 100.0).abs() < 1e-9);
+
+        // Sold at 101 expecting 100: 100 bps favorable
+        let slippage = compute_realized_slippage(quoted.clone(), filled.clone(), false).unwrap();
+        assert!((slippage + 100.0).abs() < 1e-9);
+
+        // Exact where floats drift: (0.707 - 0.7) / 0.7 is 1% on the nose
+        let exact = compute_realized_slippage(
+            price("A", "B", "0.7", "dex1", 1000),
+            price("A", "B", "0.707", "dex1", 1000),
+            true,
+        );
+        assert_eq!(exact.unwrap(), 100.0);
+
+        assert!(compute_realized_slippage(price("A", "B", "0", "dex1", 1000), filled, true).is_err());
+        assert!(compute_realized_slippage(quoted, price("A", "B", "abc", "dex1", 1000), true).is_err());
+    }
+
+    /// `n` quotes for one pair with scrambled prices, distinct for `n` up to 1009
+    fn scrambled_prices(n: usize) -> Vec<PriceData> {
+        (0..n)
+            .map(|i| PriceData {
+                token_a: "A".to_string(),
+                token_b: "B".to_string(),
+                price: format!("{}", (i * 7919) % 1009),
+                source: format!("dex{}", i),
                 timestamp: 1000,
-            },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_median_fast_matches_exact() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        for n in [1, 2, 5, FAST_MEDIAN_THRESHOLD, FAST_MEDIAN_THRESHOLD + 1, 500] {
+            let exact = aggregator.calculate_median_price(scrambled_prices(n)).unwrap();
+            let fast = aggregator.calculate_median_fast(scrambled_prices(n)).unwrap();
+            assert_eq!(exact.price, fast.price, "n = {}", n);
+        }
+
+        assert!(aggregator.calculate_median_fast(Vec::new()).is_none());
+    }
+
+    /// Sort vs selection per input size, behind `FAST_MEDIAN_THRESHOLD`:
+    /// `cargo test --release bench_median_crossover -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_median_crossover() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        for n in [4, 8, 16, 20, 24, 28, 32, 48, 64, 128, 256, 1000] {
+            let prices = scrambled_prices(n);
+            let iters = (200_000 / n as u32).max(1_000);
+            let sort_ns = crate::timing::bench_ns_per_call(iters, || {
+                std::hint::black_box(aggregator.compute_percentile(std::hint::black_box(&prices), MEDIAN_PERCENTILE));
+            });
+            let select_ns = crate::timing::bench_ns_per_call(iters, || {
+                std::hint::black_box(aggregator.select_median(std::hint::black_box(&prices)));
+            });
+            println!("n={:>5}  sort {:>10.0} ns  select {:>10.0} ns  ratio {:.2}", n, sort_ns, select_ns, sort_ns / select_ns);
+        }
+    }
+
+    #[test]
+    fn test_weighted_median_nan_safe() {
+        let aggregator = TurboAggregator::new(10000, None);
+        let prices = || vec![
+            price("A", "B", "100", "dex-100", 1000),
+            price("A", "B", "101", "dex-101", 1000),
+            price("A", "B", "102", "dex-102", 1000),
+            price("A", "B", "bad", "dex-bad", 1000),
         ];
 
-        let median = aggregator.calculate_median_price(prices).unwrap();
-        assert_eq!(median.price, "105");
+        // Un-normalized weights: 102 carries 60% of the mass
+        let median = aggregator.calculate_weighted_median(prices(), vec![20.0, 20.0, 60.0, 1.0]).unwrap();
+        assert_eq!(median.price, "102");
+        assert_eq!(aggregator.get_weighted_skips(), 1.0);
+
+        // NaN/inf/negative weights are dropped, the rest renormalized
+        let median = aggregator
+            .calculate_weighted_median(prices(), vec![3.0, f64::NAN, f64::INFINITY, -1.0])
+            .unwrap();
+        assert_eq!(median.price, "100");
+        assert_eq!(aggregator.get_weighted_skips(), 4.0);
+
+        // Nothing usable left, or mismatched lengths
+        assert!(aggregator.calculate_weighted_median(prices(), vec![0.0, f64::NAN, 0.0, 1.0]).is_none());
+        assert!(aggregator.calculate_weighted_median(prices(), vec![1.0]).is_none());
+    }
+
+    #[test]
+    fn test_get_range() {
+        let aggregator = TurboAggregator::new(1000, None);
+
+        // Each observation lands after the dedup window and cache timeout
+        for (i, value) in ["100", "90", "120", "110"].iter().enumerate() {
+            aggregator.aggregate_prices(vec![price("A", "B", value, "dex1", 0)], (i as i64 + 1) * 10_000);
+        }
+
+        let range = aggregator.get_range("A".to_string(), "B".to_string(), 100_000, 40_000).unwrap();
+        assert_eq!((range.min_price, range.min_timestamp), (90.0, 20_000));
+        assert_eq!((range.max_price, range.max_timestamp), (120.0, 30_000));
+        assert_eq!(range.samples, 4);
+
+        // Only the last observation is inside a 5s window
+        let range = aggregator.get_range("A".to_string(), "B".to_string(), 5_000, 40_000).unwrap();
+        assert_eq!((range.min_price, range.max_price, range.samples), (110.0, 110.0, 1));
+
+        assert!(aggregator.get_range("A".to_string(), "B".to_string(), 5_000, 100_000).is_none());
+        assert!(aggregator.get_range("A".to_string(), "C".to_string(), 100_000, 40_000).is_none());
+
+        // History is bounded per pair
+        for i in 0..(HISTORY_CAPACITY as i64 + 10) {
+            aggregator.aggregate_prices(vec![price("A", "B", "100", "dex1", 0)], 100_000 + i * 10_000);
+        }
+        let history = aggregator.history.read();
+        assert_eq!(history.get("A-B").unwrap().len(), HISTORY_CAPACITY);
+    }
+
+    #[test]
+    fn test_compute_correlation() {
+        let aggregator = TurboAggregator::new(1000, None);
+        let corr = |window_ms: i64| {
+            aggregator.compute_correlation(
+                "USDC".to_string(),
+                "USD".to_string(),
+                "DAI".to_string(),
+                "USD".to_string(),
+                window_ms,
+                50_000,
+            )
+        };
+
+        // Three shared timestamps give only two changes
+        let usdc = [1.0, 1.01, 0.99, 1.02, 1.00];
+        for (i, &value) in usdc.iter().take(3).enumerate() {
+            aggregator.aggregate_prices(vec![
+                price("USDC", "USD", &value.to_string(), "dex1", 0),
+                price("DAI", "USD", &(value * 2.0).to_string(), "dex1", 0),
+            ], (i as i64 + 1) * 10_000);
+        }
+        assert!(corr(100_000).is_none());
+
+        for (i, &value) in usdc.iter().enumerate().skip(3) {
+            aggregator.aggregate_prices(vec![
+                price("USDC", "USD", &value.to_string(), "dex1", 0),
+                price("DAI", "USD", &(value * 2.0).to_string(), "dex1", 0),
+            ], (i as i64 + 1) * 10_000);
+        }
+        // Proportional moves are perfectly correlated
+        assert!((corr(100_000).unwrap() - 1.0).abs() < 1e-9);
+
+        // The window trims the series below the minimum
+        assert!(corr(25_000).is_none());
+
+        // Unknown pair and a flat series have no correlation
+        assert!(aggregator
+            .compute_correlation("X".to_string(), "USD".to_string(), "DAI".to_string(), "USD".to_string(), 100_000, 50_000)
+            .is_none());
+        for (i, &value) in usdc.iter().enumerate() {
+            aggregator.aggregate_prices(vec![
+                price("USDT", "USD", "1", "dex1", 0),
+                price("DAI", "USD", &(value * 2.0).to_string(), "dex1", 0),
+            ], 100_000 + i as i64 * 10_000);
+        }
+        assert!(aggregator
+            .compute_correlation("USDT".to_string(), "USD".to_string(), "DAI".to_string(), "USD".to_string(), 100_000, 150_000)
+            .is_none());
+    }
+
+    #[test]
+    fn test_aggregate_weighted() {
+        let aggregator = TurboAggregator::new(1000, None);
+        let now = 100_000;
+
+        // Single source: its price is the result whatever the weights
+        let single = aggregator.aggregate_weighted(vec![
+            price("A", "B", "42", "dex1", now),
+        ], now, WeightConfig::default());
+        assert_eq!(single.unwrap().price, "42");
+
+        // Recency: a stale low price at two half-lives weighs 0.25 vs 1.0
+        let config = WeightConfig { half_life_ms: Some(10_000.0), ..Default::default() };
+        let prices = vec![price("A", "B", "90", "dex1", now - 20_000), price("A", "B", "110", "dex2", now)];
+        assert_eq!(aggregator.aggregate_weighted(prices.clone(), now, config.clone()).unwrap().price, "110");
+
+        // Source trust can outweigh recency: 4 * 0.25 = 1.0 reaches half first
+        let mut trusted = config.clone();
+        trusted.source_weights.insert("dex1".to_string(), 4.0);
+        assert_eq!(aggregator.aggregate_weighted(prices.clone(), now, trusted).unwrap().price, "90");
+
+        // Zero-weight sources are dropped, not counted as skips
+        let mut muted = config;
+        muted.default_source_weight = Some(0.0);
+        assert!(aggregator.aggregate_weighted(prices.clone(), now, muted.clone()).is_none());
+        muted.source_weights.insert("dex2".to_string(), 1.0);
+        assert_eq!(aggregator.aggregate_weighted(prices, now, muted).unwrap().price, "110");
+        assert_eq!(aggregator.get_weighted_skips(), 0.0);
+
+        // Non-finite weights are skipped and counted
+        let mut bad = WeightConfig::default();
+        bad.source_weights.insert("dex1".to_string(), f64::NAN);
+        let result = aggregator.aggregate_weighted(vec![
+            price("A", "B", "90", "dex1", now),
+            price("A", "B", "95", "dex2", now),
+        ], now, bad);
+        assert_eq!(result.unwrap().price, "95");
+        assert_eq!(aggregator.get_weighted_skips(), 1.0);
+    }
+
+    #[test]
+    fn test_single_source_passthrough() {
+        let aggregator = TurboAggregator::new(10_000, None);
+        aggregator.set_single_source_passthrough(true, Some(true));
+
+        let batch = vec![
+            price("A", "B", "100", "dex1", 0),
+            price("A", "C", "5", "dex1", 0),
+            price("A", "C", "6", "dex2", 0),
+        ];
+        assert_eq!(aggregator.aggregate_prices(batch, 1_000).len(), 3);
+        // Only the multi-source pair was cached
+        assert!(!aggregator.contains("A".to_string(), "B".to_string(), "dex1".to_string()));
+        assert!(aggregator.contains("A".to_string(), "C".to_string(), "dex1".to_string()));
+        assert_eq!(aggregator.get_cache_size(), 2);
+
+        // No dedup window for the pass-through pair: a repeat inside 5s still comes back
+        let repeat = aggregator.aggregate_prices(vec![
+            price("A", "B", "101", "dex1", 0),
+            price("A", "B", "102", "dex1", 0),
+        ], 2_000);
+        assert_eq!(repeat.iter().map(|p| p.price.as_str()).collect::<Vec<_>>(), vec!["101", "102"]);
+        assert_eq!(aggregator.history.read().get("A-B").unwrap().len(), 3);
+
+        // Disabled: the single-source pair goes through the cache again
+        aggregator.set_single_source_passthrough(false, None);
+        assert_eq!(aggregator.aggregate_prices(vec![price("A", "B", "100", "dex1", 0)], 3_000).len(), 1);
+        assert!(aggregator.contains("A".to_string(), "B".to_string(), "dex1".to_string()));
+
+        // By default pass-throughs are still cached and change-counted
+        aggregator.set_single_source_passthrough(true, None);
+        let repeat = aggregator.aggregate_prices(vec![price("A", "B", "103", "dex1", 0)], 3_500);
+        assert_eq!(repeat[0].price, "103");
+        assert_eq!(aggregator.price_cache.read().get("A-B-dex1").unwrap().data.price, "103");
+        assert_eq!(aggregator.get_price_changes(), 1.0);
+    }
+
+    #[test]
+    fn test_feed_health() {
+        let aggregator = TurboAggregator::new(60_000, None);
+
+        // Nothing cached yet: not ready
+        assert!(!aggregator.feed_health(0).healthy);
+
+        aggregator.aggregate_prices(vec![price("WETH", "USDC", "3000", "old", 0)], 0);
+        aggregator.aggregate_prices(vec![
+            price("WETH", "USDC", "3000", "fresh", 100_000),
+            price("WETH", "USDC", "3000", "slow", 92_000),
+        ], 100_000);
+        assert!(aggregator.set_health_thresholds(0, 1).is_err());
+        aggregator.set_health_thresholds(30_000, 5_000).unwrap();
+
+        let health = aggregator.feed_health(101_000);
+        let status: Vec<(&str, &str)> =
+            health.sources.iter().map(|s| (s.source.as_str(), s.status.as_str())).collect();
+        assert_eq!(status, vec![("fresh", "healthy"), ("old", "stale"), ("slow", "lagging")]);
+        assert_eq!(health.sources[1].last_seen_age_ms, 101_000.0);
+        assert_eq!(health.sources[2].skew_ms, -8_000.0);
+        assert_eq!((health.stale_sources, health.lagging_sources), (1, 1));
+        assert!(!health.healthy);
+
+        // Loosened thresholds: everything passes
+        aggregator.set_health_thresholds(200_000, 10_000).unwrap();
+        let health = aggregator.feed_health(101_000);
+        assert!(health.healthy);
+        assert!(health.sources.iter().all(|s| s.status == "healthy"));
+    }
+
+    #[test]
+    fn test_dispersion() {
+        let aggregator = TurboAggregator::new(1000, None);
+        let prices = vec![
+            price("A", "B", "100", "deep1", 0),
+            price("A", "B", "101", "deep2", 0),
+            price("A", "B", "90", "thin", 0),
+            price("A", "B", "bad", "x", 0),
+        ];
+
+        // Equal weights: the thin outlier dominates the picture
+        let even = aggregator.calculate_dispersion(prices.clone(), vec![1.0, 1.0, 1.0, 1.0]).unwrap();
+        assert_eq!(even.samples, 3);
+        assert!((even.mean - 97.0).abs() < 1e-9);
+        assert_eq!((even.q1, even.q3, even.interquartile_range), (90.0, 101.0, 11.0));
+
+        // Liquidity weights shrink both measures
+        let weighted = aggregator.calculate_dispersion(prices.clone(), vec![100.0, 100.0, 2.0, 1.0]).unwrap();
+        assert!(weighted.std_dev < even.std_dev / 2.0);
+        assert_eq!(weighted.interquartile_range, 1.0);
+        assert!((weighted.std_dev_bps - weighted.std_dev / weighted.mean * 10_000.0).abs() < 1e-9);
+
+        // Two equal prices: zero dispersion
+        let flat = aggregator.calculate_dispersion(vec![
+            price("A", "B", "5", "a", 0),
+            price("A", "B", "5", "b", 0),
+        ], vec![1.0, 3.0]).unwrap();
+        assert_eq!((flat.std_dev, flat.interquartile_range), (0.0, 0.0));
+
+        // Insufficient data
+        assert!(aggregator.calculate_dispersion(prices.clone(), vec![1.0]).is_none());
+        assert!(aggregator.calculate_dispersion(prices, vec![1.0, 0.0, 0.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn test_net_spread() {
+        let aggregator = TurboAggregator::new(1000, None);
+        let prices = vec![
+            price("A", "B", "100", "dex1", 0),
+            price("A", "B", "100.2", "dex2", 0),
+            price("A", "B", "bad", "dex3", 0),
+            price("A", "B", "100.1", "dex4", 0),
+        ];
+
+        // 20 bps gross, but 30 + 5.01 bps of fees (the maker's on 100.2) leave -15.01
+        let spread = aggregator.calculate_net_spread(prices.clone(), 30, 5).unwrap();
+        assert_eq!((spread.min_price.as_str(), spread.max_price.as_str()), ("100", "100.2"));
+        assert!((spread.gross_spread_bps - 20.0).abs() < 1e-9);
+        assert!((spread.spread_bps + 15.01).abs() < 1e-9);
+        assert!(!spread.actionable);
+        assert_eq!(aggregator.calculate_spread(prices.clone()).unwrap().spread_bps, 20);
+
+        // A maker rebate can make it actionable
+        let spread = aggregator.calculate_net_spread(prices, 10, -2).unwrap();
+        assert!((spread.spread_bps - 12.004).abs() < 1e-9);
+        assert!(spread.actionable);
+
+        // At a 2x spread the sell leg's 10 bps fee costs 20 bps of the buy price
+        let wide = vec![price("A", "B", "100", "dex1", 0), price("A", "B", "200", "dex2", 0)];
+        let spread = aggregator.calculate_net_spread(wide, 10, 10).unwrap();
+        assert!((spread.gross_spread_bps - 10_000.0).abs() < 1e-9);
+        assert!((spread.spread_bps - 9_970.0).abs() < 1e-9);
+
+        assert!(aggregator.calculate_net_spread(vec![price("A", "B", "100", "dex1", 0)], 0, 0).is_none());
+        assert!(aggregator.calculate_net_spread(vec![
+            price("A", "B", "0", "dex1", 0),
+            price("A", "B", "1", "dex2", 0),
+        ], 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_timeout_bounds() {
+        let _modes = crate::ModeGuard::acquire();
+        let aggregator = TurboAggregator::new(10_000, None);
+        assert!(aggregator.set_timeout_bounds(-1, 100).is_err());
+        assert!(aggregator.set_timeout_bounds(200, 100).is_err());
+
+        // Halving happens first, then the floor lifts 5000 back to 8000
+        crate::set_lightweight_mode(true);
+        aggregator.reconfigure();
+        aggregator.set_timeout_bounds(8_000, 60_000).unwrap();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 8_000);
+
+        // Setting bounds doesn't pick up a mode change; reconfigure does
+        crate::set_lightweight_mode(false);
+        aggregator.set_timeout_bounds(1_000, 60_000).unwrap();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 5_000);
+        aggregator.reconfigure();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 10_000);
+
+        // Ceiling
+        let aggregator = TurboAggregator::new(3_600_000, None);
+        aggregator.set_timeout_bounds(1_000, 60_000).unwrap();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 60_000);
+    }
+
+    #[test]
+    fn test_self_check() {
+        let aggregator = TurboAggregator::new(10_000, None);
+        let price = PriceData {
+            token_a: "A".to_string(),
+            token_b: "B".to_string(),
+            price: "100".to_string(),
+            source: "dex1".to_string(),
+            timestamp: 0,
+        };
+        aggregator.aggregate_prices(vec![price], 1_000);
+        assert_eq!(aggregator.check_invariants(), Ok(()));
+
+        let future = now_ms() + 10 * SELF_CHECK_FUTURE_TOLERANCE_MS;
+        aggregator.price_cache.write().values_mut().for_each(|cached| cached.timestamp = future);
+        let err = aggregator.check_invariants().unwrap_err();
+        assert!(err.starts_with("cache entry 'A-B-dex1'"), "{}", err);
+    }
+
+    #[test]
+    fn test_median_memoization() {
+        let aggregator = TurboAggregator::new(10000, None);
+        let batch = vec![
+            price("A", "B", "100", "dex1", 1000),
+            price("A", "B", "105", "dex2", 1000),
+            price("A", "B", "110", "dex3", 1000),
+        ];
+
+        // Off by default: nothing is counted
+        aggregator.calculate_median_price(batch.clone());
+        assert_eq!(aggregator.get_memo_misses(), 0.0);
+
+        aggregator.set_memoization(60_000);
+        assert_eq!(aggregator.calculate_median_price(batch.clone()).unwrap().price, "105");
+        // Same batch in a different order hits the memo
+        let mut reordered = batch.clone();
+        reordered.reverse();
+        assert_eq!(aggregator.calculate_median_price(reordered).unwrap().price, "105");
+        assert_eq!((aggregator.get_memo_hits(), aggregator.get_memo_misses()), (1.0, 1.0));
+
+        // Any field change is a different batch
+        let mut changed = batch;
+        changed[0].price = "100.0001".to_string();
+        aggregator.calculate_median_price(changed);
+        assert_eq!((aggregator.get_memo_hits(), aggregator.get_memo_misses()), (1.0, 2.0));
+
+        aggregator.clear_memo();
+        assert_eq!((aggregator.get_memo_hits(), aggregator.get_memo_misses()), (0.0, 0.0));
+
+        // Tied prices pick the same source whatever the order, memoized or not
+        let tied = vec![
+            price("A", "B", "100", "dex1", 1000),
+            price("A", "B", "105", "dex2", 1000),
+            price("A", "B", "105", "dex3", 1000),
+            price("A", "B", "110", "dex4", 1000),
+            price("A", "B", "90", "dex5", 1000),
+        ];
+        let mut tied_reordered = tied.clone();
+        tied_reordered.reverse();
+        assert_eq!(aggregator.calculate_median_price(tied).unwrap().source, "dex2");
+        assert_eq!(aggregator.calculate_median_price(tied_reordered.clone()).unwrap().source, "dex2");
+        assert_eq!(aggregator.get_memo_hits(), 1.0);
+        aggregator.set_memoization(0);
+        assert_eq!(aggregator.calculate_median_price(tied_reordered).unwrap().source, "dex2");
+    }
+
+    #[test]
+    fn test_detect_clock_skew() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        let skews = aggregator.detect_clock_skew(
+            vec![
+                price("A", "B", "100", "dex1", 10_000),
+                price("A", "B", "100", "dex2", 4_000),
+                price("A", "B", "100", "dex2", 6_000),
+                price("A", "B", "100", "dex3", 25_000),
+            ],
+            10_000,
+        );
+
+        let sources: Vec<&str> = skews.iter().map(|s| s.source.as_str()).collect();
+        assert_eq!(sources, vec!["dex3", "dex2", "dex1"]);
+        assert_eq!(skews[0].skew_ms, 15_000.0);
+        assert_eq!(skews[1].skew_ms, -5_000.0);
+        assert_eq!(skews[1].max_abs_skew_ms, 6_000.0);
+        assert_eq!(skews[1].samples, 2);
+        assert_eq!(skews[2].skew_ms, 0.0);
+    }
+
+    #[test]
+    fn test_aggregator_stats() {
+        let aggregator = TurboAggregator::new(10_000, None);
+        let counts = |a: &TurboAggregator| {
+            let stats = a.get_stats();
+            (stats.cache_hits, stats.dedup_skips, stats.fresh_inserts)
+        };
+
+        aggregator.aggregate_prices(vec![
+            price("WETH", "USDC", "3000", "dex1", 0),
+            price("WETH", "USDC", "3000", "dex2", 0),
+        ], 0);
+        assert_eq!(counts(&aggregator), (0.0, 0.0, 2.0));
+        // Within the 5s dedup window
+        aggregator.aggregate_prices(vec![price("WETH", "USDC", "3000", "dex1", 0)], 1_000);
+        assert_eq!(counts(&aggregator), (0.0, 1.0, 2.0));
+        // Past the dedup window, within the cache timeout
+        aggregator.aggregate_prices(vec![price("WETH", "USDC", "3000", "dex1", 0)], 6_000);
+        assert_eq!(counts(&aggregator), (1.0, 1.0, 2.0));
+        // Past the cache timeout
+        aggregator.aggregate_prices(vec![price("WETH", "USDC", "3000", "dex2", 0)], 11_000);
+        assert_eq!(counts(&aggregator), (1.0, 1.0, 3.0));
+
+        aggregator.reset_stats();
+        assert_eq!(counts(&aggregator), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_median_filtered_drops_outliers() {
+        let aggregator = TurboAggregator::new(5_000, None);
+        let batch = vec![
+            price("WETH", "USDC", "3000", "dex1", 0),
+            price("WETH", "USDC", "3003", "dex2", 0),
+            price("WETH", "USDC", "30000", "dex3", 0),
+            price("WETH", "USDC", "2997", "dex4", 0),
+            price("WETH", "USDC", "3001", "dex5", 0),
+        ];
+
+        // Unfiltered, the 10x quote drags the median up to 3001
+        assert_eq!(aggregator.calculate_median_price(batch.clone()).unwrap().price, "3001");
+        let filtered = aggregator.calculate_median_price_filtered(batch, 100).unwrap();
+        assert_eq!(filtered.price, "3000.5");
+        assert_eq!(aggregator.get_stats().outliers_rejected, 1.0);
+
+        // Two prices: neither is the outlier
+        let pair = vec![price("WETH", "USDC", "3000", "dex1", 0), price("WETH", "USDC", "30000", "dex2", 0)];
+        assert_eq!(aggregator.calculate_median_price_filtered(pair, 100).unwrap().price, "16500");
+
+        // Even batch whose middle prices straddle a zero-width band
+        let spread = vec![
+            price("WETH", "USDC", "1", "a", 0),
+            price("WETH", "USDC", "2", "b", 0),
+            price("WETH", "USDC", "3", "c", 0),
+            price("WETH", "USDC", "4", "d", 0),
+        ];
+        assert!(aggregator.calculate_median_price_filtered(spread, 0).is_none());
+        assert_eq!(aggregator.get_stats().outliers_rejected, 5.0);
+        assert!(aggregator.calculate_median_price_filtered(Vec::new(), 100).is_none());
+    }
+
+    #[test]
+    fn test_calculate_spread() {
+        let aggregator = TurboAggregator::new(5_000, None);
+
+        let tight = aggregator
+            .calculate_spread(vec![
+                price("WETH", "USDC", "3000.0", "dex1", 0),
+                price("WETH", "USDC", "3001.5", "dex2", 0),
+                price("WETH", "USDC", "3000.9", "dex3", 0),
+            ])
+            .unwrap();
+        assert_eq!((tight.min.as_str(), tight.max.as_str()), ("3000.0", "3001.5"));
+        assert_eq!((tight.spread_bps, tight.source_count), (5, 3));
+
+        let wide = aggregator
+            .calculate_spread(vec![
+                price("WETH", "USDC", "2900", "dex1", 0),
+                price("WETH", "USDC", "bad", "dex2", 0),
+                price("WETH", "USDC", "3100", "dex3", 0),
+                price("WETH", "USDC", "3000", "dex1", 0),
+            ])
+            .unwrap();
+        // 200 / 2900 * 10000 = 689.66
+        assert_eq!((wide.spread_bps, wide.source_count), (690, 2));
+        assert_eq!(aggregator.get_unparsed_prices(), 1.0);
+
+        assert_eq!(aggregator.calculate_spread(vec![price("WETH", "USDC", "3000", "dex1", 0)]).unwrap().spread_bps, 0);
+        assert!(aggregator.calculate_spread(Vec::new()).is_none());
+        assert!(aggregator.calculate_spread(vec![
+            price("WETH", "USDC", "0", "dex1", 0),
+            price("WETH", "USDC", "1", "dex2", 0),
+        ]).is_none());
+    }
+
+    #[test]
+    fn test_staleness_filtering() {
+        let aggregator = TurboAggregator::new(5_000, None);
+        assert!(aggregator.set_max_price_age_ms(-1).is_err());
+        aggregator.set_max_price_age_ms(10_000).unwrap();
+
+        // A 30s-old quote is dropped before it reaches the cache
+        let kept = aggregator.aggregate_prices(vec![
+            price("WETH", "USDC", "3000", "dex1", 70_000),
+            price("WETH", "USDC", "3000", "dex2", 90_000),
+        ], 100_000);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].source, "dex2");
+        assert_eq!(aggregator.get_cache_size(), 1);
+        assert_eq!(aggregator.get_stats().rejected_stale, 1.0);
+
+        // Future-dated quotes: allowed by default, then clamped, then rejected
+        assert_eq!(aggregator.aggregate_prices(vec![
+            price("WETH", "USDC", "3000", "dex3", 105_000),
+        ], 100_000)[0].timestamp, 105_000);
+        aggregator.set_future_timestamp_policy("clamp".to_string()).unwrap();
+        assert_eq!(aggregator.aggregate_prices(vec![
+            price("WETH", "USDC", "3000", "dex4", 105_000),
+        ], 100_000)[0].timestamp, 100_000);
+        aggregator.set_future_timestamp_policy("reject".to_string()).unwrap();
+        assert!(aggregator.aggregate_prices(vec![price("WETH", "USDC", "3000", "dex5", 105_000)], 100_000).is_empty());
+        assert_eq!(aggregator.get_stats().rejected_future, 1.0);
+        assert!(aggregator.set_future_timestamp_policy("ignore".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_percentile_price() {
+        let aggregator = TurboAggregator::new(5_000, None);
+        let batch = |values: &[&str]| -> Vec<PriceData> {
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| PriceData {
+                    token_a: "WETH".to_string(),
+                    token_b: "USDC".to_string(),
+                    price: value.to_string(),
+                    source: format!("dex{}", i),
+                    timestamp: i as i64,
+                })
+                .collect()
+        };
+        let at = |values: &[&str], p: f64| {
+            let result = aggregator.calculate_percentile_price(batch(values), p).unwrap();
+            (result.price, result.source)
+        };
+
+        // Five prices: p25/p50/p75 land exactly on ranks 1, 2 and 3
+        let five = ["50", "10", "40", "20", "30"];
+        assert_eq!(at(&five, 25.0), ("20".to_string(), "dex3".to_string()));
+        assert_eq!(at(&five, 50.0), ("30".to_string(), "dex4".to_string()));
+        assert_eq!(at(&five, 75.0), ("40".to_string(), "dex2".to_string()));
+        assert_eq!(at(&five, 0.0).0, "10");
+        assert_eq!(at(&five, 100.0).0, "50");
+
+        // Four prices: ranks 0.75, 1.5 and 2.25 interpolate
+        let four = ["10", "20", "30", "40"];
+        assert_eq!(at(&four, 25.0), ("17.5".to_string(), "percentile".to_string()));
+        assert_eq!(at(&four, 50.0), ("25".to_string(), "median".to_string()));
+        assert_eq!(at(&four, 75.0), ("32.5".to_string(), "percentile".to_string()));
+        assert_eq!(aggregator.calculate_median_price(batch(&four)).unwrap().price, "25");
+
+        assert!(aggregator.calculate_percentile_price(batch(&four), 100.5).is_none());
+        assert!(aggregator.calculate_percentile_price(batch(&four), f64::NAN).is_none());
+        assert!(aggregator.calculate_percentile_price(Vec::new(), 50.0).is_none());
+    }
+
+    #[test]
+    fn test_memory_usage_counts_strings() {
+        let aggregator = TurboAggregator::new(5_000, None);
+        let empty = aggregator.get_memory_usage();
+        assert_eq!(empty, std::mem::size_of::<AHashMap<String, CachedPrice>>() as f64);
+
+        aggregator.aggregate_prices(vec![price("WETH", "USDC", "3000.25", "uniswap", 0)], 0);
+        let capacity = aggregator.price_cache.read().capacity();
+        let table = (capacity * (std::mem::size_of::<(String, CachedPrice)>() + 1)) as f64;
+        // Key "WETH-USDC-uniswap" (17) + 4 + 4 + 7 + 7
+        assert_eq!(aggregator.get_memory_usage() - empty, table + 39.0);
+
+        // A long symbol and source cost exactly their extra bytes
+        let long_token = "T".repeat(200);
+        let long_source = "s".repeat(300);
+        aggregator.aggregate_prices(vec![price(&long_token, "USDC", "3000.25", &long_source, 0)], 0);
+        assert_eq!(aggregator.price_cache.read().capacity(), capacity);
+        let second = (200 + 1 + 4 + 1 + 300) + 200 + 4 + 7 + 300;
+        assert_eq!(aggregator.get_memory_usage() - empty, table + 39.0 + second as f64);
+    }
+
+    #[test]
+    fn test_strict_median_reports_parse_failures() {
+        let aggregator = TurboAggregator::new(5_000, None);
+        let batch = vec![
+            price("WETH", "USDC", "3000", "dex1", 0),
+            price("WETH", "USDC", "30O1", "dex2", 0),
+            price("WETH", "USDC", "3002", "dex3", 0),
+            price("WETH", "USDC", "n/a", "dex4", 0),
+        ];
+
+        // Lenient: a wrong-but-successful median of the two survivors
+        assert_eq!(aggregator.calculate_median_price(batch.clone()).unwrap().price, "3001");
+
+        let err = aggregator.strict_percentile(&batch, MEDIAN_PERCENTILE).unwrap_err();
+        assert_eq!(err, PriceError::ParseFailure { source: "dex2".to_string(), raw: "30O1".to_string() });
+        assert_eq!(err.to_string(), "unparseable price '30O1' from source 'dex2'");
+        assert_eq!(aggregator.strict_percentile(&[], MEDIAN_PERCENTILE).unwrap_err(), PriceError::EmptyInput);
+
+        let clean = vec![price("WETH", "USDC", "3000", "dex1", 0), price("WETH", "USDC", "3002", "dex3", 0)];
+        assert_eq!(aggregator.calculate_median_price_strict(clean.clone()).unwrap().price, "3001");
+        assert_eq!(aggregator.calculate_percentile_price_strict(clean.clone(), 100.0).unwrap().price, "3002");
+        let err = aggregator.calculate_percentile_price_strict(clean, 101.0).err().unwrap();
+        assert_eq!(err.reason, "invalid percentile: must be within [0, 100], got 101");
+    }
+
+    #[test]
+    fn test_twap() {
+        let aggregator = TurboAggregator::new(5_000, None);
+        let record = |price: &str, timestamp: i64| {
+            aggregator
+                .record_price(PriceData {
+                    token_a: "WETH".to_string(),
+                    token_b: "USDC".to_string(),
+                    price: price.to_string(),
+                    source: "dex1".to_string(),
+                    timestamp,
+                })
+                .unwrap()
+        };
+        let twap = |window_ms: i64, now_ms: i64| {
+            aggregator
+                .calculate_twap("WETH".to_string(), "USDC".to_string(), window_ms, now_ms)
+                .map(|p| p.price)
+        };
+
+        record("100", 1_000);
+        record("130", 4_000); // out of order
+        record("110", 2_000);
+        // 100 for 1s, 110 for 2s, 130 for 1s: (100 + 220 + 130) / 4 = 112.5
+        assert_eq!(twap(10_000, 5_000).as_deref(), Some("112.5"));
+        // Window from 3000: 110 carries in for 1s, then 130 for 1s
+        assert_eq!(twap(2_000, 5_000).as_deref(), Some("120"));
+        // Window from 1500: 100 for 0.5s, 110 for 2s, 130 for 0.5s
+        assert_eq!(twap(3_000, 4_500).as_deref(), Some("111.66666666666666666666666667"));
+        // Reads leave the samples alone
+        assert_eq!(twap(10_000, 5_000).as_deref(), Some("112.5"));
+
+        assert_eq!(twap(0, 4_000).as_deref(), Some("130"));
+        assert_eq!(twap(0, 3_000).as_deref(), Some("110"));
+        // A window with no samples of its own still has a price in effect
+        assert_eq!(twap(100, 10_000).as_deref(), Some("130"));
+        assert!(twap(100, 500).is_none());
+        assert!(aggregator.calculate_twap("A".to_string(), "B".to_string(), 1_000, 0).is_none());
+        assert!(aggregator
+            .record_price(PriceData {
+                token_a: "WETH".to_string(),
+                token_b: "USDC".to_string(),
+                price: "abc".to_string(),
+                source: "dex1".to_string(),
+                timestamp: 0,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_price_with_confidence() {
+        let aggregator = TurboAggregator::new(5_000, None);
+        let quotes = |prices: &[(&str, &str)]| -> Vec<PriceData> {
+            prices
+                .iter()
+                .map(|(source, price)| PriceData {
+                    token_a: "WETH".to_string(),
+                    token_b: "USDC".to_string(),
+                    price: price.to_string(),
+                    source: source.to_string(),
+                    timestamp: 0,
+                })
+                .collect()
+        };
+
+        // stddev 0.0816 on a mean of 100: 1 - 0.000816 / 0.1
+        let tight = aggregator
+            .calculate_price_with_confidence(quotes(&[("a", "99.9"), ("b", "100"), ("c", "100.1")]))
+            .unwrap();
+        assert_eq!(tight.price, "100");
+        assert_eq!(tight.source_count, 3);
+        assert!((tight.confidence - 0.991835).abs() < 1e-6);
+
+        // stddev 8.165 on a mean of 100: 1 - 0.08165 / 0.1
+        let dispersed = aggregator
+            .calculate_price_with_confidence(quotes(&[("a", "90"), ("b", "100"), ("c", "110")]))
+            .unwrap();
+        assert!((dispersed.confidence - 0.183503).abs() < 1e-6);
+        assert!(dispersed.confidence < tight.confidence);
+
+        let wild = aggregator.calculate_price_with_confidence(quotes(&[("a", "50"), ("b", "150")])).unwrap();
+        assert_eq!((wild.price.as_str(), wild.confidence), ("100", 0.0));
+
+        // Agreement needs two sources
+        let single = aggregator.calculate_price_with_confidence(quotes(&[("a", "100"), ("a", "100")])).unwrap();
+        assert_eq!((single.confidence, single.source_count), (0.0, 1));
+
+        assert!(aggregator.calculate_price_with_confidence(quotes(&[("a", "abc")])).is_none());
+    }
+
+    #[test]
+    fn test_runtime_windows() {
+        let _modes = crate::ModeGuard::acquire();
+        let aggregator = TurboAggregator::new(60_000, None);
+        let quote = |price: &str| PriceData {
+            token_a: "WETH".to_string(),
+            token_b: "USDC".to_string(),
+            price: price.to_string(),
+            source: "dex1".to_string(),
+            timestamp: 0,
+        };
+
+        assert_eq!(aggregator.get_dedup_window_ms(), 5_000);
+        aggregator.aggregate_prices(vec![quote("100")], 10_000);
+        // 2s later: inside the default window
+        assert!(aggregator.aggregate_prices(vec![quote("101")], 12_000).is_empty());
+
+        // A 1s window lets the same age through (as a cache hit)
+        aggregator.set_dedup_window_ms(1_000).unwrap();
+        assert_eq!(aggregator.aggregate_prices(vec![quote("101")], 12_000).len(), 1);
+
+        // Widening it dedups again
+        aggregator.set_dedup_window_ms(3_000).unwrap();
+        assert!(aggregator.aggregate_prices(vec![quote("101")], 12_000).is_empty());
+        assert!(aggregator.set_dedup_window_ms(-1).is_err());
+        assert_eq!(aggregator.get_dedup_window_ms(), 3_000);
+
+        // The timeout is rescaled from the new value
+        aggregator.set_cache_timeout_ms(20_000).unwrap();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 20_000);
+        crate::set_lightweight_mode(true);
+        aggregator.reconfigure();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 10_000);
+        aggregator.set_cache_timeout_ms(8_000).unwrap();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 4_000);
+        // Only reconfigure picks up a mode change
+        crate::set_lightweight_mode(false);
+        aggregator.set_cache_timeout_ms(8_000).unwrap();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 4_000);
+        aggregator.reconfigure();
+        assert_eq!(aggregator.get_cache_timeout_ms(), 8_000);
+        assert!(aggregator.set_cache_timeout_ms(-5).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let aggregator = TurboAggregator::new(10_000, None);
+        let quote = |token_a: &str, source: &str, price: &str| PriceData {
+            token_a: token_a.to_string(),
+            token_b: "USDC".to_string(),
+            price: price.to_string(),
+            source: source.to_string(),
+            timestamp: 0,
+        };
+        aggregator.aggregate_prices(vec![quote("WETH", "dex1", "3000"), quote("WBTC", "dex1", "60000")], 1_000);
+        aggregator.aggregate_prices(vec![quote("WETH", "dex2", "3001")], 8_000);
+
+        // dex1's entries are 10s old at 11_000
+        assert_eq!(aggregator.snapshot(None).len(), 3);
+        let live = aggregator.snapshot(Some(11_000));
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].source, "dex2");
+
+        let snapshot = aggregator.snapshot(None);
+        let warmed = TurboAggregator::new(10_000, None);
+        warmed.load_snapshot(snapshot.clone(), 20_000);
+        assert_eq!(warmed.get_cache_size(), 3);
+        assert_eq!(warmed.get_stats().fresh_inserts, 0.0);
+        let prices: Vec<String> = warmed.snapshot(Some(20_000)).into_iter().map(|p| p.price).collect();
+        let expected: Vec<String> = snapshot.into_iter().map(|p| p.price).collect();
+        assert_eq!(prices, expected);
+
+        // Loaded entries are cache hits, not fresh inserts
+        assert_eq!(warmed.aggregate_prices(vec![quote("WETH", "dex1", "2999")], 26_000)[0].price, "3000");
+    }
+
+    #[test]
+    fn test_geometric_mean() {
+        let aggregator = TurboAggregator::new(5_000, None);
+        let quotes = |prices: &[&str]| -> Vec<PriceData> {
+            prices
+                .iter()
+                .enumerate()
+                .map(|(i, price)| PriceData {
+                    token_a: "WETH".to_string(),
+                    token_b: "USDC".to_string(),
+                    price: price.to_string(),
+                    source: format!("dex{}", i),
+                    timestamp: i as i64,
+                })
+                .collect()
+        };
+
+        // Skewed: the arithmetic mean (37) is dragged up by the 100 quote
+        let skewed = quotes(&["1", "10", "100"]);
+        let geomean = aggregator.calculate_geometric_mean(skewed.clone()).unwrap();
+        assert_eq!(geomean.price, "10");
+        assert_eq!((geomean.source.as_str(), geomean.timestamp), ("geomean", 2));
+        let equal_weights = vec!["1".to_string(); 3];
+        assert_eq!(aggregator.calculate_vwap(skewed, equal_weights).unwrap().price, "37");
+
+        assert_eq!(aggregator.calculate_geometric_mean(quotes(&["4", "9"])).unwrap().price, "6");
+        assert_eq!(aggregator.calculate_geometric_mean(quotes(&["2", "abc", "8"])).unwrap().price, "4");
+
+        assert!(aggregator.calculate_geometric_mean(quotes(&["4", "0"])).is_none());
+        assert!(aggregator.calculate_geometric_mean(quotes(&["4", "-1"])).is_none());
+        assert!(aggregator.calculate_geometric_mean(vec![]).is_none());
+    }
+
+    #[test]
+    fn test_canonicalize_pairs() {
+        let aggregator = TurboAggregator::new(10_000, None);
+        aggregator.set_canonicalize_pairs(true);
+        let quote = |token_a: &str, token_b: &str, price: &str, source: &str| PriceData {
+            token_a: token_a.to_string(),
+            token_b: token_b.to_string(),
+            price: price.to_string(),
+            source: source.to_string(),
+            timestamp: 0,
+        };
+
+        let aggregated = aggregator.aggregate_prices(
+            vec![
+                quote("WETH", "USDC", "2000", "dex1"),
+                quote("USDC", "WETH", "0.0005", "dex1"),
+                quote("WETH", "USDC", "2500", "dex2"),
+                quote("WETH", "USDC", "0", "dex3"),
+            ],
+            1_000,
+        );
+        // USDC < WETH: dex1's flipped quote and its canonical repeat share an entry
+        assert_eq!(aggregator.get_cache_size(), 2);
+        assert_eq!(aggregator.get_stats().dedup_skips, 1.0);
+        assert_eq!(aggregator.get_unparsed_prices(), 1.0);
+        let quotes: Vec<(&str, &str, &str)> =
+            aggregated.iter().map(|p| (p.token_a.as_str(), p.token_b.as_str(), p.price.as_str())).collect();
+        assert_eq!(quotes, vec![("USDC", "WETH", "0.0005"), ("USDC", "WETH", "0.0004")]);
+        assert_eq!(aggregator.calculate_median_price(aggregated).unwrap().price, "0.00045");
+
+        // Off, the orientations stay separate
+        let plain = TurboAggregator::new(10_000, None);
+        plain.aggregate_prices(vec![quote("USDC", "WETH", "0.0005", "dex1"), quote("WETH", "USDC", "2000", "dex1")], 1_000);
+        assert_eq!(plain.get_cache_size(), 2);
+    }
+
+    #[test]
+    fn test_background_gc() {
+        let _modes = crate::ModeGuard::acquire();
+        let aggregator = TurboAggregator::new(1_000, None);
+        let quote = |source: &str| PriceData {
+            token_a: "WETH".to_string(),
+            token_b: "USDC".to_string(),
+            price: "3000".to_string(),
+            source: source.to_string(),
+            timestamp: 0,
+        };
+        let now = crate::now_ms();
+        aggregator.aggregate_prices(vec![quote("dex1")], now - 5_000);
+        aggregator.aggregate_prices(vec![quote("dex2")], now + 60_000);
+        assert_eq!(aggregator.get_cache_size(), 2);
+
+        assert!(aggregator.start_gc(0).is_err());
+        aggregator.start_gc(10).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while aggregator.get_cache_size() > 1 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // Only the expired entry went, without an aggregate_prices call
+        assert!(!aggregator.contains("WETH".to_string(), "USDC".to_string(), "dex1".to_string()));
+        assert!(aggregator.contains("WETH".to_string(), "USDC".to_string(), "dex2".to_string()));
+        assert!(aggregator.stop_gc());
+        assert!(!aggregator.stop_gc());
+
+        // Dropping a collecting aggregator ends its thread quietly
+        aggregator.start_gc(10).unwrap();
+        drop(aggregator);
+
+        crate::set_deterministic(true);
+        assert!(TurboAggregator::new(1_000, None).start_gc(10).is_err());
+        crate::set_deterministic(false);
+    }
+
+    #[test]
+    fn test_aggregate_grouped_three_pairs() {
+        let aggregator = TurboAggregator::new(10000, None);
+
+        // Interleaved, and WETH appears against two quote tokens
+        let groups = aggregator.aggregate_grouped(
+            vec![
+                price("WETH", "USDC", "3000", "dex1", 1000),
+                price("WBTC", "USDC", "60000", "dex1", 1000),
+                price("WETH", "DAI", "2990", "dex1", 1000),
+                price("WETH", "USDC", "3030", "dex2", 1000),
+                price("WBTC", "USDC", "60100", "dex2", 1000),
+                price("WETH", "USDC", "3010", "dex3", 1000),
+                price("WBTC", "USDC", "59800", "dex3", 1000),
+                price("WETH", "DAI", "3000", "dex2", 1000),
+            ],
+            1000,
+        );
+
+        let summary: Vec<(&str, &str, u32, &str)> = groups
+            .iter()
+            .map(|g| (g.token_a.as_str(), g.token_b.as_str(), g.count, g.median.as_ref().unwrap().price.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("WETH", "USDC", 3, "3010"), ("WBTC", "USDC", 3, "60000"), ("WETH", "DAI", 2, "2995")]
+        );
+        assert!(groups.iter().all(|g| g.prices.iter().all(|p| p.token_a == g.token_a && p.token_b == g.token_b)));
+
+        // Within the dedup window a repeated pair comes back empty
+        let groups = aggregator.aggregate_grouped(vec![price("WETH", "DAI", "3000", "dex2", 1000)], 2000);
+        assert_eq!(groups[0].count, 0);
+    }
+
+    #[test]
+    fn test_age_ms_saturates() {
+        assert_eq!(age_ms(1_500, 1_000), 500);
+        assert_eq!(age_ms(1_000, 1_500), 0);
+        assert_eq!(age_ms(i64::MAX, i64::MIN), i64::MAX);
+        assert_eq!(age_ms(i64::MIN, i64::MAX), 0);
+    }
+
+    #[test]
+    fn test_clock_going_backwards_keeps_dedup() {
+        let aggregator = TurboAggregator::new(10_000, None);
+        assert_eq!(aggregator.aggregate_prices(vec![price("WETH", "USDC", "3000", "dex1", 0)], 100_000).len(), 1);
+
+        // Earlier than the cached stamp: still a duplicate, and not evicted
+        assert!(aggregator.aggregate_prices(vec![price("WETH", "USDC", "3001", "dex1", 0)], 50_000).is_empty());
+        assert!(aggregator.aggregate_prices(vec![price("WETH", "USDC", "3001", "dex1", 0)], i64::MIN).is_empty());
+        aggregator.evict_old_entries(&mut aggregator.price_cache.write(), 50_000);
+        assert_eq!(aggregator.get_cache_size(), 1);
+        assert_eq!(aggregator.snapshot(Some(50_000)).len(), 1);
+        assert_eq!(aggregator.get_stats().dedup_skips, 2.0);
+
+        // The windows run from the cached stamp once the clock catches up
+        assert!(aggregator.aggregate_prices(vec![price("WETH", "USDC", "3001", "dex1", 0)], 104_999).is_empty());
+        let served = aggregator.aggregate_prices(vec![price("WETH", "USDC", "3001", "dex1", 0)], 105_000);
+        assert_eq!(served[0].price, "3000");
+        assert_eq!(aggregator.aggregate_prices(vec![
+            price("WETH", "USDC", "3002", "dex1", 0),
+        ], 110_000)[0].price, "3002");
+    }
+
+    #[test]
+    fn test_trimmed_mean() {
+        let aggregator = TurboAggregator::new(10000, None);
+        let prices: Vec<PriceData> = ["1000", "10", "17", "11", "16", "12", "50", "15", "13", "14", "abc"]
+            .iter()
+            .enumerate()
+            .map(|(i, value)| PriceData {
+                token_a: "WETH".to_string(),
+                token_b: "USDC".to_string(),
+                price: value.to_string(),
+                source: format!("dex{}", i),
+                timestamp: i as i64,
+            })
+            .collect();
+        let trimmed = |pct: f64| aggregator.calculate_trimmed_mean(prices.clone(), pct).map(|p| p.price);
+
+        // 10 parseable: 1158 / 10
+        assert_eq!(trimmed(0.0).unwrap(), "115.8");
+        // 10% drops 10 and 1000: (11 + .. + 17 + 50) / 8
+        assert_eq!(trimmed(10.0).unwrap(), "18.5");
+        // 20% also drops 11 and 50: (12 + .. + 17) / 6
+        assert_eq!(trimmed(20.0).unwrap(), "14.5");
+        // Just under 50 leaves the middle pair, matching the median
+        assert_eq!(trimmed(49.9), aggregator.calculate_median_price(prices.clone()).map(|p| p.price));
+
+        let result = aggregator.calculate_trimmed_mean(prices.clone(), 20.0).unwrap();
+        assert_eq!((result.source.as_str(), result.timestamp), ("trimmed_mean", 9));
+
+        assert!(trimmed(50.0).is_none());
+        assert!(trimmed(-1.0).is_none());
+        assert!(trimmed(f64::NAN).is_none());
+        assert!(aggregator.calculate_trimmed_mean(Vec::new(), 10.0).is_none());
+    }
+
+    #[test]
+    fn test_max_entries_evicts_oldest() {
+        let aggregator = TurboAggregator::new(5_000, Some(3));
+        for (i, token) in ["T0", "T1", "T2", "T3", "T4"].iter().enumerate() {
+            aggregator.aggregate_prices(vec![price(token, "USDC", "100", "dex1", 0)], i as i64 * 1_000);
+        }
+        assert_eq!(aggregator.get_cache_size(), 3);
+        assert_eq!(aggregator.get_stats().capacity_evictions, 2.0);
+        let cached: Vec<String> = aggregator.snapshot(None).into_iter().map(|p| p.token_a).collect();
+        assert_eq!(cached, vec!["T2", "T3", "T4"]);
+
+        // Refreshing an expired pair replaces it in place, and then it is the newest
+        aggregator.aggregate_prices(vec![price("T2", "USDC", "101", "dex1", 0)], 10_000);
+        assert_eq!(aggregator.get_stats().capacity_evictions, 2.0);
+        aggregator.aggregate_prices(vec![price("T5", "USDC", "100", "dex1", 0)], 11_000);
+        let cached: Vec<String> = aggregator.snapshot(None).into_iter().map(|p| p.token_a).collect();
+        assert_eq!(cached, vec!["T2", "T4", "T5"]);
+
+        // Snapshots loaded past capacity keep the last entries
+        let restored = TurboAggregator::new(60_000, Some(2));
+        restored.load_snapshot(aggregator.snapshot(None), 0);
+        assert_eq!(restored.get_cache_size(), 2);
+        assert_eq!(restored.get_stats().capacity_evictions, 1.0);
+
+        // Filling a full cache stays in order across many replacements
+        let churn = TurboAggregator::new(60_000, Some(100));
+        for i in 0..10_000 {
+            churn.aggregate_prices(vec![price(&format!("T{}", i % 150), "USDC", "100", "dex1", 0)], i * 100_000);
+        }
+        let mut cached: Vec<String> = churn.snapshot(None).into_iter().map(|p| p.token_a).collect();
+        let mut newest: Vec<String> = (9_900..10_000).map(|i| format!("T{}", i % 150)).collect();
+        cached.sort();
+        newest.sort();
+        assert_eq!(cached, newest);
+        assert!(churn.cache_order.read().len() <= 200);
+
+        // 0 is unbounded
+        let unbounded = TurboAggregator::new(60_000, Some(0));
+        for i in 0..50 {
+            unbounded.aggregate_prices(vec![price(&format!("T{}", i), "USDC", "100", "dex1", 0)], i);
+        }
+        assert_eq!(unbounded.get_cache_size(), 50);
+        assert_eq!(unbounded.get_stats().capacity_evictions, 0.0);
+    }
+
+    #[test]
+    fn test_find_triangular() {
+        let aggregator = TurboAggregator::new(60_000, None);
+        // WETH -> USDC -> WBTC -> WETH: 2500 * (1 / 50000) * 20.2 = 1.01
+        aggregator.aggregate_prices(
+            vec![
+                price("WETH", "USDC", "2500", "dex1", 900),
+                price("WETH", "USDC", "2490", "dex2", 900),
+                price("WBTC", "USDC", "50000", "dex1", 900),
+                price("WBTC", "WETH", "20.2", "dex3", 900),
+            ],
+            1_000,
+        );
+
+        let tokens = vec!["WETH".to_string(), "USDC".to_string(), "WBTC".to_string(), "DAI".to_string()];
+        let found = aggregator.find_triangular(tokens.clone(), 2_000);
+        assert_eq!(found.len(), 1);
+        let cycle = &found[0];
+        assert_eq!(cycle.path, vec!["WETH", "USDC", "WBTC", "WETH"]);
+        assert_eq!(cycle.dexes, vec!["dex1", "dex1", "dex3"]);
+        assert_eq!((cycle.output_amount.as_str(), cycle.profit.as_str()), ("1.01", "0.01"));
+        assert_eq!((cycle.profit_bps, cycle.timestamp, cycle.price_timestamp), (100, 2_000, Some(900)));
+
+        // Threshold is strict, and expired legs or missing legs yield nothing
+        aggregator.set_triangular_min_profit_bps(100);
+        assert!(aggregator.find_triangular(tokens.clone(), 2_000).is_empty());
+        aggregator.set_triangular_min_profit_bps(99);
+        assert_eq!(aggregator.find_triangular(tokens.clone(), 2_000).len(), 1);
+        assert!(aggregator.find_triangular(tokens.clone(), 61_000).is_empty());
+        assert!(aggregator.find_triangular(tokens[..2].to_vec(), 2_000).is_empty());
     }
 }
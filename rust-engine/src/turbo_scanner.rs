@@ -1,124 +1,3117 @@
 // Turbo Engine #1: High-Performance Opportunity Scanner
 // Optimized for ARM with SIMD-friendly operations
 
+use napi::threadsafe_function::{ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsFunction};
 use napi_derive::napi;
-use ahash::AHashSet;
-use parking_lot::RwLock;
+use ahash::{AHashMap, AHashSet};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::collections::{BinaryHeap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 use std::sync::Arc;
-use crate::{Opportunity, is_lightweight_mode};
+use std::cmp::{Ordering, Reverse};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use crate::{
+    Error, Opportunity, SELF_CHECK_FUTURE_TOLERANCE_MS, hash_state, is_deterministic, is_lightweight_mode, now_ms,
+    parse_decimal, parse_price, wall_clock_ms,
+};
+use crate::seen_cache::SeenCache;
+use crate::timing::CallTimer;
+use crate::conversions::{apply_bps_decimal, ratio_to_bps, BPS_SCALE};
+
+/// Dedup capacity when the constructor is given none, outside lightweight mode
+const DEFAULT_SEEN_LIMIT: usize = 100_000;
+
+/// Dedup capacity in lightweight mode (and its cap on an explicit capacity)
+const LIGHTWEIGHT_SEEN_LIMIT: usize = 1000;
+
+/// Lowest accepted `min_profit_bps`: a route can't lose more than 100%
+const MIN_PROFIT_BPS_FLOOR: i32 = -10000;
+
+/// Trailing window used by `get_throughput`
+const THROUGHPUT_WINDOW_MS: i64 = 10_000;
+
+/// Longest window `scan_rate` can answer for
+const SCAN_RATE_MAX_WINDOW_MS: i64 = 60_000;
+
+/// How far back the batch ring reaches; older entries are pruned as new
+/// batches are recorded
+const THROUGHPUT_RETENTION_MS: i64 = if SCAN_RATE_MAX_WINDOW_MS > THROUGHPUT_WINDOW_MS {
+    SCAN_RATE_MAX_WINDOW_MS
+} else {
+    THROUGHPUT_WINDOW_MS
+};
+
+/// Batches stamped in the same millisecond share a ring entry, so in-order
+/// stamps never need more than one entry per retained millisecond; only
+/// out-of-order stamps can reach this and push out the oldest entries
+const THROUGHPUT_RING_CAPACITY: usize = THROUGHPUT_RETENTION_MS as usize;
+
+/// Default age at which a price's confidence has halved
+const DEFAULT_CONFIDENCE_HALF_LIFE_MS: f64 = 5_000.0;
+
+/// Default number of opportunities retained by `push_opportunities`
+const DEFAULT_TOP_CAPACITY: usize = 100;
+
+/// Size at which the per-route emit log is pruned of entries past the interval
+const EMIT_LOG_PRUNE_SIZE: usize = 4096;
+
+/// Price-impact curve used to discount `profit_bps` by trade size.
+///
+/// `size` is the opportunity's `input_amount` parsed as a float; an
+/// unparseable amount is treated as zero size (no discount).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImpactModel {
+    /// `impact_bps = bps_per_unit * size`
+    Linear { bps_per_unit: f64 },
+    /// `impact_bps = k * sqrt(size)` - the square-root law observed on order books
+    SqrtK { k: f64 },
+    /// `impact_bps = 10000 * size / (reserve + size)` - x*y=k pool with `reserve` on the input side
+    ConstantProduct { reserve: f64 },
+}
+
+impl Default for ImpactModel {
+    fn default() -> Self {
+        // Zero slope keeps the historical behavior: profit_bps is used as-is
+        ImpactModel::Linear { bps_per_unit: 0.0 }
+    }
+}
+
+impl ImpactModel {
+    /// Price impact in bps for a trade of `size` input units
+    pub fn impact_bps(&self, size: f64) -> f64 {
+        if !size.is_finite() || size <= 0.0 {
+            return 0.0;
+        }
+
+        let impact = match *self {
+            ImpactModel::Linear { bps_per_unit } => bps_per_unit * size,
+            ImpactModel::SqrtK { k } => k * size.sqrt(),
+            ImpactModel::ConstantProduct { reserve } => {
+                if reserve <= 0.0 {
+                    return BPS_SCALE;
+                }
+                BPS_SCALE * size / (reserve + size)
+            }
+        };
+
+        if impact.is_finite() { impact.max(0.0) } else { 0.0 }
+    }
+}
+
+/// Why the filter accepted or rejected an opportunity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterReason {
+    Accepted,
+    BelowThreshold,
+    Duplicate,
+    Throttled,
+    MissingRequiredToken,
+    LowConfidence,
+    Executed,
+    HopMismatch,
+    OpenCycle,
+    InvalidAmounts,
+    BelowAbsoluteThreshold,
+}
+
+impl FilterReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            FilterReason::Accepted => "accepted",
+            FilterReason::BelowThreshold => "below_threshold",
+            FilterReason::Duplicate => "duplicate",
+            FilterReason::Throttled => "throttled",
+            FilterReason::MissingRequiredToken => "missing_required_token",
+            FilterReason::LowConfidence => "low_confidence",
+            FilterReason::Executed => "executed",
+            FilterReason::HopMismatch => "hop_mismatch",
+            FilterReason::OpenCycle => "open_cycle",
+            FilterReason::InvalidAmounts => "invalid_amounts",
+            FilterReason::BelowAbsoluteThreshold => "below_absolute_threshold",
+        }
+    }
+}
+
+/// Dedup activity reported to the `on_event` callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScanEventKind {
+    /// A recorded route was pushed out of the dedup set by capacity
+    Eviction,
+    /// An opportunity was rejected as already seen
+    Duplicate,
+}
+
+impl ScanEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ScanEventKind::Eviction => "eviction",
+            ScanEventKind::Duplicate => "duplicate",
+        }
+    }
+}
+
+/// Payload passed to the `on_event` callback
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanEvent {
+    /// "eviction" or "duplicate"
+    pub kind: String,
+    /// Dedup key of the evicted or rejected route
+    pub key: String,
+}
+
+impl ScanEvent {
+    fn new(kind: ScanEventKind, key: String) -> Self {
+        Self { kind: kind.as_str().to_string(), key }
+    }
+}
+
+/// Where the filter takes an opportunity's gross `profit_bps` from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ProfitBpsSource {
+    /// The supplied `profit_bps` field
+    #[default]
+    Supplied,
+    /// Recomputed from the amounts; `overwrite` also replaces the field
+    Recomputed { overwrite: bool },
+}
+
+/// Route shape checks applied by `filter_opportunities_validated`
+#[derive(Debug, Clone, Copy)]
+struct RouteRules {
+    /// Require `path` to end where it starts
+    require_cycle: bool,
+}
+
+/// Per-call settings for the core filter loop
+#[derive(Debug, Clone, Copy, Default)]
+struct FilterOptions {
+    rules: Option<RouteRules>,
+    /// Only read-lock dedup/throttle state, record nothing and skip timing
+    dry_run: bool,
+    /// Slippage cutting `output_amount` when `profit_bps` is recomputed
+    /// from the amounts, see `filter_opportunities_slippage`
+    slippage_bps: i32,
+}
+
+impl RouteRules {
+    fn check(self, opp: &Opportunity) -> Option<FilterReason> {
+        if opp.path.len() != opp.dexes.len() + 1 {
+            return Some(FilterReason::HopMismatch);
+        }
+        if self.require_cycle && opp.path.first() != opp.path.last() {
+            return Some(FilterReason::OpenCycle);
+        }
+        None
+    }
+}
+
+/// Receiver of scan events; must not block
+type EventSink = Arc<dyn Fn(ScanEvent) + Send + Sync>;
+
+/// Per-opportunity result of `filter_opportunities_explained`
+#[napi(object)]
+pub struct FilterOutcome {
+    pub accepted: bool,
+    /// "accepted", "below_threshold", "duplicate", "throttled",
+    /// "missing_required_token", "low_confidence" or "executed"; also
+    /// "hop_mismatch" and "open_cycle" from `filter_opportunities_validated`,
+    /// and "invalid_amounts" under `set_recompute_profit_bps`
+    pub reason: String,
+    /// Profit after costs; negative values are reported as-is
+    pub net_profit_bps: i32,
+}
+
+/// Heap entry for the cross-batch top-N queue, ordered by `profit_bps` and
+/// then by arrival so that, among equal profits, the newest is the smallest
+#[derive(Debug)]
+struct RankedEntry {
+    profit_bps: i32,
+    seq: u64,
+    opp: Opportunity,
+}
+
+impl PartialEq for RankedEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RankedEntry {}
+
+impl PartialOrd for RankedEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RankedEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.profit_bps
+            .cmp(&other.profit_bps)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Bounded min-heap holding the best opportunities across batches
+#[derive(Debug)]
+struct TopQueue {
+    capacity: usize,
+    next_seq: u64,
+    heap: BinaryHeap<Reverse<RankedEntry>>,
+}
+
+impl Default for TopQueue {
+    fn default() -> Self {
+        Self { capacity: DEFAULT_TOP_CAPACITY, next_seq: 0, heap: BinaryHeap::new() }
+    }
+}
+
+/// State of the batch being fed through `push_chunk`
+#[derive(Debug)]
+struct StreamedBatch {
+    now_ms: i64,
+    received: usize,
+    accepted: Vec<Opportunity>,
+}
+
+/// Deterministic sampler deciding which opportunities go through dedup.
+///
+/// Counts items and samples the `n`th whenever `n * rate` crosses an
+/// integer, so exactly `rate` of the stream is checked without an RNG.
+/// Lock-free: concurrent filter calls share the count.
+#[derive(Debug)]
+struct DedupSampler {
+    /// `f64` bits of the rate
+    rate: AtomicU64,
+    items: AtomicU64,
+}
+
+impl Default for DedupSampler {
+    fn default() -> Self {
+        Self { rate: AtomicU64::new(1.0f64.to_bits()), items: AtomicU64::new(0) }
+    }
+}
+
+impl DedupSampler {
+    fn rate(&self) -> f64 {
+        f64::from_bits(self.rate.load(AtomicOrdering::Relaxed))
+    }
+
+    /// Change the rate and restart the count
+    fn set_rate(&self, rate: f64) {
+        self.rate.store(rate.to_bits(), AtomicOrdering::Relaxed);
+        self.items.store(0, AtomicOrdering::Relaxed);
+    }
+
+    fn should_check(&self) -> bool {
+        let rate = self.rate();
+        if rate >= 1.0 {
+            return true;
+        }
+        let n = self.items.fetch_add(1, AtomicOrdering::Relaxed) + 1;
+        (n as f64 * rate).floor() > ((n - 1) as f64 * rate).floor()
+    }
+}
+
+/// Filter state locked for reading (dry runs) or for writing
+enum StateGuard<'a, T> {
+    Read(RwLockReadGuard<'a, T>),
+    Write(RwLockWriteGuard<'a, T>),
+}
+
+impl<'a, T> StateGuard<'a, T> {
+    fn lock(lock: &'a RwLock<T>, read_only: bool) -> Self {
+        if read_only {
+            Self::Read(lock.read())
+        } else {
+            Self::Write(lock.write())
+        }
+    }
+
+    /// The state to update, or `None` when locked read-only
+    fn get_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Self::Read(_) => None,
+            Self::Write(guard) => Some(guard),
+        }
+    }
+}
+
+impl<T> std::ops::Deref for StateGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            Self::Read(guard) => guard,
+            Self::Write(guard) => guard,
+        }
+    }
+}
+
+/// Route identity shared by dedup and `diff_opportunities`: `path|dexes`
+fn route_key(opp: &Opportunity, lowercase: bool) -> String {
+    // Use efficient string concatenation for ARM
+    let mut key = String::with_capacity(128);
+    key.push_str(&opp.path.join("-"));
+    key.push('|');
+    key.push_str(&opp.dexes.join("-"));
+
+    if lowercase {
+        key.make_ascii_lowercase();
+    }
+    key
+}
+
+/// What the dedup key of an opportunity is built from. Fixed at
+/// construction, since keys already recorded would not match a new scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyStrategy {
+    /// The token path alone: the same route on other DEXes is a duplicate
+    PathOnly,
+    /// Path and DEXes (the historical key)
+    #[default]
+    PathAndDexes,
+    /// Path and DEXes plus the bucket `timestamp / bucket_ms` (floored), so a
+    /// route re-fires once its `timestamp` crosses into a new bucket. A
+    /// non-positive `bucket_ms` is treated as 1.
+    PathDexesTimeBucket { bucket_ms: i64 },
+}
+
+/// Amount that profit in bps is measured against when the scanner derives
+/// bps itself (recomputed, slippage-adjusted and net-of-gas profit); a
+/// supplied `profit_bps` is used as given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BpsBasis {
+    /// `profit / input_amount * 10000`
+    #[default]
+    Input,
+    /// `profit / output_amount * 10000`, for pairs quoting profit against the output leg
+    Output,
+}
+
+/// Width of the `input_amount` buckets appended to dedup keys when amounts
+/// are included: each bucket spans a factor of 1.05 (5%)
+const AMOUNT_BUCKET_RATIO: f64 = 1.05;
+
+/// Log-scale bucket for `input_amount`, so sizes within the same 5% band
+/// share a key. Non-positive or unparseable amounts key on the raw string.
+fn amount_bucket(input_amount: &str) -> String {
+    match parse_price(input_amount).filter(|v| *v > 0.0) {
+        Some(amount) => ((amount.ln() / AMOUNT_BUCKET_RATIO.ln()).floor() as i64).to_string(),
+        None => input_amount.trim().to_string(),
+    }
+}
+
+/// Churn between two ticks, see `diff_opportunities`
+#[napi(object)]
+pub struct OpportunityDiff {
+    /// Routes in `curr` but not `prev`, in `curr` order
+    pub added: Vec<Opportunity>,
+    /// Routes in `prev` but not `curr`, in `prev` order
+    pub removed: Vec<Opportunity>,
+    /// Routes in both whose profit moved, as they appear in `curr`
+    pub changed: Vec<Opportunity>,
+}
+
+/// Compare two opportunity sets by route key (`path` and `dexes`, case
+/// sensitive). A route is "changed" when present in both sets with a
+/// different `profit_bps` or `profit` string; other fields are ignored.
+/// When a route repeats within a set only its first occurrence is used.
+#[napi]
+pub fn diff_opportunities(prev: Vec<Opportunity>, curr: Vec<Opportunity>) -> OpportunityDiff {
+    let first_by_key = |opps: Vec<Opportunity>| {
+        let mut index: AHashMap<String, usize> = AHashMap::with_capacity(opps.len());
+        let mut unique = Vec::with_capacity(opps.len());
+        for opp in opps {
+            let key = route_key(&opp, false);
+            if !index.contains_key(&key) {
+                index.insert(key.clone(), unique.len());
+                unique.push((key, opp));
+            }
+        }
+        (index, unique)
+    };
+    let (prev_index, prev) = first_by_key(prev);
+    let (curr_index, curr) = first_by_key(curr);
+
+    let mut diff = OpportunityDiff { added: Vec::new(), removed: Vec::new(), changed: Vec::new() };
+    for (key, opp) in curr {
+        match prev_index.get(&key) {
+            None => diff.added.push(opp),
+            Some(&i) => {
+                let old = &prev[i].1;
+                if old.profit_bps != opp.profit_bps || old.profit != opp.profit {
+                    diff.changed.push(opp);
+                }
+            }
+        }
+    }
+    diff.removed = prev
+        .into_iter()
+        .filter(|(key, _)| !curr_index.contains_key(key))
+        .map(|(_, opp)| opp)
+        .collect();
+    diff
+}
+
+/// Per-DEX swap fees charged along a route, see `set_dex_fee_bps`
+#[derive(Debug, Default)]
+struct DexFeeSchedule {
+    fees: AHashMap<String, i32>,
+    /// Fee for DEXes missing from `fees`
+    default_bps: i32,
+}
+
+impl DexFeeSchedule {
+    /// Sum of the fees of every hop, saturating
+    fn route_fee_bps(&self, dexes: &[String], case_insensitive: bool) -> i32 {
+        dexes.iter().fold(0i32, |total, dex| {
+            let fee = if case_insensitive {
+                self.fees.get(&dex.to_ascii_lowercase())
+            } else {
+                self.fees.get(dex)
+            };
+            total.saturating_add(fee.copied().unwrap_or(self.default_bps))
+        })
+    }
+}
+
+/// `output_amount * (1 - slippage_bps / 10000)` in exact decimals, trailing
+/// zeros trimmed: the output to expect once the trade moves the pool.
+/// `slippage_bps` must be within `[0, 10000]` (10000 leaves nothing) and
+/// `output_amount` a non-negative decimal.
+#[napi]
+pub fn apply_slippage(output_amount: String, slippage_bps: i32) -> napi::Result<String> {
+    check_slippage_bps(slippage_bps)?;
+    let output = parse_decimal(&output_amount)
+        .filter(|output| !output.is_sign_negative())
+        .ok_or_else(|| Error::invalid_argument("output amount", format!("must be a non-negative decimal, got '{}'", output_amount)))?;
+    let slipped = apply_bps_decimal(output, -slippage_bps)
+        .ok_or_else(|| Error::invalid_argument("output amount", format!("'{}' is too large", output_amount)))?;
+    Ok(slipped.normalize().to_string())
+}
+
+/// Reject `slippage_bps` outside `[0, 10000]`
+fn check_slippage_bps(slippage_bps: i32) -> Result<(), Error> {
+    if !(0..=BPS_SCALE as i32).contains(&slippage_bps) {
+        return Err(Error::invalid_argument(
+            "slippage_bps",
+            format!("must be within [0, 10000], got {}", slippage_bps),
+        ));
+    }
+    Ok(())
+}
 
 /// High-performance opportunity scanner with ARM optimizations
 #[napi]
 pub struct TurboScanner {
-    seen_opportunities: Arc<RwLock<AHashSet<String>>>,
-    min_profit_bps: i32,
+    /// Short-term dedup tier: route key -> when it was recorded (ms), LRU-bounded
+    seen_opportunities: Arc<RwLock<SeenCache>>,
+    /// Capacity passed to the constructor; `None` is DEFAULT_SEEN_LIMIT outside lightweight mode
+    seen_capacity: Option<usize>,
+    /// Append the `input_amount` bucket to dedup keys
+    include_amounts: bool,
+    key_strategy: KeyStrategy,
+    /// 0 keeps short-term entries until evicted or reset
+    dedup_ttl_ms: Arc<RwLock<i64>>,
+    /// Long-term tier of routes already executed, checked before the short one
+    executed: Arc<RwLock<AHashSet<String>>>,
+    min_profit_bps: Arc<RwLock<i32>>,
+    /// Floor on the decimal `profit`, checked alongside `min_profit_bps`
+    min_profit_absolute: Arc<RwLock<Option<Decimal>>>,
+    profit_bps_source: Arc<RwLock<ProfitBpsSource>>,
+    bps_basis: Arc<RwLock<BpsBasis>>,
     scan_count: Arc<RwLock<u64>>,
+    impact_model: Arc<RwLock<ImpactModel>>,
+    timing: Arc<RwLock<CallTimer>>,
+    case_insensitive_keys: Arc<RwLock<bool>>,
+    sampler: Arc<DedupSampler>,
+    min_emit_interval_ms: Arc<RwLock<i64>>,
+    last_emitted: Arc<RwLock<AHashMap<String, i64>>>,
+    /// Ring of (scanned_at_ms, batches, opportunities) per millisecond over
+    /// the last THROUGHPUT_RETENTION_MS
+    recent_batches: Arc<RwLock<VecDeque<(i64, u32, u64)>>>,
+    top_queue: Arc<RwLock<TopQueue>>,
+    stream: Arc<RwLock<Option<StreamedBatch>>>,
+    required_token: Arc<RwLock<Option<String>>>,
+    flash_loan_fee_bps: Arc<RwLock<i32>>,
+    dex_fees: Arc<RwLock<DexFeeSchedule>>,
+    min_confidence: Arc<RwLock<f64>>,
+    confidence_half_life_ms: Arc<RwLock<f64>>,
+    event_sink: Arc<RwLock<Option<EventSink>>>,
 }
 
 #[napi]
 impl TurboScanner {
+    /// Validating constructor: rejects `min_profit_bps` below -10000 (a
+    /// loss of more than 100%), which would accept every opportunity, and a
+    /// zero `capacity`
+    #[napi(factory)]
+    pub fn try_new(
+        min_profit_bps: i32,
+        capacity: Option<u32>,
+        include_amounts: Option<bool>,
+    ) -> napi::Result<Self> {
+        if min_profit_bps < MIN_PROFIT_BPS_FLOOR {
+            return Err(Error::invalid_argument(
+                "min_profit_bps",
+                format!("must be at least {}, got {}", MIN_PROFIT_BPS_FLOOR, min_profit_bps),
+            )
+            .into());
+        }
+        if capacity == Some(0) {
+            return Err(Error::invalid_argument("capacity", "must be at least 1").into());
+        }
+        Ok(Self::new(min_profit_bps, capacity, include_amounts))
+    }
+
+    /// `capacity` (optional) bounds the dedup set: once it holds that many
+    /// routes, recording another evicts exactly the least recently used one
+    /// (a duplicate hit counts as a use). Omitted, the set holds up to
+    /// 100,000 routes; lightweight mode caps it at 1000 either way.
+    /// A capacity of 0 is treated as 1.
+    ///
+    /// `include_amounts` (default false) keys dedup on the trade size as well
+    /// as the route, so the same route quoted at materially different sizes
+    /// is emitted once per size. Sizes are bucketed on a log scale with each
+    /// bucket 5% wide (e.g. 1000 and 1001 share a key, 1000 and 1100 do not),
+    /// so rounding noise still dedups. Near a bucket edge, two sizes less
+    /// than 5% apart can land in adjacent buckets.
     #[napi(constructor)]
-    pub fn new(min_profit_bps: i32) -> Self {
+    pub fn new(min_profit_bps: i32, capacity: Option<u32>, include_amounts: Option<bool>) -> Self {
+        let seen_capacity = capacity.map(|c| c.max(1) as usize);
         Self {
-            seen_opportunities: Arc::new(RwLock::new(AHashSet::new())),
-            min_profit_bps,
+            seen_opportunities: Arc::new(RwLock::new(SeenCache::new(Some(Self::seen_limit(
+                seen_capacity,
+                is_lightweight_mode(),
+            ))))),
+            seen_capacity,
+            include_amounts: include_amounts.unwrap_or(false),
+            key_strategy: KeyStrategy::default(),
+            dedup_ttl_ms: Arc::new(RwLock::new(0)),
+            executed: Arc::new(RwLock::new(AHashSet::with_hasher(hash_state()))),
+            min_profit_bps: Arc::new(RwLock::new(min_profit_bps)),
+            min_profit_absolute: Arc::new(RwLock::new(None)),
+            profit_bps_source: Arc::new(RwLock::new(ProfitBpsSource::default())),
+            bps_basis: Arc::new(RwLock::new(BpsBasis::default())),
             scan_count: Arc::new(RwLock::new(0)),
+            impact_model: Arc::new(RwLock::new(ImpactModel::default())),
+            timing: Arc::new(RwLock::new(CallTimer::default())),
+            case_insensitive_keys: Arc::new(RwLock::new(false)),
+            sampler: Arc::new(DedupSampler::default()),
+            min_emit_interval_ms: Arc::new(RwLock::new(0)),
+            last_emitted: Arc::new(RwLock::new(AHashMap::with_hasher(hash_state()))),
+            recent_batches: Arc::new(RwLock::new(VecDeque::new())),
+            top_queue: Arc::new(RwLock::new(TopQueue::default())),
+            stream: Arc::new(RwLock::new(None)),
+            required_token: Arc::new(RwLock::new(None)),
+            flash_loan_fee_bps: Arc::new(RwLock::new(0)),
+            dex_fees: Arc::new(RwLock::new(DexFeeSchedule::default())),
+            min_confidence: Arc::new(RwLock::new(0.0)),
+            confidence_half_life_ms: Arc::new(RwLock::new(DEFAULT_CONFIDENCE_HALF_LIFE_MS)),
+            event_sink: Arc::new(RwLock::new(None)),
         }
     }
 
-    /// Fast opportunity filtering with duplicate detection
-    /// 3x faster than JavaScript implementation
+    /// `new` with a dedup key other than path and DEXes
+    pub fn with_key_strategy(
+        min_profit_bps: i32,
+        capacity: Option<u32>,
+        include_amounts: Option<bool>,
+        key_strategy: KeyStrategy,
+    ) -> Self {
+        Self { key_strategy, ..Self::new(min_profit_bps, capacity, include_amounts) }
+    }
+
+    /// `new` measuring derived bps against `bps_basis` (`new` uses
+    /// `BpsBasis::Input`), see `set_bps_basis`
+    pub fn with_bps_basis(
+        min_profit_bps: i32,
+        capacity: Option<u32>,
+        include_amounts: Option<bool>,
+        bps_basis: BpsBasis,
+    ) -> Self {
+        Self { bps_basis: Arc::new(RwLock::new(bps_basis)), ..Self::new(min_profit_bps, capacity, include_amounts) }
+    }
+
+    /// `new` with a dedup key strategy: `"path"` keys on the token path
+    /// alone, `"path_dexes"` (what `new` uses) on path and DEXes, and
+    /// `"path_dexes_time"` adds the bucket `timestamp / bucket_ms`, letting
+    /// a route re-fire once its timestamp reaches the next bucket.
+    /// `bucket_ms` is required (and must be positive) for `"path_dexes_time"`
+    /// only.
+    #[napi(factory)]
+    pub fn new_with_key_strategy(
+        min_profit_bps: i32,
+        capacity: Option<u32>,
+        include_amounts: Option<bool>,
+        strategy: String,
+        bucket_ms: Option<i64>,
+    ) -> napi::Result<Self> {
+        let key_strategy = match (strategy.as_str(), bucket_ms) {
+            ("path", _) => KeyStrategy::PathOnly,
+            ("path_dexes", _) => KeyStrategy::PathAndDexes,
+            ("path_dexes_time", Some(bucket_ms)) if bucket_ms > 0 => KeyStrategy::PathDexesTimeBucket { bucket_ms },
+            ("path_dexes_time", _) => {
+                return Err(Error::invalid_argument("bucket_ms", "must be positive for 'path_dexes_time'").into())
+            }
+            (other, _) => {
+                return Err(Error::invalid_argument(
+                    "strategy",
+                    format!("expected 'path', 'path_dexes' or 'path_dexes_time', got '{}'", other),
+                )
+                .into())
+            }
+        };
+        Ok(Self::with_key_strategy(min_profit_bps, capacity, include_amounts, key_strategy))
+    }
+
+    pub fn get_key_strategy(&self) -> KeyStrategy {
+        self.key_strategy
+    }
+
+    /// Reject opportunities whose price confidence is below `c`.
+    ///
+    /// Confidence decays exponentially with the age of the quoted prices:
+    /// `confidence = 0.5^(age_ms / half_life_ms)` with `age_ms = max(0,
+    /// now_ms - price_timestamp)`, so a fresh price scores 1.0, one half-life
+    /// old 0.5, two half-lives 0.25. Opportunities without a
+    /// `price_timestamp` score 1.0. `c` must be within `[0, 1]`; 0 (the
+    /// default) disables the check.
     #[napi]
-    pub fn filter_opportunities(&self, opportunities: Vec<Opportunity>) -> Vec<Opportunity> {
-        let mut scan_count = self.scan_count.write();
-        *scan_count += 1;
+    pub fn set_min_confidence(&self, c: f64) -> napi::Result<()> {
+        if !(0.0..=1.0).contains(&c) {
+            return Err(Error::invalid_argument("min confidence", format!("must be within [0, 1], got {}", c)).into());
+        }
+        *self.min_confidence.write() = c;
+        Ok(())
+    }
 
-        let lightweight = is_lightweight_mode();
-        let mut seen = self.seen_opportunities.write();
-        let mut filtered = Vec::new();
+    /// Half-life of the confidence decay in ms (default 5000); must be positive
+    #[napi]
+    pub fn set_confidence_half_life_ms(&self, half_life_ms: f64) -> napi::Result<()> {
+        if !(half_life_ms.is_finite() && half_life_ms > 0.0) {
+            return Err(Error::invalid_argument(
+                "confidence half-life",
+                format!("must be positive, got {}", half_life_ms),
+            )
+            .into());
+        }
+        *self.confidence_half_life_ms.write() = half_life_ms;
+        Ok(())
+    }
 
-        // Reserve capacity to avoid reallocations (ARM optimization)
-        if !lightweight {
-            filtered.reserve(opportunities.len());
+    /// Keep only routes that pass through `token` as an intermediate hop
+    /// (not the first or last element of `path`). Matching follows
+    /// `set_case_insensitive_keys`. An empty token removes the constraint.
+    #[napi]
+    pub fn set_required_token(&self, token: String) {
+        *self.required_token.write() = if token.is_empty() { None } else { Some(token) };
+    }
+
+    /// Per-route rate limit: a route key is emitted at most once per
+    /// `interval` ms, independent of dedup. 0 (the default) disables it.
+    #[napi]
+    pub fn set_min_emit_interval_ms(&self, interval: i64) {
+        *self.min_emit_interval_ms.write() = interval.max(0);
+        if interval <= 0 {
+            self.last_emitted.write().clear();
         }
+    }
 
-        for opp in opportunities {
-            // Skip low-profit opportunities early
-            if opp.profit_bps < self.min_profit_bps {
-                continue;
-            }
+    /// Overload valve: only `rate` of the opportunities that clear the profit
+    /// threshold are checked against (and recorded in) the dedup set; the
+    /// rest pass through unchecked. This trades dedup accuracy for throughput.
+    /// `rate` must be in `[0, 1]`; the default 1.0 means full dedup.
+    #[napi]
+    pub fn set_sample_rate(&self, rate: f64) -> napi::Result<()> {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(Error::invalid_argument("sample rate", format!("must be within [0, 1], got {}", rate)).into());
+        }
 
-            // Generate unique key for deduplication
-            let key = self.generate_opportunity_key(&opp);
+        self.sampler.set_rate(rate);
+        Ok(())
+    }
 
-            // Check if we've seen this before
-            if seen.contains(&key) {
-                continue;
-            }
+    #[napi]
+    pub fn get_sample_rate(&self) -> f64 {
+        self.sampler.rate()
+    }
 
-            // In lightweight mode, limit cache size to save memory
-            if lightweight && seen.len() > 1000 {
-                seen.clear();
-            }
+    /// Stop trusting the supplied `profit_bps`: when enabled, the filter
+    /// recomputes it as `(output_amount - input_amount) / input_amount *
+    /// 10000` (or over `output_amount`, see `set_bps_basis`) in exact
+    /// decimals (rounded half away from zero, saturating) and applies the
+    /// costs and threshold to that instead. Opportunities whose amounts
+    /// don't parse or whose basis amount isn't positive are rejected
+    /// ("invalid_amounts"). With `overwrite`, accepted
+    /// opportunities carry the recomputed value in `profit_bps`; otherwise
+    /// they are returned as given. Default off.
+    #[napi]
+    pub fn set_recompute_profit_bps(&self, enabled: bool, overwrite: Option<bool>) {
+        *self.profit_bps_source.write() = if enabled {
+            ProfitBpsSource::Recomputed { overwrite: overwrite.unwrap_or(false) }
+        } else {
+            ProfitBpsSource::Supplied
+        };
+    }
+
+    /// Measure derived bps against `input_amount` (the default, matching a
+    /// typical supplied `profit_bps`) or `output_amount`. Applies to
+    /// `set_recompute_profit_bps`, `filter_opportunities_slippage` and
+    /// `filter_opportunities_net`; opportunities whose basis amount is zero
+    /// (or negative) are rejected there.
+    pub fn set_bps_basis(&self, basis: BpsBasis) {
+        *self.bps_basis.write() = basis;
+    }
+
+    pub fn get_bps_basis(&self) -> BpsBasis {
+        *self.bps_basis.read()
+    }
+
+    /// `set_bps_basis` from JS: measure derived bps against `output_amount`
+    /// when enabled, `input_amount` otherwise
+    #[napi]
+    pub fn set_output_bps_basis(&self, enabled: bool) {
+        self.set_bps_basis(if enabled { BpsBasis::Output } else { BpsBasis::Input });
+    }
+
+    /// Positive `input_amount` or `output_amount` per `basis`
+    fn basis_amount(opp: &Opportunity, basis: BpsBasis) -> Option<Decimal> {
+        let raw = match basis {
+            BpsBasis::Input => &opp.input_amount,
+            BpsBasis::Output => &opp.output_amount,
+        };
+        parse_decimal(raw).filter(|amount| *amount > Decimal::ZERO)
+    }
+
+    /// Gross profit in bps from the amounts, see `set_recompute_profit_bps`;
+    /// the output first cut by `slippage_bps` (0 for none), including when
+    /// it is the basis
+    fn recomputed_profit_bps(opp: &Opportunity, slippage_bps: i32, basis: BpsBasis) -> Option<i32> {
+        let input = parse_decimal(&opp.input_amount)?;
+        let output = apply_bps_decimal(parse_decimal(&opp.output_amount)?, -slippage_bps)?;
+        let denominator = match basis {
+            BpsBasis::Input => input,
+            BpsBasis::Output => output,
+        };
+        if denominator <= Decimal::ZERO {
+            return None;
+        }
+        let bps = output
+            .checked_sub(input)
+            .and_then(|gain| gain.checked_mul(Decimal::from(BPS_SCALE as i64)))
+            .and_then(|scaled| scaled.checked_div(denominator));
+        let saturated = if output < input { i32::MIN } else { i32::MAX };
+        Some(
+            bps.and_then(|bps| bps.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero).to_i32())
+                .unwrap_or(saturated),
+        )
+    }
 
-            seen.insert(key);
-            filtered.push(opp);
+    /// Flash-loan fee charged on the borrowed `input_amount`, in bps. Since
+    /// `profit_bps` is also relative to `input_amount`, the fee comes off it
+    /// one-for-one. Costs are deducted in a fixed order before thresholding:
+    /// `profit_bps - impact(input_amount) - flash_loan_fee_bps - route fee`
+    /// (see `set_dex_fee_bps`). 0 (the default) disables it; negative fees
+    /// are rejected.
+    #[napi]
+    pub fn set_flash_loan_fee_bps(&self, bps: i32) -> napi::Result<()> {
+        if bps < 0 {
+            return Err(Error::invalid_argument("flash loan fee", format!("must be non-negative, got {}", bps)).into());
         }
+        *self.flash_loan_fee_bps.write() = bps;
+        Ok(())
+    }
 
-        filtered
+    /// Swap fee charged by `dex` on each hop routed through it, in bps.
+    ///
+    /// The route fee is the sum over the opportunity's `dexes` list (one
+    /// entry per hop, so a venue used twice is charged twice), each DEX
+    /// taking its listed fee or the default from `set_default_dex_fee_bps`.
+    /// It is summed in bps rather than compounded, which overstates the
+    /// cost by under 0.01 bps per hop at typical fee levels. Deducted with
+    /// the other costs before thresholding: `profit_bps - impact -
+    /// flash_loan_fee_bps - route fee`. Names follow the key case setting,
+    /// so call `set_case_insensitive_keys` first. Negative fees are rejected.
+    #[napi]
+    pub fn set_dex_fee_bps(&self, dex: String, bps: i32) -> napi::Result<()> {
+        if bps < 0 {
+            return Err(Error::invalid_argument("dex fee", format!("must be non-negative, got {}", bps)).into());
+        }
+        let dex = if *self.case_insensitive_keys.read() { dex.to_ascii_lowercase() } else { dex };
+        self.dex_fees.write().fees.insert(dex, bps);
+        Ok(())
     }
 
-    /// Generate unique key for opportunity (ARM-optimized string operations)
-    fn generate_opportunity_key(&self, opp: &Opportunity) -> String {
-        // Use efficient string concatenation for ARM
-        let mut key = String::with_capacity(128);
-        key.push_str(&opp.path.join("-"));
-        key.push('|');
-        key.push_str(&opp.dexes.join("-"));
-        key
+    /// Fee for DEXes without an entry from `set_dex_fee_bps`. 0 (the
+    /// default) leaves unlisted venues free; negative fees are rejected.
+    #[napi]
+    pub fn set_default_dex_fee_bps(&self, bps: i32) -> napi::Result<()> {
+        if bps < 0 {
+            return Err(Error::invalid_argument("dex fee", format!("must be non-negative, got {}", bps)).into());
+        }
+        self.dex_fees.write().default_bps = bps;
+        Ok(())
     }
 
+    /// Lowercase path tokens and DEX names when building dedup keys so
+    /// checksummed and lowercase addresses collapse to one key (default off)
     #[napi]
-    pub fn get_scan_count(&self) -> f64 {
-        *self.scan_count.read() as f64
+    pub fn set_case_insensitive_keys(&self, enabled: bool) {
+        *self.case_insensitive_keys.write() = enabled;
     }
 
+    /// Opt-in per-call timing of `filter_opportunities` (excludes NAPI marshaling)
     #[napi]
-    pub fn reset(&self) {
-        self.seen_opportunities.write().clear();
-        let mut count = self.scan_count.write();
-        *count = 0;
+    pub fn set_timing_enabled(&self, enabled: bool) {
+        self.timing.write().set_enabled(enabled);
     }
 
+    /// Duration of the last timed call in microseconds
     #[napi]
-    pub fn get_cache_size(&self) -> u32 {
-        self.seen_opportunities.read().len() as u32
+    pub fn get_last_duration_us(&self) -> f64 {
+        self.timing.read().last_us()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Rolling average over recent timed calls in microseconds
+    #[napi]
+    pub fn get_average_duration_us(&self) -> f64 {
+        self.timing.read().average_us()
+    }
 
-    #[test]
-    fn test_turbo_scanner() {
-        let scanner = TurboScanner::new(50);
-        
-        let opp = Opportunity {
-            path: vec!["A".to_string(), "B".to_string()],
-            dexes: vec!["dex1".to_string()],
-            input_amount: "1000".to_string(),
-            output_amount: "1100".to_string(),
-            profit: "100".to_string(),
-            profit_bps: 100,
-            timestamp: 0,
-        };
+    /// Select the price-impact curve applied by `filter_opportunities`
+    pub fn set_impact_model(&self, model: ImpactModel) {
+        *self.impact_model.write() = model;
+    }
 
-        let filtered = scanner.filter_opportunities(vec![opp.clone()]);
-        assert_eq!(filtered.len(), 1);
+    pub fn get_impact_model(&self) -> ImpactModel {
+        *self.impact_model.read()
+    }
 
-        // Second time should be filtered out (duplicate)
-        let filtered2 = scanner.filter_opportunities(vec![opp]);
-        assert_eq!(filtered2.len(), 0);
+    /// Linear impact: `impact_bps = bps_per_unit * input_amount`
+    #[napi]
+    pub fn set_linear_impact(&self, bps_per_unit: f64) {
+        self.set_impact_model(ImpactModel::Linear { bps_per_unit });
+    }
+
+    /// Square-root impact: `impact_bps = k * sqrt(input_amount)`
+    #[napi]
+    pub fn set_sqrt_impact(&self, k: f64) {
+        self.set_impact_model(ImpactModel::SqrtK { k });
+    }
+
+    /// Constant-product (x*y=k) impact: `impact_bps = 10000 * input_amount / (reserve + input_amount)`
+    #[napi]
+    pub fn set_constant_product_impact(&self, reserve: f64) {
+        self.set_impact_model(ImpactModel::ConstantProduct { reserve });
+    }
+
+    /// Fast opportunity filtering with duplicate detection
+    /// 3x faster than JavaScript implementation. Reads the wall clock, so it
+    /// fails in deterministic mode; use `filter_opportunities_at` there.
+    #[napi]
+    pub fn filter_opportunities(&self, opportunities: Vec<Opportunity>) -> napi::Result<Vec<Opportunity>> {
+        Ok(self.filter_opportunities_at(opportunities, wall_clock_ms("filter_opportunities")?))
+    }
+
+    /// `filter_opportunities` with an explicit clock for the time-based rules
+    #[napi]
+    pub fn filter_opportunities_at(&self, opportunities: Vec<Opportunity>, now_ms: i64) -> Vec<Opportunity> {
+        self.filter_accepted(opportunities, now_ms, FilterOptions::default())
+    }
+
+    /// Count a scan and return what the filter accepts under `options`
+    fn filter_accepted(&self, opportunities: Vec<Opportunity>, now_ms: i64, options: FilterOptions) -> Vec<Opportunity> {
+        self.count_scan(now_ms, opportunities.len());
+        let mut filtered = Vec::new();
+
+        // Reserve capacity to avoid reallocations (ARM optimization)
+        if !is_lightweight_mode() {
+            filtered.reserve(opportunities.len());
+        }
+
+        self.run_filter(opportunities, now_ms, options, |opp, verdict, _| {
+            if verdict == FilterReason::Accepted {
+                filtered.push(opp);
+            }
+        });
+        filtered
+    }
+
+    /// What `filter_opportunities_at` would accept right now, without
+    /// changing anything: existing dedup and throttle state is consulted but
+    /// no key is recorded or refreshed, no scan is counted and no events are
+    /// sent, so the same batch gives the same answer every time, e.g. for
+    /// backtesting against live state. Repeats within the batch are still
+    /// rejected as duplicates. Every opportunity is checked against the
+    /// dedup set, whatever the sample rate. The dedup and throttle state is
+    /// only read-locked, and dry runs aren't timed.
+    #[napi]
+    pub fn filter_opportunities_dry(&self, opportunities: Vec<Opportunity>, now_ms: i64) -> Vec<Opportunity> {
+        let mut filtered = Vec::new();
+        self.run_filter(opportunities, now_ms, FilterOptions { dry_run: true, ..Default::default() }, |opp, verdict, _| {
+            if verdict == FilterReason::Accepted {
+                filtered.push(opp);
+            }
+        });
+        filtered
+    }
+
+    /// Filter a single opportunity as it arrives: the same rules and shared
+    /// dedup state as `filter_opportunities`, counting one scan per call.
+    /// Returns the opportunity if it passes. Reads the wall clock; see
+    /// `scan_one_at`.
+    #[napi]
+    pub fn scan_one(&self, opp: Opportunity) -> napi::Result<Option<Opportunity>> {
+        Ok(self.scan_one_at(opp, wall_clock_ms("scan_one")?))
+    }
+
+    /// `scan_one` with an explicit clock for the time-based rules
+    #[napi]
+    pub fn scan_one_at(&self, opp: Opportunity, now_ms: i64) -> Option<Opportunity> {
+        self.count_scan(now_ms, 1);
+        let mut passed = None;
+        self.run_filter(vec![opp], now_ms, FilterOptions::default(), |opp, verdict, _| {
+            if verdict == FilterReason::Accepted {
+                passed = Some(opp);
+            }
+        });
+        passed
+    }
+
+    /// Per-input accept/reject decisions for `filter_opportunities`, in
+    /// input order, for logging why each opportunity was dropped. Reads the
+    /// wall clock like `filter_opportunities`; see
+    /// `filter_opportunities_explained` for the explicit-clock variant.
+    #[napi]
+    pub fn filter_with_reasons(&self, opportunities: Vec<Opportunity>) -> napi::Result<Vec<FilterOutcome>> {
+        Ok(self.filter_opportunities_explained(opportunities, wall_clock_ms("filter_with_reasons")?))
+    }
+
+    /// `filter_opportunities` net of gas. `gas_cost` is in the same units as
+    /// `profit`; with `hop_multiplier` set, a route with `n` entries in
+    /// `dexes` is charged `gas_cost * (1 + hop_multiplier * (n - 1))`. An
+    /// opportunity is kept only if `profit - gas` is positive and, as a
+    /// fraction of `input_amount`, reaches `min_profit_bps`; everything is
+    /// computed in exact decimals. Opportunities failing this (or with an
+    /// unparseable `profit`, or a zero or unparseable `input_amount`; the
+    /// `output_amount` instead under `set_bps_basis(Output)`) are
+    /// dropped before the regular filter, so they are never recorded as
+    /// seen. Reads the wall clock; see `filter_opportunities_net_at`.
+    #[napi]
+    pub fn filter_opportunities_net(
+        &self,
+        opportunities: Vec<Opportunity>,
+        gas_cost: String,
+        hop_multiplier: Option<String>,
+    ) -> napi::Result<Vec<Opportunity>> {
+        let now_ms = wall_clock_ms("filter_opportunities_net")?;
+        self.filter_opportunities_net_at(opportunities, gas_cost, hop_multiplier, now_ms)
+    }
+
+    /// `filter_opportunities_net` with an explicit clock
+    #[napi]
+    pub fn filter_opportunities_net_at(
+        &self,
+        opportunities: Vec<Opportunity>,
+        gas_cost: String,
+        hop_multiplier: Option<String>,
+        now_ms: i64,
+    ) -> napi::Result<Vec<Opportunity>> {
+        let gas_cost = parse_decimal(&gas_cost)
+            .filter(|gas| !gas.is_sign_negative())
+            .ok_or_else(|| Error::invalid_argument("gas cost", format!("must be a non-negative decimal, got '{}'", gas_cost)))?;
+        let hop_multiplier = match hop_multiplier {
+            Some(raw) => Some(parse_decimal(&raw).filter(|m| !m.is_sign_negative()).ok_or_else(|| {
+                Error::invalid_argument("hop multiplier", format!("must be a non-negative decimal, got '{}'", raw))
+            })?),
+            None => None,
+        };
+
+        let min_profit_bps = Decimal::from(*self.min_profit_bps.read());
+        let basis = *self.bps_basis.read();
+        let profitable: Vec<Opportunity> = opportunities
+            .into_iter()
+            .filter(|opp| {
+                Self::net_of_gas(opp, gas_cost, hop_multiplier, basis)
+                    .is_some_and(|(net, base)| net > Decimal::ZERO && net * Decimal::from(BPS_SCALE as i64) >= min_profit_bps * base)
+            })
+            .collect();
+        Ok(self.filter_opportunities_at(profitable, now_ms))
+    }
+
+    /// `filter_opportunities` on the output expected after slippage: each
+    /// opportunity's `profit_bps` is replaced by the one its amounts give
+    /// once `output_amount` is cut by `slippage_bps` (see `apply_slippage`),
+    /// and the regular costs and threshold then apply to that. Opportunities
+    /// whose amounts don't parse or whose basis amount (see `set_bps_basis`)
+    /// isn't positive are dropped before the filter, as are all of them at
+    /// 10000 bps unless `min_profit_bps` allows a total loss. `slippage_bps`
+    /// outside `[0, 10000]` is an error. Reads the wall clock; see
+    /// `filter_opportunities_slippage_at`.
+    #[napi]
+    pub fn filter_opportunities_slippage(
+        &self,
+        opportunities: Vec<Opportunity>,
+        slippage_bps: i32,
+    ) -> napi::Result<Vec<Opportunity>> {
+        let now_ms = wall_clock_ms("filter_opportunities_slippage")?;
+        self.filter_opportunities_slippage_at(opportunities, slippage_bps, now_ms)
+    }
+
+    /// `filter_opportunities_slippage` with an explicit clock
+    #[napi]
+    pub fn filter_opportunities_slippage_at(
+        &self,
+        opportunities: Vec<Opportunity>,
+        slippage_bps: i32,
+        now_ms: i64,
+    ) -> napi::Result<Vec<Opportunity>> {
+        check_slippage_bps(slippage_bps)?;
+        let basis = *self.bps_basis.read();
+        let slipped: Vec<Opportunity> = opportunities
+            .into_iter()
+            .filter_map(|mut opp| {
+                opp.profit_bps = Self::recomputed_profit_bps(&opp, slippage_bps, basis)?;
+                Some(opp)
+            })
+            .collect();
+        Ok(self.filter_accepted(slipped, now_ms, FilterOptions { slippage_bps, ..Default::default() }))
+    }
+
+    /// (`profit` minus the route's gas, basis amount), or `None` when an
+    /// amount doesn't parse, the basis isn't positive or the math overflows
+    fn net_of_gas(
+        opp: &Opportunity,
+        gas_cost: Decimal,
+        hop_multiplier: Option<Decimal>,
+        basis: BpsBasis,
+    ) -> Option<(Decimal, Decimal)> {
+        let profit = parse_decimal(&opp.profit)?;
+        let base = Self::basis_amount(opp, basis)?;
+        let gas = match hop_multiplier {
+            Some(multiplier) => {
+                let extra_hops = Decimal::from(opp.dexes.len().saturating_sub(1) as u64);
+                gas_cost.checked_mul(multiplier.checked_mul(extra_hops)?.checked_add(Decimal::ONE)?)?
+            }
+            None => gas_cost,
+        };
+        Some((profit.checked_sub(gas)?, base))
+    }
+
+    /// Same filtering (and state updates) as `filter_opportunities_at`, but
+    /// reports an outcome for every input in order. `net_profit_bps` is the
+    /// profit after the impact curve and is never clamped, so a rejected
+    /// opportunity shows how far below the threshold it actually was.
+    #[napi]
+    pub fn filter_opportunities_explained(&self, opportunities: Vec<Opportunity>, now_ms: i64) -> Vec<FilterOutcome> {
+        self.count_scan(now_ms, opportunities.len());
+        let mut outcomes = Vec::with_capacity(opportunities.len());
+        self.run_filter(opportunities, now_ms, FilterOptions::default(), |_, verdict, net_profit_bps| {
+            outcomes.push(FilterOutcome {
+                accepted: verdict == FilterReason::Accepted,
+                reason: verdict.as_str().to_string(),
+                net_profit_bps,
+            });
+        });
+        outcomes
+    }
+
+    /// `filter_opportunities_explained` that first checks each route's
+    /// shape: `dexes` must hold one entry per hop (`path.len() - 1`),
+    /// otherwise the reason is "hop_mismatch"; with `require_cycle`, `path`
+    /// must also start and end on the same token, otherwise "open_cycle".
+    /// Rejected routes are never recorded as seen. Reads the wall clock;
+    /// see `filter_opportunities_validated_at`.
+    #[napi]
+    pub fn filter_opportunities_validated(
+        &self,
+        opportunities: Vec<Opportunity>,
+        require_cycle: Option<bool>,
+    ) -> napi::Result<Vec<FilterOutcome>> {
+        let now_ms = wall_clock_ms("filter_opportunities_validated")?;
+        Ok(self.filter_opportunities_validated_at(opportunities, require_cycle, now_ms))
+    }
+
+    /// `filter_opportunities_validated` with an explicit clock
+    #[napi]
+    pub fn filter_opportunities_validated_at(
+        &self,
+        opportunities: Vec<Opportunity>,
+        require_cycle: Option<bool>,
+        now_ms: i64,
+    ) -> Vec<FilterOutcome> {
+        self.count_scan(now_ms, opportunities.len());
+        let rules = RouteRules { require_cycle: require_cycle.unwrap_or(false) };
+        let mut outcomes = Vec::with_capacity(opportunities.len());
+        self.run_filter(opportunities, now_ms, FilterOptions { rules: Some(rules), ..Default::default() }, |_, verdict, net_profit_bps| {
+            outcomes.push(FilterOutcome {
+                accepted: verdict == FilterReason::Accepted,
+                reason: verdict.as_str().to_string(),
+                net_profit_bps,
+            });
+        });
+        outcomes
+    }
+
+    /// Start a streamed batch. Opportunities are then fed with `push_chunk`,
+    /// which filters each chunk as it arrives, and collected with
+    /// `end_batch`. The scanner holds the batch state between calls and only
+    /// one batch can be in flight at a time: calling `begin_batch` again
+    /// before `end_batch` is an error. The clock is read once here and used
+    /// for every chunk; the whole stream counts as a single scan.
+    #[napi]
+    pub fn begin_batch(&self) -> napi::Result<()> {
+        self.begin_batch_at(wall_clock_ms("begin_batch")?)
+    }
+
+    /// `begin_batch` with an explicit clock for the time-based rules
+    pub fn begin_batch_at(&self, now_ms: i64) -> napi::Result<()> {
+        let mut stream = self.stream.write();
+        if stream.is_some() {
+            return Err(Error::InvalidInput("a streamed batch is already in flight".to_string()).into());
+        }
+        *stream = Some(StreamedBatch { now_ms, received: 0, accepted: Vec::new() });
+        Ok(())
+    }
+
+    /// Filter one chunk of the in-flight batch, keeping the accepted ones
+    #[napi]
+    pub fn push_chunk(&self, chunk: Vec<Opportunity>) -> napi::Result<()> {
+        let mut stream = self.stream.write();
+        let batch = stream
+            .as_mut()
+            .ok_or_else(|| Error::InvalidInput("push_chunk called without begin_batch".to_string()))?;
+
+        batch.received += chunk.len();
+        let accepted = &mut batch.accepted;
+        self.run_filter(chunk, batch.now_ms, FilterOptions::default(), |opp, verdict, _| {
+            if verdict == FilterReason::Accepted {
+                accepted.push(opp);
+            }
+        });
+        Ok(())
+    }
+
+    /// Finish the in-flight batch and return everything accepted, in arrival order
+    #[napi]
+    pub fn end_batch(&self) -> napi::Result<Vec<Opportunity>> {
+        let batch = self
+            .stream
+            .write()
+            .take()
+            .ok_or_else(|| Error::InvalidInput("end_batch called without begin_batch".to_string()))?;
+        self.count_scan(batch.now_ms, batch.received);
+        Ok(batch.accepted)
+    }
+
+    /// Staleness confidence in `(0, 1]`, see `set_min_confidence`
+    fn price_confidence(opp: &Opportunity, now_ms: i64, half_life_ms: f64) -> f64 {
+        match opp.price_timestamp {
+            Some(observed_at) => {
+                let age_ms = now_ms.saturating_sub(observed_at).max(0) as f64;
+                0.5f64.powf(age_ms / half_life_ms)
+            }
+            None => 1.0,
+        }
+    }
+
+    /// Whether `token` appears strictly inside the route
+    fn has_intermediate(opp: &Opportunity, token: &str, case_insensitive: bool) -> bool {
+        let hops = match opp.path.len() {
+            0..=2 => return false,
+            len => &opp.path[1..len - 1],
+        };
+        hops.iter().any(|hop| if case_insensitive { hop.eq_ignore_ascii_case(token) } else { hop == token })
+    }
+
+    /// Count one scan of `size` opportunities for `get_scan_count` and throughput
+    fn count_scan(&self, now_ms: i64, size: usize) {
+        *self.scan_count.write() += 1;
+        self.record_batch(now_ms, size);
+    }
+
+    /// Report evictions and duplicates to `callback` as `{ kind, key }`
+    /// objects, `kind` being "eviction" or "duplicate" and `key` the route's
+    /// dedup key. Replaces any previous callback. Events are queued to the
+    /// JS thread without blocking and delivered after the filter call has
+    /// released its locks; the callback doesn't keep the process alive.
+    #[napi]
+    pub fn on_event(&self, env: Env, callback: JsFunction) -> napi::Result<()> {
+        let mut tsfn: ThreadsafeFunction<ScanEvent, ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<ScanEvent>| Ok(vec![ctx.value]))?;
+        tsfn.unref(&env)?;
+        self.set_event_sink(Some(Arc::new(move |event| {
+            tsfn.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+        })));
+        Ok(())
+    }
+
+    /// Remove the `on_event` callback
+    #[napi]
+    pub fn off_event(&self) {
+        self.set_event_sink(None);
+    }
+
+    fn set_event_sink(&self, sink: Option<EventSink>) {
+        *self.event_sink.write() = sink;
+    }
+
+    /// Core filter loop: decides each opportunity, updates dedup/throttle
+    /// state and hands every input to `emit` with its verdict and net bps.
+    /// A dry run only read-locks that state and isn't timed. Scan events
+    /// are collected under the locks and dispatched after.
+    fn run_filter(
+        &self,
+        opportunities: Vec<Opportunity>,
+        now_ms: i64,
+        options: FilterOptions,
+        emit: impl FnMut(Opportunity, FilterReason, i32),
+    ) {
+        let sink = if options.dry_run { None } else { self.event_sink.read().clone() };
+        let mut events = sink.as_ref().map(|_| Vec::new());
+        self.run_filter_locked(opportunities, now_ms, options, &mut events, emit);
+        if let (Some(sink), Some(events)) = (sink, events) {
+            events.into_iter().for_each(|event| sink(event));
+        }
+    }
+
+    fn run_filter_locked(
+        &self,
+        opportunities: Vec<Opportunity>,
+        now_ms: i64,
+        options: FilterOptions,
+        events: &mut Option<Vec<ScanEvent>>,
+        mut emit: impl FnMut(Opportunity, FilterReason, i32),
+    ) {
+        let FilterOptions { rules, dry_run, slippage_bps } = options;
+        let started = if dry_run { None } else { self.timing.read().start() };
+        let lightweight = is_lightweight_mode();
+        let impact_model = *self.impact_model.read();
+        let flash_loan_fee_bps = *self.flash_loan_fee_bps.read();
+        let dex_fees = self.dex_fees.read();
+        let min_emit_interval_ms = *self.min_emit_interval_ms.read();
+        let required_token = self.required_token.read();
+        let case_insensitive = *self.case_insensitive_keys.read();
+        let min_profit_bps = *self.min_profit_bps.read();
+        let min_profit_absolute = *self.min_profit_absolute.read();
+        let profit_bps_source = *self.profit_bps_source.read();
+        let bps_basis = *self.bps_basis.read();
+        let min_confidence = *self.min_confidence.read();
+        let half_life_ms = *self.confidence_half_life_ms.read();
+        let dedup_ttl_ms = *self.dedup_ttl_ms.read();
+        let executed = self.executed.read();
+        // Locked on the first sampled opportunity, so an unsampled batch never takes it
+        let mut seen: Option<StateGuard<'_, SeenCache>> = None;
+        let lock_seen = || {
+            let mut seen = StateGuard::lock(&self.seen_opportunities, dry_run);
+            if let Some(seen) = seen.get_mut() {
+                seen.set_capacity(Some(Self::seen_limit(self.seen_capacity, lightweight)));
+                if dedup_ttl_ms > 0 && seen.len() > EMIT_LOG_PRUNE_SIZE {
+                    seen.retain(|_, at| now_ms.saturating_sub(at) < dedup_ttl_ms);
+                }
+            }
+            seen
+        };
+        let mut last_emitted = StateGuard::lock(&self.last_emitted, dry_run);
+        // Keys a dry run would have recorded, so in-batch repeats still count
+        let mut dry_seen: AHashSet<String> = AHashSet::new();
+
+        if let Some(last_emitted) = last_emitted.get_mut() {
+            if last_emitted.len() > EMIT_LOG_PRUNE_SIZE {
+                last_emitted.retain(|_, at| now_ms.saturating_sub(*at) < min_emit_interval_ms);
+            }
+        }
+
+        for mut opp in opportunities {
+            let profit_bps = match profit_bps_source {
+                ProfitBpsSource::Supplied => Some(opp.profit_bps),
+                ProfitBpsSource::Recomputed { overwrite } => {
+                    let recomputed = Self::recomputed_profit_bps(&opp, slippage_bps, bps_basis);
+                    if let (true, Some(bps)) = (overwrite, recomputed) {
+                        opp.profit_bps = bps;
+                    }
+                    recomputed
+                }
+            };
+
+            // Skip low-profit opportunities early (after size-dependent price impact)
+            let net_profit_bps = Self::effective_profit_bps(profit_bps.unwrap_or(opp.profit_bps), &opp.input_amount, &impact_model)
+                .saturating_sub(flash_loan_fee_bps)
+                .saturating_sub(dex_fees.route_fee_bps(&opp.dexes, case_insensitive));
+
+            // Long-term tier first: executed routes never pass, whatever else fails
+            let mut key = None;
+            if !executed.is_empty() {
+                let executed_key = self.generate_opportunity_key(&opp);
+                if executed.contains(&executed_key) {
+                    emit(opp, FilterReason::Executed, net_profit_bps);
+                    continue;
+                }
+                key = Some(executed_key);
+            }
+
+            if profit_bps.is_none() {
+                emit(opp, FilterReason::InvalidAmounts, net_profit_bps);
+                continue;
+            }
+
+            if let Some(reason) = rules.and_then(|rules| rules.check(&opp)) {
+                emit(opp, reason, net_profit_bps);
+                continue;
+            }
+
+            if let Some(token) = required_token.as_deref() {
+                if !Self::has_intermediate(&opp, token, case_insensitive) {
+                    emit(opp, FilterReason::MissingRequiredToken, net_profit_bps);
+                    continue;
+                }
+            }
+
+            if min_confidence > 0.0 && Self::price_confidence(&opp, now_ms, half_life_ms) < min_confidence {
+                emit(opp, FilterReason::LowConfidence, net_profit_bps);
+                continue;
+            }
+
+            if net_profit_bps < min_profit_bps {
+                emit(opp, FilterReason::BelowThreshold, net_profit_bps);
+                continue;
+            }
+
+            if let Some(floor) = min_profit_absolute {
+                match parse_decimal(&opp.profit) {
+                    None => {
+                        emit(opp, FilterReason::InvalidAmounts, net_profit_bps);
+                        continue;
+                    }
+                    Some(profit) if profit < floor => {
+                        emit(opp, FilterReason::BelowAbsoluteThreshold, net_profit_bps);
+                        continue;
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            // Under sampling, unsampled opportunities bypass dedup entirely;
+            // dry runs check every opportunity and leave the count alone
+            let check_dedup = dry_run || self.sampler.should_check();
+            if !check_dedup && min_emit_interval_ms == 0 {
+                emit(opp, FilterReason::Accepted, net_profit_bps);
+                continue;
+            }
+
+            // Generate unique key for deduplication
+            let key = key.unwrap_or_else(|| self.generate_opportunity_key(&opp));
+
+            // Check if we've seen this recently (short-term tier)
+            if check_dedup {
+                if dry_seen.contains(&key) {
+                    emit(opp, FilterReason::Duplicate, net_profit_bps);
+                    continue;
+                }
+                let seen = seen.get_or_insert_with(lock_seen);
+                if let Some(at) = seen.recorded_at(&key) {
+                    if dedup_ttl_ms == 0 || now_ms.saturating_sub(at) < dedup_ttl_ms {
+                        if let Some(seen) = seen.get_mut() {
+                            seen.touch(&key);
+                        }
+                        if let Some(events) = events.as_mut() {
+                            events.push(ScanEvent::new(ScanEventKind::Duplicate, key));
+                        }
+                        emit(opp, FilterReason::Duplicate, net_profit_bps);
+                        continue;
+                    }
+                }
+            }
+
+            // Per-route throttle, applied even when the dedup set forgot the key
+            if min_emit_interval_ms > 0 {
+                if let Some(&at) = last_emitted.get(&key) {
+                    if now_ms.saturating_sub(at) < min_emit_interval_ms {
+                        emit(opp, FilterReason::Throttled, net_profit_bps);
+                        continue;
+                    }
+                }
+                if let Some(last_emitted) = last_emitted.get_mut() {
+                    last_emitted.insert(key.clone(), now_ms);
+                }
+            }
+
+            if check_dedup {
+                match seen.get_or_insert_with(lock_seen).get_mut() {
+                    None => {
+                        dry_seen.insert(key);
+                    }
+                    // Past capacity this evicts only the least recently used route
+                    Some(seen) => match events.as_mut() {
+                        Some(events) => seen.insert_reporting(key, now_ms, |evicted| {
+                            events.push(ScanEvent::new(ScanEventKind::Eviction, evicted))
+                        }),
+                        None => {
+                            seen.insert(key, now_ms);
+                        }
+                    },
+                }
+            }
+            emit(opp, FilterReason::Accepted, net_profit_bps);
+        }
+
+        if let Some(started) = started {
+            self.timing.write().record(started.elapsed());
+        }
+    }
+
+    /// Dedup key for an opportunity, suitable for persisting and passing to `preload_seen`
+    #[napi]
+    pub fn opportunity_key(&self, opp: Opportunity) -> String {
+        self.generate_opportunity_key(&opp)
+    }
+
+    /// Seed the dedup set with keys acted upon in a previous session so a
+    /// restart doesn't re-emit them. Keys are expected oldest-first and are
+    /// recorded as the most recently used, so past the dedup capacity only
+    /// the newest survive (evicting least recently used keys already held).
+    /// Returns the number of keys inserted. Under a dedup TTL, preloaded
+    /// keys count as recorded now.
+    #[napi]
+    pub fn preload_seen(&self, keys: Vec<String>) -> napi::Result<u32> {
+        self.insert_seen(keys, true, wall_clock_ms("preload_seen")?)
+    }
+
+    /// Alias of `preload_seen` for warming the dedup set from replayed history
+    #[napi]
+    pub fn prefill_seen(&self, keys: Vec<String>) -> napi::Result<u32> {
+        self.preload_seen(keys)
+    }
+
+    /// Snapshot of the dedup set for sharing with other scanner instances
+    /// via `import_seen`, oldest first (ties by key). Keys past the dedup
+    /// TTL are left out, which reads the wall clock.
+    #[napi]
+    pub fn export_seen(&self) -> napi::Result<Vec<String>> {
+        let ttl_ms = *self.dedup_ttl_ms.read();
+        let now = if ttl_ms > 0 { wall_clock_ms("export_seen")? } else { 0 };
+        let seen = self.seen_opportunities.read();
+
+        let mut live: Vec<(i64, &String)> = seen
+            .iter()
+            .filter(|(_, at)| ttl_ms == 0 || now.saturating_sub(*at) < ttl_ms)
+            .map(|(key, at)| (at, key))
+            .collect();
+        live.sort_unstable();
+        Ok(live.into_iter().map(|(_, key)| key.clone()).collect())
+    }
+
+    /// Merge keys exported by another instance into the dedup set, so
+    /// sharded scanners converge on a shared view by periodically gossiping
+    /// `export_seen` output instead of contending on one lock.
+    ///
+    /// Best-effort and eventually consistent: a route both shards see
+    /// between two exchanges can still be emitted twice. Same capacity
+    /// handling as `preload_seen`; keys already present keep their local
+    /// timestamp and recency, new ones count as recorded now.
+    /// Returns the number of keys inserted.
+    #[napi]
+    pub fn import_seen(&self, keys: Vec<String>) -> napi::Result<u32> {
+        self.insert_seen(keys, false, wall_clock_ms("import_seen")?)
+    }
+
+    /// Shared by `preload_seen`/`import_seen`: stamp `keys` with
+    /// `recorded_at`, optionally refreshing keys already present
+    fn insert_seen(&self, keys: Vec<String>, refresh: bool, recorded_at: i64) -> napi::Result<u32> {
+        let mut seen = self.seen_opportunities.write();
+        seen.set_capacity(Some(Self::seen_limit(self.seen_capacity, is_lightweight_mode())));
+
+        // Only the last `capacity` keys could survive anyway
+        let skip = seen.capacity().map_or(0, |capacity| keys.len().saturating_sub(capacity));
+        let mut inserted = 0;
+        for key in keys.into_iter().skip(skip) {
+            let is_new = if refresh {
+                let is_new = !seen.contains_key(&key);
+                seen.insert(key, recorded_at);
+                is_new
+            } else {
+                seen.insert_if_absent(key, recorded_at)
+            };
+            inserted += is_new as u32;
+        }
+        Ok(inserted)
+    }
+
+    /// Dedup capacity in effect: the constructor's (or the default), capped
+    /// in lightweight mode
+    fn seen_limit(capacity: Option<usize>, lightweight: bool) -> usize {
+        let capacity = capacity.unwrap_or(DEFAULT_SEEN_LIMIT);
+        if lightweight { capacity.min(LIGHTWEIGHT_SEEN_LIMIT) } else { capacity }
+    }
+
+    /// Return the best `n` opportunities without touching the dedup state.
+    ///
+    /// Comparison chain (first difference wins):
+    /// 1. `profit_bps` descending
+    /// 2. opportunities with a parseable `liquidity` before those without
+    /// 3. `liquidity` descending
+    /// 4. absolute `profit` descending, compared as exact decimals
+    ///    (unparseable profit sorts last)
+    /// 5. input order (the sort is stable)
+    #[napi]
+    pub fn top_opportunities(&self, mut opportunities: Vec<Opportunity>, n: u32) -> Vec<Opportunity> {
+        opportunities.sort_by(Self::compare_rank);
+        opportunities.truncate(n as usize);
+        opportunities
+    }
+
+    /// `filter_opportunities`, then the survivors best first in the
+    /// `top_opportunities` order, truncated to `top_n` when given. Reads the
+    /// wall clock; in deterministic mode combine `filter_opportunities_at`
+    /// with `top_opportunities` instead.
+    #[napi]
+    pub fn rank_opportunities(&self, opportunities: Vec<Opportunity>, top_n: Option<u32>) -> napi::Result<Vec<Opportunity>> {
+        let mut ranked = self.filter_opportunities_at(opportunities, wall_clock_ms("rank_opportunities")?);
+        ranked.sort_by(Self::compare_rank);
+        if let Some(n) = top_n {
+            ranked.truncate(n as usize);
+        }
+        Ok(ranked)
+    }
+
+    /// Number of opportunities kept by `push_opportunities` (default 100).
+    /// Shrinking the capacity evicts the worst entries immediately.
+    #[napi]
+    pub fn set_top_capacity(&self, capacity: u32) {
+        let mut queue = self.top_queue.write();
+        queue.capacity = capacity as usize;
+        while queue.heap.len() > queue.capacity {
+            queue.heap.pop();
+        }
+    }
+
+    /// Offer opportunities to the cross-batch top-N queue. No threshold or
+    /// dedup is applied here - filter first if needed. Memory is bounded by
+    /// the capacity: when full, a newcomer replaces the current worst entry
+    /// only if its `profit_bps` is strictly higher (ties keep the older one).
+    #[napi]
+    pub fn push_opportunities(&self, opportunities: Vec<Opportunity>) {
+        let mut queue = self.top_queue.write();
+        if queue.capacity == 0 {
+            return;
+        }
+
+        for opp in opportunities {
+            if queue.heap.len() == queue.capacity {
+                match queue.heap.peek() {
+                    Some(Reverse(worst)) if opp.profit_bps > worst.profit_bps => {
+                        queue.heap.pop();
+                    }
+                    _ => continue,
+                }
+            }
+
+            let seq = queue.next_seq;
+            queue.next_seq += 1;
+            queue.heap.push(Reverse(RankedEntry { profit_bps: opp.profit_bps, seq, opp }));
+        }
+    }
+
+    /// Return and clear the queued opportunities, best first (same
+    /// comparison chain as `top_opportunities`)
+    #[napi]
+    pub fn drain_top(&self) -> Vec<Opportunity> {
+        let mut entries: Vec<RankedEntry> = {
+            let mut queue = self.top_queue.write();
+            queue.heap.drain().map(|Reverse(entry)| entry).collect()
+        };
+        entries.sort_by_key(|entry| entry.seq);
+
+        let mut drained: Vec<Opportunity> = entries.into_iter().map(|entry| entry.opp).collect();
+        drained.sort_by(Self::compare_rank);
+        drained
+    }
+
+    /// Ranking order used by `top_opportunities` (best first)
+    fn compare_rank(a: &Opportunity, b: &Opportunity) -> Ordering {
+        let liquidity = |o: &Opportunity| o.liquidity.as_deref().and_then(parse_price);
+        // None sorts below every parsed profit
+        let profit = |o: &Opportunity| parse_decimal(&o.profit);
+
+        b.profit_bps
+            .cmp(&a.profit_bps)
+            .then_with(|| match (liquidity(a), liquidity(b)) {
+                (Some(la), Some(lb)) => lb.total_cmp(&la),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            })
+            .then_with(|| profit(b).cmp(&profit(a)))
+    }
+
+    /// `profit_bps` discounted by the configured impact curve (the flash-loan
+    /// fee is deducted after this, in `run_filter`)
+    fn effective_profit_bps(profit_bps: i32, input_amount: &str, model: &ImpactModel) -> i32 {
+        let size = parse_price(input_amount).unwrap_or(0.0);
+        let impact = ratio_to_bps(model.impact_bps(size) / BPS_SCALE);
+        profit_bps.saturating_sub(impact)
+    }
+
+    /// Generate unique key for opportunity (ARM-optimized string operations)
+    fn generate_opportunity_key(&self, opp: &Opportunity) -> String {
+        let lowercase = *self.case_insensitive_keys.read();
+        let mut key = match self.key_strategy {
+            KeyStrategy::PathOnly => {
+                let mut key = opp.path.join("-");
+                if lowercase {
+                    key.make_ascii_lowercase();
+                }
+                key
+            }
+            KeyStrategy::PathAndDexes => route_key(opp, lowercase),
+            KeyStrategy::PathDexesTimeBucket { bucket_ms } => {
+                let mut key = route_key(opp, lowercase);
+                key.push('|');
+                key.push_str(&opp.timestamp.div_euclid(bucket_ms.max(1)).to_string());
+                key
+            }
+        };
+        if self.include_amounts {
+            key.push('|');
+            key.push_str(&amount_bucket(&opp.input_amount));
+        }
+        key
+    }
+
+    /// Re-read the lightweight flag and, if it is now on, trim the dedup set
+    /// down to the lightweight limit, least recently used keys first.
+    /// Returns the number of evicted keys.
+    #[napi]
+    pub fn reconfigure(&self) -> u32 {
+        let limit = Self::seen_limit(self.seen_capacity, is_lightweight_mode());
+        self.seen_opportunities.write().set_capacity(Some(limit)) as u32
+    }
+
+    /// Whether this opportunity's key is already in the dedup set or the
+    /// executed tier (read-only)
+    #[napi]
+    pub fn contains(&self, opp: Opportunity) -> bool {
+        let key = self.generate_opportunity_key(&opp);
+        self.executed.read().contains(&key) || self.seen_opportunities.read().contains_key(&key)
+    }
+
+    /// Change the profitability floor without losing the dedup state; takes
+    /// effect from the next filter call (a call in progress on another
+    /// thread finishes with the old value)
+    #[napi]
+    pub fn set_min_profit_bps(&self, bps: i32) {
+        *self.min_profit_bps.write() = bps;
+    }
+
+    #[napi]
+    pub fn get_min_profit_bps(&self) -> i32 {
+        *self.min_profit_bps.read()
+    }
+
+    /// Also require the opportunity's `profit` (in output token units,
+    /// parsed as an exact decimal) to be at least `min`, e.g. "5" so tiny
+    /// high-bps routes that can't cover gas are dropped
+    /// ("below_absolute_threshold"). Both floors must pass when both are
+    /// set. While set, an unparseable `profit` is rejected
+    /// ("invalid_amounts"). `None` (the default) removes the floor.
+    #[napi]
+    pub fn set_min_profit_absolute(&self, min: Option<String>) -> napi::Result<()> {
+        let min = match min {
+            Some(raw) => Some(
+                parse_decimal(&raw)
+                    .ok_or_else(|| Error::invalid_argument("min_profit_absolute", format!("must be a decimal, got '{}'", raw)))?,
+            ),
+            None => None,
+        };
+        *self.min_profit_absolute.write() = min;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn get_min_profit_absolute(&self) -> Option<String> {
+        self.min_profit_absolute.read().map(|min| min.normalize().to_string())
+    }
+
+    /// Short-term dedup TTL: a route recorded more than `ttl_ms` ago is
+    /// treated as new again. 0 (the default) keeps entries until they are
+    /// evicted or `reset`. Executed routes are unaffected (see `mark_executed`).
+    #[napi]
+    pub fn set_dedup_ttl_ms(&self, ttl_ms: i64) {
+        *self.dedup_ttl_ms.write() = ttl_ms.max(0);
+    }
+
+    /// Move a route into the long-term executed tier.
+    ///
+    /// Dedup has two tiers. The short-term tier (the dedup set, optionally
+    /// with a TTL) suppresses spam and forgets routes over time or under
+    /// lightweight eviction. The executed tier holds routes already acted
+    /// upon; it is checked before every other filter, ignores sampling and
+    /// the short-term TTL, is never evicted, and survives `reset`. Clear it
+    /// with `clear_executed` (e.g. once a day).
+    #[napi]
+    pub fn mark_executed(&self, opp: Opportunity) {
+        let key = self.generate_opportunity_key(&opp);
+        self.seen_opportunities.write().remove(&key);
+        self.executed.write().insert(key);
+    }
+
+    #[napi]
+    pub fn get_executed_count(&self) -> u32 {
+        self.executed.read().len() as u32
+    }
+
+    #[napi]
+    pub fn clear_executed(&self) {
+        self.executed.write().clear();
+    }
+
+    /// Opportunities processed per second over the trailing
+    /// `THROUGHPUT_WINDOW_MS`, counting every input (accepted or not)
+    #[napi]
+    pub fn get_throughput(&self, now_ms: i64) -> f64 {
+        let since = now_ms.saturating_sub(THROUGHPUT_WINDOW_MS);
+        let processed: u64 = self
+            .recent_batches
+            .read()
+            .iter()
+            .filter(|(at, _, _)| *at > since && *at <= now_ms)
+            .map(|(_, _, size)| *size)
+            .sum();
+        processed as f64 * 1000.0 / THROUGHPUT_WINDOW_MS as f64
+    }
+
+    /// Scans (filter calls, whatever their size) per second over the
+    /// trailing `(now_ms - window_ms, now_ms]`, from the same ring as
+    /// `get_throughput`, e.g. to spot a stalled feed. Exact at any scan rate
+    /// for windows up to `SCAN_RATE_MAX_WINDOW_MS` (60s), which is as far
+    /// back as the ring reaches; a longer window returns NaN rather than an
+    /// undercount. 0 for a non-positive window.
+    #[napi]
+    pub fn scan_rate(&self, window_ms: i64, now_ms: i64) -> f64 {
+        if window_ms <= 0 {
+            return 0.0;
+        }
+        if window_ms > SCAN_RATE_MAX_WINDOW_MS {
+            return f64::NAN;
+        }
+        let since = now_ms.saturating_sub(window_ms);
+        let scans: u64 = self
+            .recent_batches
+            .read()
+            .iter()
+            .filter(|(at, _, _)| *at > since && *at <= now_ms)
+            .map(|(_, scans, _)| *scans as u64)
+            .sum();
+        scans as f64 * 1000.0 / window_ms as f64
+    }
+
+    fn record_batch(&self, now_ms: i64, size: usize) {
+        let mut batches = self.recent_batches.write();
+        let since = now_ms.saturating_sub(THROUGHPUT_RETENTION_MS);
+        while batches.front().is_some_and(|(at, _, _)| *at <= since) {
+            batches.pop_front();
+        }
+        match batches.back_mut() {
+            Some((at, scans, total)) if *at == now_ms => {
+                *scans = scans.saturating_add(1);
+                *total = total.saturating_add(size as u64);
+            }
+            _ => {
+                if batches.len() == THROUGHPUT_RING_CAPACITY {
+                    batches.pop_front();
+                }
+                batches.push_back((now_ms, 1, size as u64));
+            }
+        }
+    }
+
+    #[napi]
+    pub fn get_scan_count(&self) -> f64 {
+        *self.scan_count.read() as f64
+    }
+
+    /// Clear the short-term state; the executed tier is kept
+    #[napi]
+    pub fn reset(&self) {
+        self.seen_opportunities.write().clear();
+        self.last_emitted.write().clear();
+        self.recent_batches.write().clear();
+        self.stream.write().take();
+        let mut count = self.scan_count.write();
+        *count = 0;
+    }
+
+    #[napi]
+    pub fn get_cache_size(&self) -> u32 {
+        self.seen_opportunities.read().len() as u32
+    }
+
+    /// Debugging aid for a scanner suspected of corruption: checks that the
+    /// dedup set's recency index matches its entries, that the throughput
+    /// ring and top queue are within capacity and no bigger than the scans
+    /// counted, and that nothing is stamped in the future. Throws a "corrupt
+    /// state" error naming the first violation. Reads the wall clock (except
+    /// in deterministic mode, which skips the future-timestamp checks) and
+    /// takes every lock, so keep it off the hot path.
+    #[napi]
+    pub fn self_check(&self) -> napi::Result<()> {
+        Ok(self.check_invariants().map_err(Error::CorruptState)?)
+    }
+
+    fn check_invariants(&self) -> Result<(), String> {
+        let limit = if is_deterministic() {
+            i64::MAX
+        } else {
+            now_ms().saturating_add(SELF_CHECK_FUTURE_TOLERANCE_MS)
+        };
+
+        let batches = self.recent_batches.read();
+        if batches.len() > THROUGHPUT_RING_CAPACITY {
+            return Err(format!("throughput ring holds {} batches (capacity {})", batches.len(), THROUGHPUT_RING_CAPACITY));
+        }
+        if let Some(&(ts, _, _)) = batches.iter().find(|(ts, _, _)| *ts > limit) {
+            return Err(format!("throughput ring has a batch stamped {} ms in the future", ts - now_ms()));
+        }
+        let ring_scans: u64 = batches.iter().map(|(_, scans, _)| *scans as u64).sum();
+        if ring_scans > *self.scan_count.read() {
+            return Err(format!("throughput ring holds {} batches but scan_count is lower", ring_scans));
+        }
+
+        self.seen_opportunities.read().check()?;
+
+        if let Some((key, ts)) = self.last_emitted.read().iter().find(|(_, ts)| **ts > limit) {
+            return Err(format!("emit log entry '{}' is stamped {} ms in the future", key, ts - now_ms()));
+        }
+
+        let queue = self.top_queue.read();
+        if queue.heap.len() > queue.capacity {
+            return Err(format!("top queue holds {} entries (capacity {})", queue.heap.len(), queue.capacity));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A two-hop route `A -> path_suffix` that clears the default threshold;
+    /// tests override the fields they care about with struct-update syntax
+    fn opp(path_suffix: &str, profit_bps: i32) -> Opportunity {
+        Opportunity {
+            path: vec!["A".to_string(), path_suffix.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_turbo_scanner() {
+        let scanner = TurboScanner::new(50, None, None);
+        
+        let opp = Opportunity {
+            path: vec!["A".to_string(), "B".to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        let filtered = scanner.filter_opportunities(vec![opp.clone()]).unwrap();
+        assert_eq!(filtered.len(), 1);
+
+        // Second time should be filtered out (duplicate)
+        let filtered2 = scanner.filter_opportunities(vec![opp]).unwrap();
+        assert_eq!(filtered2.len(), 0);
+    }
+
+    #[test]
+    fn test_top_opportunities_tie_breakers() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |id: &str, profit_bps: i32, profit: &str, liquidity: Option<&str>| Opportunity {
+            path: vec!["A".to_string(), id.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: profit.to_string(),
+            profit_bps,
+            timestamp: 0,
+            liquidity: liquidity.map(|l| l.to_string()),
+            price_timestamp: None,
+        };
+
+        let ranked = scanner.top_opportunities(
+            vec![
+                make("thin", 100, "10", Some("500")),
+                make("no_liq_small", 100, "5", None),
+                make("best", 200, "1", None),
+                make("deep", 100, "10", Some("5000")),
+                make("no_liq_big", 100, "50", None),
+            ],
+            10,
+        );
+
+        let ids: Vec<&str> = ranked.iter().map(|o| o.path[1].as_str()).collect();
+        assert_eq!(ids, vec!["best", "deep", "thin", "no_liq_big", "no_liq_small"]);
+
+        // Ranking is side-effect free and truncates
+        assert_eq!(scanner.top_opportunities(ranked, 2).len(), 2);
+        assert_eq!(scanner.get_cache_size(), 0);
+    }
+
+    #[test]
+    fn test_reconfigure_applies_mode() {
+        let _modes = crate::ModeGuard::acquire();
+        let scanner = TurboScanner::new(50, None, None);
+        let keys: Vec<String> = (0..1500).map(|i| format!("key{}", i)).collect();
+        scanner.preload_seen(keys).unwrap();
+        assert_eq!(scanner.reconfigure(), 0);
+
+        crate::set_lightweight_mode(true);
+        assert_eq!(scanner.reconfigure(), 500);
+        assert_eq!(scanner.get_cache_size(), LIGHTWEIGHT_SEEN_LIMIT as u32);
+        crate::set_lightweight_mode(false);
+    }
+
+    #[test]
+    fn test_contains_is_read_only() {
+        let scanner = TurboScanner::new(50, None, None);
+        let opp = Opportunity {
+            path: vec!["A".to_string(), "B".to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        assert!(!scanner.contains(opp.clone()));
+        assert_eq!(scanner.get_cache_size(), 0);
+        assert_eq!(scanner.get_scan_count(), 0.0);
+
+        scanner.filter_opportunities(vec![opp.clone()]).unwrap();
+        assert!(scanner.contains(opp));
+        assert_eq!(scanner.get_cache_size(), 1);
+        assert_eq!(scanner.get_scan_count(), 1.0);
+    }
+
+    #[test]
+    fn test_min_emit_interval() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |route: &str| Opportunity {
+            path: vec!["A".to_string(), route.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        // Disable dedup so the throttle is the only thing suppressing repeats
+        scanner.set_sample_rate(0.0).unwrap();
+        scanner.set_min_emit_interval_ms(1000);
+
+        let batch = vec![make("B"), make("B"), make("C")];
+        let emitted = scanner.filter_opportunities_at(batch, 10_000);
+        assert_eq!(emitted.len(), 2);
+
+        // Rapid repeat of B is throttled, a new route D passes
+        assert_eq!(scanner.filter_opportunities_at(vec![make("B"), make("D")], 10_500).len(), 1);
+
+        // Once the interval has elapsed B may fire again
+        assert_eq!(scanner.filter_opportunities_at(vec![make("B")], 11_000).len(), 1);
+    }
+
+    #[test]
+    fn test_throughput() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |i: usize| Opportunity {
+            path: vec!["A".to_string(), format!("T{}", i)],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: if i.is_multiple_of(2) { 100 } else { 0 },
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        assert_eq!(scanner.get_throughput(0), 0.0);
+
+        // 300 inputs (accepted or not) over the 10s window -> 30/s
+        for batch in 0..3 {
+            let opps = (0..100).map(|i| make(batch * 100 + i)).collect();
+            scanner.filter_opportunities_at(opps, 1_000 + batch as i64 * 1_000);
+        }
+        assert_eq!(scanner.get_throughput(5_000), 30.0);
+
+        // The first batch has aged out of the window
+        assert_eq!(scanner.get_throughput(11_500), 20.0);
+
+        scanner.reset();
+        assert_eq!(scanner.get_throughput(5_000), 0.0);
+
+        // Far more batches than fit a count-capped ring: 200/s of 10 for 20s
+        for i in 0..4_000 {
+            let opps = (0..10).map(|j| make(i * 10 + j)).collect();
+            scanner.filter_opportunities_at(opps, 20_000 + (i as i64) * 5);
+        }
+        assert_eq!(scanner.get_throughput(39_995), 2_000.0);
+        assert_eq!(scanner.recent_batches.read().len(), 4_000);
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let scanner = TurboScanner::new(50, None, None);
+        let opp = Opportunity {
+            path: vec!["A".to_string(), "B".to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        assert_eq!(scanner.get_sample_rate(), 1.0);
+        assert!(scanner.set_sample_rate(1.5).is_err());
+        assert!(scanner.set_sample_rate(-0.1).is_err());
+
+        // Half the stream is checked: 5 unchecked + 1 first-seen pass, 4 duplicates drop
+        scanner.set_sample_rate(0.5).unwrap();
+        let filtered = scanner.filter_opportunities(vec![opp.clone(); 10]).unwrap();
+        assert_eq!(filtered.len(), 6);
+
+        // Zero disables dedup entirely
+        scanner.set_sample_rate(0.0).unwrap();
+        assert_eq!(scanner.filter_opportunities(vec![opp; 3]).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_two_tier_dedup() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |id: &str| Opportunity {
+            path: vec!["A".to_string(), id.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+        let reasons = |opps: Vec<Opportunity>, now: i64| -> Vec<String> {
+            scanner.filter_opportunities_explained(opps, now).into_iter().map(|o| o.reason).collect()
+        };
+
+        // Short tier: a duplicate until the TTL lapses
+        scanner.set_dedup_ttl_ms(1_000);
+        assert_eq!(reasons(vec![make("a"), make("a")], 0), vec!["accepted", "duplicate"]);
+        assert_eq!(reasons(vec![make("a")], 999), vec!["duplicate"]);
+        assert_eq!(reasons(vec![make("a")], 1_000), vec!["accepted"]);
+
+        // Long tier: executed routes stay rejected past the TTL, under
+        // sampling and across reset
+        scanner.mark_executed(make("a"));
+        assert_eq!(scanner.get_executed_count(), 1);
+        assert_eq!(reasons(vec![make("a"), make("b")], 50_000), vec!["executed", "accepted"]);
+        assert!(scanner.contains(make("a")));
+
+        // Checked before every other filter
+        let unprofitable = Opportunity { profit_bps: 1, ..make("a") };
+        assert_eq!(reasons(vec![unprofitable], 50_000), vec!["executed"]);
+        scanner.set_sample_rate(0.0).unwrap();
+        scanner.reset();
+        assert_eq!(reasons(vec![make("a"), make("b")], 100_000), vec!["executed", "accepted"]);
+
+        scanner.clear_executed();
+        assert_eq!(reasons(vec![make("a")], 100_000), vec!["accepted"]);
+    }
+
+    #[test]
+    fn test_staleness_confidence() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |id: &str, price_timestamp: Option<i64>| Opportunity {
+            path: vec!["A".to_string(), id.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp,
+        };
+        let now = 100_000;
+
+        assert!(scanner.set_min_confidence(1.5).is_err());
+        assert!(scanner.set_confidence_half_life_ms(0.0).is_err());
+
+        // Half-life 1000 ms: ages 0 / 500 / 1000 / 2000 ms score 1 / 0.71 / 0.5 / 0.25
+        scanner.set_confidence_half_life_ms(1_000.0).unwrap();
+        scanner.set_min_confidence(0.5).unwrap();
+        let opps = vec![
+            make("fresh", Some(now)),
+            make("half", Some(now - 500)),
+            make("one", Some(now - 1_000)),
+            make("two", Some(now - 2_000)),
+            make("unknown", None),
+            make("future", Some(now + 5_000)),
+        ];
+        let reasons: Vec<String> = scanner
+            .filter_opportunities_explained(opps.clone(), now)
+            .into_iter()
+            .map(|o| o.reason)
+            .collect();
+        assert_eq!(reasons, vec!["accepted", "accepted", "accepted", "low_confidence", "accepted", "accepted"]);
+
+        // Raising the bar rejects the one-half-life price too
+        scanner.reset();
+        scanner.set_min_confidence(0.6).unwrap();
+        assert_eq!(scanner.filter_opportunities_at(opps.clone(), now).len(), 4);
+
+        // 0 disables the check
+        scanner.reset();
+        scanner.set_min_confidence(0.0).unwrap();
+        assert_eq!(scanner.filter_opportunities_at(opps, now).len(), 6);
+    }
+
+    #[test]
+    fn test_diff_opportunities() {
+        let make = |id: &str, profit_bps: i32| Opportunity {
+            path: vec!["A".to_string(), id.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "1".to_string(),
+            profit_bps,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+        let ids = |opps: &[Opportunity]| opps.iter().map(|o| o.path[1].clone()).collect::<Vec<_>>();
+
+        let prev = vec![make("a", 10), make("b", 20), make("c", 30), make("b", 99)];
+        // "d" repeats: only its first occurrence counts
+        let curr = vec![make("d", 5), make("c", 31), make("a", 10), make("d", 6)];
+
+        let diff = diff_opportunities(prev, curr);
+        assert_eq!(ids(&diff.added), vec!["d"]);
+        assert_eq!(diff.added[0].profit_bps, 5);
+        assert_eq!(ids(&diff.removed), vec!["b"]);
+        assert_eq!(diff.removed[0].profit_bps, 20);
+        assert_eq!(ids(&diff.changed), vec!["c"]);
+        assert_eq!(diff.changed[0].profit_bps, 31);
+
+        let same = diff_opportunities(vec![make("a", 10)], vec![make("a", 10)]);
+        assert!(same.added.is_empty() && same.removed.is_empty() && same.changed.is_empty());
+    }
+
+    #[test]
+    fn test_flash_loan_fee() {
+        let scanner = TurboScanner::new(50, None, None);
+        let opp = Opportunity {
+            path: vec!["A".to_string(), "B".to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1006".to_string(),
+            profit: "6".to_string(),
+            profit_bps: 60,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        assert!(scanner.set_flash_loan_fee_bps(-1).is_err());
+
+        // 60 bps clears the threshold without the fee...
+        assert_eq!(scanner.filter_opportunities_explained(vec![opp.clone()], 0)[0].net_profit_bps, 60);
+
+        // ...but not after a 15 bps flash-loan fee on the input
+        scanner.reset();
+        scanner.set_flash_loan_fee_bps(15).unwrap();
+        let outcome = &scanner.filter_opportunities_explained(vec![opp.clone()], 0)[0];
+        assert_eq!((outcome.accepted, outcome.net_profit_bps), (false, 45));
+
+        // Impact (1000 * 0.002 = 2 bps) and the fee are both deducted
+        scanner.reset();
+        scanner.set_flash_loan_fee_bps(5).unwrap();
+        scanner.set_linear_impact(0.002);
+        assert_eq!(scanner.filter_opportunities_explained(vec![opp.clone()], 0)[0].net_profit_bps, 53);
+
+        // Zero disables the fee
+        scanner.reset();
+        scanner.set_flash_loan_fee_bps(0).unwrap();
+        assert_eq!(scanner.filter_opportunities_at(vec![opp], 0).len(), 1);
+    }
+
+    #[test]
+    fn test_dex_fee_schedule() {
+        let scanner = TurboScanner::new(50, None, None);
+        let opp = Opportunity {
+            path: vec!["A".to_string(), "B".to_string(), "A".to_string()],
+            dexes: vec!["uniswap".to_string(), "curve".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1008".to_string(),
+            profit: "8".to_string(),
+            profit_bps: 80,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        assert!(scanner.set_dex_fee_bps("uniswap".to_string(), -1).is_err());
+        assert!(scanner.set_default_dex_fee_bps(-1).is_err());
+
+        // 30 bps on the first hop plus 4 on the second
+        scanner.set_dex_fee_bps("uniswap".to_string(), 30).unwrap();
+        scanner.set_dex_fee_bps("curve".to_string(), 4).unwrap();
+        let outcome = &scanner.filter_opportunities_explained(vec![opp.clone()], 0)[0];
+        assert_eq!((outcome.accepted, outcome.net_profit_bps), (false, 46));
+
+        // Unlisted venues take the default, stacked with the flash-loan fee
+        scanner.reset();
+        scanner.set_default_dex_fee_bps(10).unwrap();
+        scanner.set_flash_loan_fee_bps(5).unwrap();
+        let mut unlisted = opp.clone();
+        unlisted.dexes[1] = "balancer".to_string();
+        assert_eq!(scanner.filter_opportunities_explained(vec![unlisted], 0)[0].net_profit_bps, 35);
+
+        // Case-insensitive keys match venue names regardless of case
+        let folded = TurboScanner::new(50, None, None);
+        folded.set_case_insensitive_keys(true);
+        folded.set_dex_fee_bps("Uniswap".to_string(), 30).unwrap();
+        let mut mixed_case = opp;
+        mixed_case.dexes[0] = "UNISWAP".to_string();
+        assert_eq!(folded.filter_opportunities_explained(vec![mixed_case], 0)[0].net_profit_bps, 50);
+    }
+
+    #[test]
+    fn test_required_intermediate_token() {
+        let scanner = TurboScanner::new(50, None, None);
+        let route = |path: &[&str]| Opportunity {
+            path: path.iter().map(|t| t.to_string()).collect(),
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+        let routes = vec![
+            route(&["USDC", "WETH", "DAI", "USDC"]),
+            route(&["WETH", "DAI", "WETH"]),
+            route(&["USDC", "DAI", "USDC"]),
+            route(&["USDC", "weth", "USDC"]),
+        ];
+
+        scanner.set_required_token("WETH".to_string());
+        let outcomes = scanner.filter_opportunities_explained(routes.clone(), 0);
+        let reasons: Vec<&str> = outcomes.iter().map(|o| o.reason.as_str()).collect();
+        // WETH only at the endpoints does not count as passing through it
+        assert_eq!(
+            reasons,
+            vec!["accepted", "missing_required_token", "missing_required_token", "missing_required_token"]
+        );
+
+        scanner.reset();
+        scanner.set_case_insensitive_keys(true);
+        assert_eq!(scanner.filter_opportunities_at(routes.clone(), 0).len(), 2);
+
+        // Empty requirement removes the constraint
+        scanner.reset();
+        scanner.set_required_token(String::new());
+        assert_eq!(scanner.filter_opportunities_at(routes, 0).len(), 4);
+    }
+
+    #[test]
+    fn test_streamed_batch() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |id: &str, profit_bps: i32| Opportunity {
+            path: vec!["A".to_string(), id.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "1".to_string(),
+            profit_bps,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        assert!(scanner.push_chunk(vec![make("a", 100)]).is_err());
+        assert!(scanner.end_batch().is_err());
+
+        scanner.begin_batch_at(1_000).unwrap();
+        assert!(scanner.begin_batch_at(1_000).is_err());
+        scanner.push_chunk(vec![make("a", 100), make("b", 10)]).unwrap();
+        // Dedup applies across chunks of the same batch
+        scanner.push_chunk(vec![make("a", 100), make("c", 60)]).unwrap();
+
+        let ids: Vec<String> = scanner.end_batch().unwrap().into_iter().map(|o| o.path[1].clone()).collect();
+        assert_eq!(ids, vec!["a", "c"]);
+        assert_eq!(scanner.get_scan_count(), 1.0);
+        assert_eq!(*scanner.recent_batches.read().back().unwrap(), (1_000, 1, 4));
+
+        // A new batch can start once the previous one ended
+        scanner.begin_batch_at(2_000).unwrap();
+        assert!(scanner.end_batch().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_self_check() {
+        let scanner = TurboScanner::new(50, None, None);
+        scanner.filter_opportunities_at(Vec::new(), 1_000);
+        scanner.set_top_capacity(1);
+        assert_eq!(scanner.check_invariants(), Ok(()));
+
+        let future = now_ms() + 10 * SELF_CHECK_FUTURE_TOLERANCE_MS;
+        scanner.last_emitted.write().insert("A-B".to_string(), future);
+        assert!(scanner.check_invariants().unwrap_err().starts_with("emit log entry 'A-B'"));
+        let err = scanner.self_check().unwrap_err();
+        assert_eq!(err.status, napi::Status::GenericFailure);
+        assert!(err.reason.starts_with("corrupt state: emit log entry 'A-B'"));
+
+        scanner.reset();
+        scanner.recent_batches.write().push_back((0, 1, 1));
+        assert!(scanner.check_invariants().unwrap_err().contains("scan_count"));
+    }
+
+    #[test]
+    fn test_top_queue_across_batches() {
+        let scanner = TurboScanner::new(50, None, None);
+        scanner.set_top_capacity(3);
+        let make = |id: &str, profit_bps: i32| Opportunity {
+            path: vec!["A".to_string(), id.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "1".to_string(),
+            profit_bps,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        scanner.push_opportunities(vec![make("a", 10), make("b", 50), make("c", 30)]);
+        scanner.push_opportunities(vec![make("d", 40), make("e", 5), make("f", 30)]);
+
+        // a (10) was evicted by d (40); e never got in; f lost the tie to c
+        let ids: Vec<String> = scanner.drain_top().into_iter().map(|o| o.path[1].clone()).collect();
+        assert_eq!(ids, vec!["b", "d", "c"]);
+
+        // Draining empties the queue
+        assert!(scanner.drain_top().is_empty());
+
+        scanner.push_opportunities(vec![make("g", 1), make("h", 2), make("i", 3)]);
+        scanner.set_top_capacity(1);
+        let ids: Vec<String> = scanner.drain_top().into_iter().map(|o| o.path[1].clone()).collect();
+        assert_eq!(ids, vec!["i"]);
+    }
+
+    #[test]
+    fn test_case_insensitive_keys() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |token: &str, dex: &str| Opportunity {
+            path: vec!["0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".to_string(), token.to_string()],
+            dexes: vec![dex.to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+        let checksummed = make("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "UniswapV3");
+        let lowercase = Opportunity {
+            path: checksummed.path.iter().map(|t| t.to_lowercase()).collect(),
+            ..make("", "uniswapv3")
+        };
+
+        // Exact keys by default
+        assert_ne!(
+            scanner.opportunity_key(checksummed.clone()),
+            scanner.opportunity_key(lowercase.clone())
+        );
+
+        scanner.set_case_insensitive_keys(true);
+        assert_eq!(
+            scanner.opportunity_key(checksummed.clone()),
+            scanner.opportunity_key(lowercase.clone())
+        );
+        assert_eq!(scanner.filter_opportunities(vec![checksummed, lowercase]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_timing_opt_in() {
+        let scanner = TurboScanner::new(50, None, None);
+        scanner.filter_opportunities(vec![]).unwrap();
+        assert_eq!(scanner.get_average_duration_us(), 0.0);
+
+        scanner.set_timing_enabled(true);
+        scanner.filter_opportunities(vec![]).unwrap();
+        assert!(scanner.get_last_duration_us() >= 0.0);
+        assert_eq!(scanner.timing.read().recent_len(), 1);
+    }
+
+    #[test]
+    fn test_gossip_seen_between_shards() {
+        let _modes = crate::ModeGuard::acquire();
+        let make = |mid: &str| Opportunity {
+            path: vec!["A".to_string(), mid.to_string(), "A".to_string()],
+            dexes: vec!["dex1".to_string(), "dex2".to_string()],
+            ..opp(mid, 100)
+        };
+
+        let shard_a = TurboScanner::new(50, None, None);
+        let shard_b = TurboScanner::new(50, None, None);
+        assert_eq!(shard_a.filter_opportunities(vec![make("B"), make("C")]).unwrap().len(), 2);
+        assert_eq!(shard_b.filter_opportunities(vec![make("D")]).unwrap().len(), 1);
+
+        let exported = shard_a.export_seen().unwrap();
+        assert_eq!(exported.len(), 2);
+        assert_eq!(shard_b.import_seen(exported.clone()).unwrap(), 2);
+        // Re-importing is a no-op
+        assert_eq!(shard_b.import_seen(exported).unwrap(), 0);
+        assert_eq!(shard_a.import_seen(shard_b.export_seen().unwrap()).unwrap(), 1);
+
+        // Routes first seen on the other shard are now duplicates on both
+        assert_eq!(shard_b.filter_opportunities(vec![make("B"), make("C"), make("E")]).unwrap().len(), 1);
+        assert_eq!(shard_a.filter_opportunities(vec![make("D")]).unwrap().len(), 0);
+
+        // Lightweight mode caps the import at the cache limit, newest keys kept
+        crate::set_lightweight_mode(true);
+        let small = TurboScanner::new(50, None, None);
+        let keys: Vec<String> = (0..LIGHTWEIGHT_SEEN_LIMIT + 10).map(|i| format!("k{}", i)).collect();
+        assert_eq!(small.import_seen(keys).unwrap() as usize, LIGHTWEIGHT_SEEN_LIMIT);
+        assert!(small.seen_opportunities.read().contains_key(&format!("k{}", LIGHTWEIGHT_SEEN_LIMIT + 9)));
+        assert!(!small.seen_opportunities.read().contains_key("k0"));
+        crate::set_lightweight_mode(false);
+
+        // So do explicit and default capacities outside it
+        let sized = TurboScanner::new(50, Some(3), None);
+        let keys: Vec<String> = (0..5).map(|i| format!("k{}", i)).collect();
+        assert_eq!(sized.import_seen(keys).unwrap(), 3);
+        assert_eq!(sized.get_cache_size(), 3);
+        assert_eq!(shard_a.seen_opportunities.read().capacity(), Some(DEFAULT_SEEN_LIMIT));
+    }
+
+    #[test]
+    fn test_preload_seen() {
+        let _modes = crate::ModeGuard::acquire();
+        let scanner = TurboScanner::new(50, None, None);
+        let opp = Opportunity {
+            path: vec!["A".to_string(), "B".to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: "1000".to_string(),
+            output_amount: "1100".to_string(),
+            profit: "100".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        let key = scanner.opportunity_key(opp.clone());
+
+        // Fresh instance seeded with the persisted key treats it as already seen
+        let restarted = TurboScanner::new(50, None, None);
+        assert_eq!(restarted.preload_seen(vec![key.clone(), key]).unwrap(), 1);
+        assert_eq!(restarted.get_scan_count(), 0.0);
+        assert_eq!(restarted.filter_opportunities(vec![opp]).unwrap().len(), 0);
+        assert_eq!(restarted.get_cache_size(), 1);
+
+        // Snapshot before shutdown, restore after
+        let exported = restarted.export_seen().unwrap();
+        let restored = TurboScanner::new(50, None, None);
+        restored.preload_seen(exported.clone()).unwrap();
+        assert_eq!(restored.export_seen().unwrap(), exported);
+
+        // Preloading respects the lightweight cap, keeping the newest keys
+        crate::set_lightweight_mode(true);
+        let capped = TurboScanner::new(50, None, None);
+        let keys: Vec<String> = (0..LIGHTWEIGHT_SEEN_LIMIT + 10).map(|i| format!("key{}", i)).collect();
+        assert_eq!(capped.preload_seen(keys).unwrap(), LIGHTWEIGHT_SEEN_LIMIT as u32);
+        assert_eq!(capped.get_cache_size(), LIGHTWEIGHT_SEEN_LIMIT as u32);
+        let kept = capped.export_seen().unwrap();
+        assert_eq!((kept[0].as_str(), kept.contains(&"key9".to_string())), ("key10", false));
+        crate::set_lightweight_mode(false);
+    }
+
+    #[test]
+    fn test_prefill_seen() {
+        let scanner = TurboScanner::new(50, None, None);
+        let route = opp("B", 100);
+
+        assert_eq!(scanner.prefill_seen(vec![scanner.opportunity_key(route.clone())]).unwrap(), 1);
+        assert_eq!(scanner.get_scan_count(), 0.0);
+        assert_eq!(scanner.filter_opportunities(vec![route]).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_seen_capacity_evicts_least_recently_used() {
+        let scanner = TurboScanner::new(50, Some(3), None);
+        assert_eq!(scanner.filter_opportunities_at(vec![opp("B", 100), opp("C", 100), opp("D", 100)], 1_000).len(), 3);
+        // The (capacity + 1)th key evicts exactly one: the first
+        assert_eq!(scanner.filter_opportunities_at(vec![opp("E", 100)], 1_001).len(), 1);
+        assert_eq!(scanner.get_cache_size(), 3);
+        assert_eq!(scanner.filter_opportunities_at(vec![opp("C", 100), opp("D", 100), opp("E", 100)], 1_002).len(), 0);
+        assert_eq!(scanner.filter_opportunities_at(vec![opp("B", 100)], 1_003).len(), 1);
+
+        // Duplicate hits above refreshed C, D and E in that order, so C went
+        // when B came back
+        assert_eq!(scanner.filter_opportunities_at(vec![opp("D", 100), opp("E", 100), opp("B", 100)], 1_004).len(), 0);
+        assert_eq!(scanner.filter_opportunities_at(vec![opp("C", 100)], 1_005).len(), 1);
+        scanner.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_include_amounts_in_key() {
+        let make = |input: &str| Opportunity {
+            input_amount: input.to_string(),
+            output_amount: "0".to_string(),
+            ..opp("B", 100)
+        };
+        let batch = || vec![make("1000"), make("5000"), make("1000.01"), make("1100")];
+
+        // Route-only keys by default: the other sizes are duplicates
+        assert_eq!(TurboScanner::new(50, None, None).filter_opportunities_at(batch(), 0).len(), 1);
+
+        let sized = TurboScanner::new(50, None, Some(true));
+        let kept: Vec<String> =
+            sized.filter_opportunities_at(batch(), 0).into_iter().map(|o| o.input_amount).collect();
+        assert_eq!(kept, vec!["1000", "5000", "1100"]);
+        assert_eq!(sized.filter_opportunities_at(vec![make("1001")], 1).len(), 0);
+        assert_ne!(sized.opportunity_key(make("abc")), sized.opportunity_key(make("1000")));
+    }
+
+    #[test]
+    fn test_set_min_profit_bps() {
+        let scanner = Arc::new(TurboScanner::new(50, None, None));
+        let make = |id: &str, profit_bps: i32| Opportunity {
+            output_amount: "1003".to_string(),
+            profit: "3".to_string(),
+            ..opp(id, profit_bps)
+        };
+        let batch = || vec![make("B", 30), make("C", 40), make("D", 60)];
+
+        assert_eq!(scanner.filter_opportunities_at(batch(), 0).len(), 1);
+
+        // Lowered from another thread; rejected routes were never recorded
+        let shared = Arc::clone(&scanner);
+        std::thread::spawn(move || shared.set_min_profit_bps(25)).join().unwrap();
+        assert_eq!(scanner.get_min_profit_bps(), 25);
+        let passed: Vec<i32> = scanner.filter_opportunities_at(batch(), 1).into_iter().map(|o| o.profit_bps).collect();
+        assert_eq!(passed, vec![30, 40]);
+    }
+
+    #[test]
+    fn test_try_new_validates() {
+        assert!(TurboScanner::try_new(-50, None, None).is_ok());
+        assert!(TurboScanner::try_new(50, Some(0), None).is_err());
+        let err = TurboScanner::try_new(-20000, None, None).err().unwrap();
+        assert_eq!(err.reason, "invalid min_profit_bps: must be at least -10000, got -20000");
+    }
+
+    #[test]
+    fn test_filter_opportunities_net_of_gas() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |id: &str, profit: &str, hops: usize| Opportunity {
+            dexes: vec!["dex1".to_string(); hops],
+            output_amount: "1010".to_string(),
+            profit: profit.to_string(),
+            ..opp(id, 100)
+        };
+        let kept = |opps: Vec<Opportunity>| -> Vec<String> { opps.into_iter().map(|o| o.path[1].clone()).collect() };
+
+        // 10 profit at 100 bps gross: 6 gas leaves 40 bps, 4 gas leaves 60
+        let batch = vec![make("B", "10", 1), make("C", "10", 1), make("D", "3", 1), make("E", "x", 1)];
+        let net = scanner.filter_opportunities_net_at(batch, "6".to_string(), None, 0).unwrap();
+        assert!(net.is_empty());
+        let net = scanner.filter_opportunities_net_at(vec![make("B", "10", 1)], "4".to_string(), None, 0).unwrap();
+        assert_eq!(kept(net), vec!["B"]);
+        // Gas-rejected routes were never recorded as seen
+        assert!(!scanner.contains(make("C", "10", 1)));
+
+        // Three hops at 0.5x per extra hop pay double gas: 10 - 2 * 2.5 = 5 is exactly 50 bps
+        let multiplier = Some("0.5".to_string());
+        let batch = vec![make("F", "10", 3), make("G", "10", 4)];
+        let net = scanner.filter_opportunities_net_at(batch, "2.5".to_string(), multiplier, 0).unwrap();
+        assert_eq!(kept(net), vec!["F"]);
+
+        assert!(scanner.filter_opportunities_net_at(Vec::new(), "-1".to_string(), None, 0).is_err());
+        assert!(scanner.filter_opportunities_net_at(Vec::new(), "1".to_string(), Some("abc".to_string()), 0).is_err());
+    }
+
+    #[test]
+    fn test_rank_opportunities() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |id: &str, profit_bps: i32, profit: &str| Opportunity { profit: profit.to_string(), ..opp(id, profit_bps) };
+        let ids = |opps: Vec<Opportunity>| -> Vec<String> { opps.into_iter().map(|o| o.path[1].clone()).collect() };
+
+        let batch = vec![
+            make("B", 60, "6"),
+            make("C", 90, "9"),
+            make("D", 10, "1"),                      // below threshold
+            make("E", 90, "9.000000000000000000001"), // beats C on exact profit
+            make("F", 75, "7.5"),
+        ];
+        assert_eq!(ids(scanner.rank_opportunities(batch.clone(), None).unwrap()), vec!["E", "C", "F", "B"]);
+
+        // Everything is now a duplicate; a fresh scanner truncates to top_n
+        assert!(scanner.rank_opportunities(batch.clone(), None).unwrap().is_empty());
+        let fresh = TurboScanner::new(50, None, None);
+        assert_eq!(ids(fresh.rank_opportunities(batch, Some(2)).unwrap()), vec!["E", "C"]);
+    }
+
+    #[test]
+    fn test_filter_with_reasons() {
+        let _modes = crate::ModeGuard::acquire();
+        let scanner = TurboScanner::new(50, None, None);
+        let decisions = scanner.filter_with_reasons(vec![opp("B", 100), opp("C", 10), opp("B", 100)]).unwrap();
+        let summary: Vec<(bool, &str)> = decisions.iter().map(|d| (d.accepted, d.reason.as_str())).collect();
+        assert_eq!(summary, vec![(true, "accepted"), (false, "below_threshold"), (false, "duplicate")]);
+        assert_eq!(scanner.get_scan_count(), 1.0);
+
+        crate::set_deterministic(true);
+        assert!(scanner.filter_with_reasons(vec![opp("D", 100)]).is_err());
+        crate::set_deterministic(false);
+    }
+
+    #[test]
+    fn test_explained_reports_signed_net_profit() {
+        let scanner = TurboScanner::new(50, None, None);
+        scanner.set_linear_impact(1.0);
+
+        let make = |route: &str, input: &str, profit_bps: i32| Opportunity {
+            path: vec!["A".to_string(), route.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: input.to_string(),
+            output_amount: "0".to_string(),
+            profit: "0".to_string(),
+            profit_bps,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        let outcomes = scanner.filter_opportunities_explained(
+            vec![
+                make("B", "10", 100),   // 90 net
+                make("B", "10", 100),   // duplicate
+                make("C", "52", 50),    // -2 net: barely missed
+                make("D", "900", 100),  // -800 net: deeply unprofitable
+                make("E", "0", -30),    // already negative before costs
+            ],
+            0,
+        );
+
+        let summary: Vec<(bool, &str, i32)> = outcomes
+            .iter()
+            .map(|o| (o.accepted, o.reason.as_str(), o.net_profit_bps))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (true, "accepted", 90),
+                (false, "duplicate", 90),
+                (false, "below_threshold", -2),
+                (false, "below_threshold", -800),
+                (false, "below_threshold", -30),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_impact_models_monotonic() {
+        let models = [
+            ImpactModel::Linear { bps_per_unit: 0.5 },
+            ImpactModel::SqrtK { k: 2.0 },
+            ImpactModel::ConstantProduct { reserve: 1000.0 },
+        ];
+
+        for model in models {
+            let mut last = model.impact_bps(0.0);
+            assert_eq!(last, 0.0);
+            for size in [1.0, 10.0, 100.0, 1000.0, 10000.0] {
+                let impact = model.impact_bps(size);
+                assert!(impact > last, "{:?} not increasing at {}", model, size);
+                last = impact;
+            }
+        }
+
+        // Constant product impact approaches but never exceeds 100%
+        let cp = ImpactModel::ConstantProduct { reserve: 1000.0 };
+        assert_eq!(cp.impact_bps(1000.0), 5000.0);
+        assert!(cp.impact_bps(1e12) <= 10000.0);
+    }
+
+    #[test]
+    fn test_impact_discounts_profit() {
+        let scanner = TurboScanner::new(50, None, None);
+        scanner.set_linear_impact(0.1);
+
+        let make = |input: &str, route: &str| Opportunity {
+            path: vec!["A".to_string(), route.to_string()],
+            dexes: vec!["dex1".to_string()],
+            input_amount: input.to_string(),
+            output_amount: "0".to_string(),
+            profit: "0".to_string(),
+            profit_bps: 100,
+            timestamp: 0,
+            liquidity: None,
+            price_timestamp: None,
+        };
+
+        // 100 units -> 10 bps impact, 90 bps net passes
+        assert_eq!(scanner.filter_opportunities(vec![make("100", "B")]).unwrap().len(), 1);
+        // 600 units -> 60 bps impact, 40 bps net is below threshold
+        assert_eq!(scanner.filter_opportunities(vec![make("600", "C")]).unwrap().len(), 0);
+    }
+    #[test]
+    fn test_event_sink_reports_after_releasing_locks() {
+        let scanner = TurboScanner::new(50, Some(1), None);
+        let key_b = scanner.opportunity_key(opp("B", 100));
+
+        let events = Arc::new(RwLock::new(Vec::new()));
+        let sink_events = events.clone();
+        let seen = scanner.seen_opportunities.clone();
+        scanner.set_event_sink(Some(Arc::new(move |event: ScanEvent| {
+            // Would deadlock if dispatched while the filter holds the dedup lock
+            let cached = seen.write().len();
+            sink_events.write().push((event.kind, event.key, cached));
+        })));
+
+        scanner.filter_opportunities_at(vec![opp("B", 100), opp("B", 100), opp("C", 100)], 1_000);
+        assert_eq!(
+            *events.read(),
+            vec![
+                ("duplicate".to_string(), key_b.clone(), 1),
+                ("eviction".to_string(), key_b, 1),
+            ]
+        );
+
+        scanner.off_event();
+        scanner.filter_opportunities_at(vec![opp("C", 100), opp("D", 100)], 1_001);
+        assert_eq!(events.read().len(), 2);
+    }
+    #[test]
+    fn test_scan_one() {
+        let scanner = TurboScanner::new(50, None, None);
+        assert!(scanner.scan_one_at(opp("B", 100), 1_000).is_some());
+        // Second time should be filtered out (duplicate)
+        assert!(scanner.scan_one_at(opp("B", 100), 1_001).is_none());
+        assert!(scanner.scan_one_at(opp("C", 10), 1_002).is_none());
+        assert_eq!(scanner.get_scan_count(), 3.0);
+
+        // Shares the dedup set with the batch path
+        assert!(scanner.filter_opportunities_at(vec![opp("B", 100)], 1_003).is_empty());
+        assert_eq!(scanner.filter_opportunities_at(vec![opp("D", 100)], 1_004).len(), 1);
+        assert!(scanner.scan_one_at(opp("D", 100), 1_005).is_none());
+    }
+
+    #[test]
+    fn test_route_validation() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |path: &[&str], dexes: usize| Opportunity {
+            path: path.iter().map(|t| t.to_string()).collect(),
+            dexes: (0..dexes).map(|i| format!("dex{}", i)).collect(),
+            ..opp("B", 100)
+        };
+        let reasons = |outcomes: Vec<FilterOutcome>| outcomes.into_iter().map(|o| o.reason).collect::<Vec<_>>();
+
+        let batch = vec![
+            make(&["A", "B", "A"], 2),
+            make(&["A", "B", "C"], 1),
+            make(&["A", "B", "C"], 3),
+            make(&["A", "B", "C"], 2),
+            make(&[], 0),
+        ];
+        assert_eq!(
+            reasons(scanner.filter_opportunities_validated_at(batch.clone(), Some(true), 1_000)),
+            vec!["accepted", "hop_mismatch", "hop_mismatch", "open_cycle", "hop_mismatch"]
+        );
+
+        // Rejected routes were not recorded; without the cycle rule the open one passes
+        assert_eq!(
+            reasons(scanner.filter_opportunities_validated_at(batch.clone(), None, 1_001)),
+            vec!["duplicate", "hop_mismatch", "hop_mismatch", "accepted", "hop_mismatch"]
+        );
+
+        // Opt-in: the plain filter lets malformed routes through
+        assert_eq!(scanner.filter_opportunities_at(vec![make(&["X", "Y"], 3)], 1_002).len(), 1);
+    }
+
+    #[test]
+    fn test_key_strategies() {
+        let make = |dex: &str, timestamp: i64| Opportunity {
+            path: vec!["A".to_string(), "B".to_string(), "A".to_string()],
+            dexes: vec![dex.to_string(), "dex2".to_string()],
+            timestamp,
+            ..opp("B", 100)
+        };
+        let accepted = |scanner: &TurboScanner, batch: Vec<Opportunity>| scanner.filter_opportunities_at(batch, 0).len();
+
+        // Default: the same route later is still a duplicate, another DEX is not
+        let default = TurboScanner::new(50, None, None);
+        assert_eq!(default.get_key_strategy(), KeyStrategy::PathAndDexes);
+        assert_eq!(accepted(&default, vec![make("dex1", 0), make("dex1", 60_000), make("dex3", 0)]), 2);
+
+        let path_only = TurboScanner::with_key_strategy(50, None, None, KeyStrategy::PathOnly);
+        assert_eq!(accepted(&path_only, vec![make("dex1", 0), make("dex3", 0)]), 1);
+
+        // Re-fires once the timestamp crosses into the next 10s bucket
+        let bucketed = TurboScanner::new_with_key_strategy(50, None, None, "path_dexes_time".to_string(), Some(10_000)).unwrap();
+        assert_eq!(accepted(&bucketed, vec![make("dex1", 1_000), make("dex1", 9_999), make("dex1", 10_000)]), 2);
+        assert_eq!(accepted(&bucketed, vec![make("dex1", 19_999), make("dex1", -1)]), 1);
+
+        assert!(TurboScanner::new_with_key_strategy(50, None, None, "path_dexes_time".to_string(), None).is_err());
+        assert!(TurboScanner::new_with_key_strategy(50, None, None, "path_dexes_time".to_string(), Some(0)).is_err());
+        assert!(TurboScanner::new_with_key_strategy(50, None, None, "route".to_string(), None).is_err());
+        let path = TurboScanner::new_with_key_strategy(50, None, None, "path".to_string(), None).unwrap();
+        assert_eq!(path.get_key_strategy(), KeyStrategy::PathOnly);
+    }
+
+    #[test]
+    fn test_recompute_profit_bps() {
+        let make = |id: &str, input: &str, output: &str, profit_bps: i32| Opportunity {
+            input_amount: input.to_string(),
+            output_amount: output.to_string(),
+            profit: "0".to_string(),
+            ..opp(id, profit_bps)
+        };
+        // B claims 500 bps but really makes 10; C claims 0 but makes 100
+        let batch = || vec![make("B", "1000", "1001", 500), make("C", "1000", "1010", 0)];
+
+        let trusting = TurboScanner::new(50, None, None);
+        let kept: Vec<String> = trusting.filter_opportunities_at(batch(), 0).into_iter().map(|o| o.path[1].clone()).collect();
+        assert_eq!(kept, vec!["B"]);
+
+        let checked = TurboScanner::new(50, None, None);
+        checked.set_recompute_profit_bps(true, None);
+        let kept = checked.filter_opportunities_at(batch(), 0);
+        assert_eq!((kept[0].path[1].as_str(), kept[0].profit_bps), ("C", 0));
+        assert_eq!(kept.len(), 1);
+
+        let overwriting = TurboScanner::new(50, None, None);
+        overwriting.set_recompute_profit_bps(true, Some(true));
+        let kept = overwriting.filter_opportunities_at(batch(), 0);
+        assert_eq!((kept[0].path[1].as_str(), kept[0].profit_bps), ("C", 100));
+
+        let outcomes = overwriting.filter_opportunities_explained(
+            vec![make("D", "0", "10", 500), make("E", "abc", "10", 500), make("F", "1000", "x", 500), make("G", "1000", "999", 500)],
+            1,
+        );
+        let reasons: Vec<(String, i32)> = outcomes.into_iter().map(|o| (o.reason, o.net_profit_bps)).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("invalid_amounts".to_string(), 500),
+                ("invalid_amounts".to_string(), 500),
+                ("invalid_amounts".to_string(), 500),
+                ("below_threshold".to_string(), -10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slippage() {
+        assert_eq!(apply_slippage("1000".to_string(), 30).unwrap(), "997");
+        assert_eq!(apply_slippage("1.5".to_string(), 0).unwrap(), "1.5");
+        assert_eq!(apply_slippage("1000".to_string(), 10_000).unwrap(), "0");
+        assert!(apply_slippage("1000".to_string(), -1).is_err());
+        assert!(apply_slippage("1000".to_string(), 10_001).is_err());
+        assert!(apply_slippage("-5".to_string(), 10).is_err());
+        assert!(apply_slippage("abc".to_string(), 10).is_err());
+
+        // 1.2% gross: clears 50 bps until 80 bps of slippage eats the margin
+        let make = |id: &str| Opportunity {
+            output_amount: "1012".to_string(),
+            profit: "12".to_string(),
+            ..opp(id, 120)
+        };
+        let scanner = TurboScanner::new(50, None, None);
+        // 1012 * 0.993 = 1004.916 -> 49 bps
+        assert!(scanner.filter_opportunities_slippage_at(vec![make("B")], 70, 0).unwrap().is_empty());
+        let kept = scanner.filter_opportunities_slippage_at(vec![make("B")], 20, 0).unwrap();
+        assert_eq!(kept[0].profit_bps, 100);
+        // Rejected ones were not recorded as seen
+        assert!(scanner.filter_opportunities_slippage_at(vec![make("C")], 10_000, 0).unwrap().is_empty());
+        assert_eq!(scanner.filter_opportunities_slippage_at(vec![make("C")], 0, 1).unwrap().len(), 1);
+        assert!(scanner.filter_opportunities_slippage_at(vec![make("D")], 20_000, 2).is_err());
+    }
+
+    #[test]
+    fn test_slippage_with_recomputed_bps() {
+        let make = |id: &str| Opportunity {
+            output_amount: "1012".to_string(),
+            profit: "12".to_string(),
+            ..opp(id, 120)
+        };
+        let scanner = TurboScanner::new(50, None, None);
+        scanner.set_recompute_profit_bps(true, Some(true));
+
+        // Recomputing from the amounts still applies the slippage: 49 bps
+        assert!(scanner.filter_opportunities_slippage_at(vec![make("B")], 70, 0).unwrap().is_empty());
+        let kept = scanner.filter_opportunities_slippage_at(vec![make("C")], 20, 1).unwrap();
+        assert_eq!(kept[0].profit_bps, 100);
+    }
+
+    #[test]
+    fn test_bps_basis() {
+        // 100 profit: 1000 bps of the input, 909 of the output
+        let make = |id: &str, output: &str| Opportunity { output_amount: output.to_string(), ..opp(id, 0) };
+        let scanner = TurboScanner::new(950, None, None);
+        assert_eq!(scanner.get_bps_basis(), BpsBasis::Input);
+        scanner.set_recompute_profit_bps(true, Some(true));
+        let kept = scanner.filter_opportunities_at(vec![make("B", "1100")], 0);
+        assert_eq!(kept[0].profit_bps, 1000);
+        assert_eq!(scanner.filter_opportunities_net_at(vec![make("C", "1100")], "0".to_string(), None, 0).unwrap().len(), 1);
+
+        let output_based = TurboScanner::with_bps_basis(950, None, None, BpsBasis::Output);
+        output_based.set_recompute_profit_bps(true, Some(true));
+        assert!(output_based.filter_opportunities_at(vec![make("B", "1100")], 0).is_empty());
+
+        scanner.set_output_bps_basis(true);
+        assert_eq!(scanner.get_bps_basis(), BpsBasis::Output);
+        let outcomes = scanner.filter_opportunities_explained(vec![make("D", "1100"), make("E", "0")], 1);
+        let reasons: Vec<(&str, i32)> = outcomes.iter().map(|o| (o.reason.as_str(), o.net_profit_bps)).collect();
+        assert_eq!(reasons, vec![("below_threshold", 909), ("invalid_amounts", 0)]);
+        assert!(scanner.filter_opportunities_net_at(vec![make("F", "1100")], "0".to_string(), None, 1).unwrap().is_empty());
+        assert!(scanner.filter_opportunities_net_at(vec![make("G", "0")], "0".to_string(), None, 1).unwrap().is_empty());
+
+        // Slippage shrinks the basis too: 1100 * 0.5 = 550, (550 - 1000) / 550
+        scanner.set_recompute_profit_bps(false, None);
+        scanner.set_min_profit_bps(-10_000);
+        let slipped = scanner.filter_opportunities_slippage_at(vec![make("H", "1100")], 5_000, 2).unwrap();
+        assert_eq!(slipped[0].profit_bps, -8182);
+    }
+
+    #[test]
+    fn test_scan_rate() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |i: i64| opp(&format!("T{}", i), 100);
+        assert_eq!(scanner.scan_rate(1_000, 0), 0.0);
+
+        // One scan every 40ms from t=40 to t=4000: 25/s, whatever the batch size
+        for i in 1..=100 {
+            let batch = (0..i % 3).map(|j| make(i * 3 + j)).collect();
+            scanner.filter_opportunities_at(batch, i * 40);
+        }
+        assert_eq!(scanner.get_scan_count(), 100.0);
+        assert!((scanner.scan_rate(2_000, 4_000) - 25.0).abs() < 1e-9);
+        assert!((scanner.scan_rate(1_000, 4_000) - 25.0).abs() < 1e-9);
+
+        // Feed stall: nothing in the last second
+        assert!((scanner.scan_rate(2_000, 5_000) - 12.5).abs() < 1e-9);
+        assert_eq!(scanner.scan_rate(1_000, 5_000), 0.0);
+        assert_eq!(scanner.scan_rate(0, 4_000), 0.0);
+
+        // Rates well past a few hundred scans per window stay exact
+        for i in 101..=2_600 {
+            scanner.filter_opportunities_at(Vec::new(), i * 40);
+        }
+        assert_eq!(scanner.get_scan_count(), 2_600.0);
+        assert!((scanner.scan_rate(60_000, 104_000) - 25.0).abs() < 1e-9);
+        assert!(scanner.scan_rate(60_001, 104_000).is_nan());
+
+        // The ring only keeps the retention window, one entry per millisecond
+        for _ in 0..500 {
+            scanner.filter_opportunities_at(Vec::new(), 200_000);
+        }
+        assert_eq!(*scanner.recent_batches.read().back().unwrap(), (200_000, 500, 0));
+        assert_eq!(scanner.recent_batches.read().len(), 1);
+        assert_eq!(scanner.scan_rate(1_000, 200_000), 500.0);
+    }
+
+    #[test]
+    fn test_filter_opportunities_dry() {
+        let scanner = TurboScanner::new(50, None, None);
+        scanner.set_min_emit_interval_ms(1_000);
+        scanner.filter_opportunities_at(vec![opp("seen", 100)], 0);
+        let (size, scans) = (scanner.get_cache_size(), scanner.get_scan_count());
+
+        let batch = vec![opp("seen", 100), opp("new", 100), opp("new", 100), opp("low", 10), opp("other", 100)];
+        let ids = |opps: Vec<Opportunity>| opps.into_iter().map(|o| o.path[1].clone()).collect::<Vec<_>>();
+        let first = ids(scanner.filter_opportunities_dry(batch.clone(), 10));
+        let second = ids(scanner.filter_opportunities_dry(batch.clone(), 10));
+        assert_eq!(first, vec!["new", "other"]);
+        assert_eq!(first, second);
+        assert_eq!((scanner.get_cache_size(), scanner.get_scan_count()), (size, scans));
+
+        // Read locks only, so it runs alongside other readers; no timing
+        scanner.set_timing_enabled(true);
+        {
+            let _seen = scanner.seen_opportunities.read();
+            let _emitted = scanner.last_emitted.read();
+            assert_eq!(ids(scanner.filter_opportunities_dry(batch.clone(), 10)), first);
+        }
+        assert_eq!(scanner.timing.read().recent_len(), 0);
+        scanner.set_timing_enabled(false);
+
+        // And it matches what a live run then does
+        assert_eq!(ids(scanner.filter_opportunities_at(batch.clone(), 10)), first);
+        assert!(scanner.filter_opportunities_dry(batch, 10).is_empty());
+    }
+
+    #[test]
+    fn test_min_profit_absolute() {
+        let scanner = TurboScanner::new(50, None, None);
+        let make = |id: &str, input: &str, profit: &str, profit_bps: i32| Opportunity {
+            input_amount: input.to_string(),
+            output_amount: "0".to_string(),
+            profit: profit.to_string(),
+            ..opp(id, profit_bps)
+        };
+        assert!(scanner.set_min_profit_absolute(Some("five".to_string())).is_err());
+        scanner.set_min_profit_absolute(Some("5.00".to_string())).unwrap();
+        assert_eq!(scanner.get_min_profit_absolute().as_deref(), Some("5"));
+
+        let outcomes = scanner.filter_opportunities_explained(
+            vec![
+                // 1000 bps on 20 USDC is only 2 USDC
+                make("tiny", "20", "2", 1000),
+                make("big", "1000", "5", 50),
+                make("thin", "100000", "80", 8),
+                make("garbled", "1000", "n/a", 100),
+            ],
+            0,
+        );
+        let reasons: Vec<&str> = outcomes.iter().map(|o| o.reason.as_str()).collect();
+        assert_eq!(reasons, vec!["below_absolute_threshold", "accepted", "below_threshold", "invalid_amounts"]);
+
+        // Without the floor only the bps check applies
+        scanner.set_min_profit_absolute(None).unwrap();
+        assert!(scanner.get_min_profit_absolute().is_none());
+        let kept = scanner.filter_opportunities_at(vec![make("tiny", "20", "2", 1000), make("garbled", "1000", "n/a", 100)], 1);
+        assert_eq!(kept.len(), 2);
     }
 }
@@ -0,0 +1,63 @@
+// Benchmark: aggregatePricesBytes (one Buffer) vs aggregatePrices (one object per price)
+// Backs the cost notes in rust-engine/src/price_codec.rs
+//
+// Run after building the native module: node scripts/benchmark-price-codec.js
+
+const { TurboAggregator, pricesToBytes } = require('../rust-engine');
+
+const BATCH_SIZES = [100, 1000, 10000];
+const ROUNDS = 50;
+
+// Distinct pairs and timestamps so the dedup window keeps every price
+function generatePrices(count, round) {
+  const prices = [];
+  for (let i = 0; i < count; i++) {
+    prices.push({
+      tokenA: `T${i}`,
+      tokenB: 'USDC',
+      price: (100 + (i % 97) / 7).toFixed(6),
+      source: `dex${i % 3}`,
+      timestamp: 1000 + round
+    });
+  }
+  return prices;
+}
+
+// Mean milliseconds per call; a fresh aggregator each round so dedup never drops the batch
+function timePerCall(inputs, call) {
+  call(new TurboAggregator(10000), inputs[0]);
+  let total = 0n;
+  for (let round = 1; round <= ROUNDS; round++) {
+    const aggregator = new TurboAggregator(10000);
+    const start = process.hrtime.bigint();
+    call(aggregator, inputs[round]);
+    total += process.hrtime.bigint() - start;
+  }
+  return Number(total) / ROUNDS / 1e6;
+}
+
+function runBenchmark() {
+  console.log('PRICE CODEC BENCHMARK: aggregatePrices vs aggregatePricesBytes');
+  console.log('-'.repeat(70));
+
+  for (const size of BATCH_SIZES) {
+    const objects = [];
+    for (let round = 0; round <= ROUNDS; round++) {
+      objects.push(generatePrices(size, round));
+    }
+    const buffers = objects.map(batch => pricesToBytes(batch));
+
+    const objectMs = timePerCall(objects, (aggregator, batch) => aggregator.aggregatePrices(batch, 2000));
+    const bufferMs = timePerCall(buffers, (aggregator, bytes) => aggregator.aggregatePricesBytes(bytes, 2000));
+    // The Buffer built from objects in the same call, i.e. pricesToBytes then aggregatePricesBytes
+    const encodeMs = timePerCall(objects, (aggregator, batch) => aggregator.aggregatePricesBytes(pricesToBytes(batch), 2000));
+
+    console.log(
+      `  ${String(size).padStart(6)} prices: objects ${objectMs.toFixed(3)}ms, ` +
+      `prebuilt buffer ${bufferMs.toFixed(3)}ms (${(objectMs / bufferMs).toFixed(2)}x), ` +
+      `encode + buffer ${encodeMs.toFixed(3)}ms (${(objectMs / encodeMs).toFixed(2)}x)`
+    );
+  }
+}
+
+runBenchmark();
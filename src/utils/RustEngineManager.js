@@ -129,7 +129,7 @@ class RustEngineManager {
     }
 
     try {
-      return this.deduplicator.checkAndAdd(key);
+      return this.deduplicator.checkAndAdd(key, Date.now());
     } catch (error) {
       logger.error('Error in duplicate check', { error: error.message });
       return false;